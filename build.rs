@@ -0,0 +1,28 @@
+use std::{env, fs, path::Path};
+
+#[path = "src/codegen.rs"]
+mod codegen;
+
+use codegen::Query;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/codegen.rs");
+
+    let queries = [
+        // Q(a, b, c) <- R(a, b), S(b, c), T(a, c)
+        Query::new(
+            "triangles",
+            &[("r", &[0, 1]), ("s", &[1, 2]), ("t", &[0, 2])],
+            3,
+        ),
+    ];
+
+    let mut out = String::new();
+    for query in &queries {
+        out.push_str(&codegen::generate(query));
+        out.push('\n');
+    }
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("compiled.rs");
+    fs::write(path, out).unwrap();
+}
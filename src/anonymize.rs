@@ -0,0 +1,26 @@
+// Scrubbing a graph for sharing, e.g. attached to a bug report: only the
+// structure is kept. Every vertex gets a new id in `0..n`, in the order of a
+// salted hash of its old one, so without the salt nothing about the old ids
+// (not even their order) survives; attributes such as weights are dropped;
+// and the edges come out sorted by their new ids, so neither does the order
+// of the original file.
+
+use std::collections::HashMap;
+
+use crate::index::mix;
+
+pub fn anonymize(edges: &[(u64, u64)], salt: u64) -> Vec<(u64, u64)> {
+    let hash = |u: u64| mix(mix(salt) ^ u);
+    let mut vertices: Vec<u64> = edges.iter().flat_map(|&(u, v)| [u, v]).collect();
+    vertices.sort_unstable();
+    vertices.dedup();
+    vertices.sort_unstable_by_key(|&u| hash(u));
+    let new_id: HashMap<u64, u64> = vertices
+        .into_iter()
+        .enumerate()
+        .map(|(i, u)| (u, i as u64))
+        .collect();
+    let mut scrubbed: Vec<(u64, u64)> = edges.iter().map(|(u, v)| (new_id[u], new_id[v])).collect();
+    scrubbed.sort_unstable();
+    scrubbed
+}
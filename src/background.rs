@@ -0,0 +1,92 @@
+// Futures for running counts and enumerations off the caller's thread, for
+// embedding in async applications (like a server) whose runtime mustn't be
+// blocked for the length of a query. Each one runs its work on a thread of its
+// own and wakes whoever is awaiting it when done; nothing here depends on a
+// particular runtime.
+
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use crate::{batch, index::Adjacency, sink};
+
+// The result of some work running on another thread.
+pub struct Background<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+struct Shared<T> {
+    // The work's result, or the panic it ended in.
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+// Start running `f` on a new thread.
+pub fn spawn<T, F>(f: F) -> Background<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let shared = Arc::new(Mutex::new(Shared {
+        result: None,
+        waker: None,
+    }));
+    let theirs = shared.clone();
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(f));
+        let mut shared = theirs.lock().unwrap();
+        shared.result = Some(result);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    });
+    Background { shared }
+}
+
+impl<T> Future for Background<T> {
+    type Output = T;
+
+    // A panic in the work is passed on to whoever awaits it.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.result.take() {
+            Some(Ok(value)) => Poll::Ready(value),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// Count the triangles (a, b, c) of R(a, b), S(b, c), T(a, c) over `data`.
+pub fn count_triangles(data: Arc<Adjacency>) -> Background<u64> {
+    spawn(move || batch::count_triangles(&data, batch::DEFAULT_BATCH_SIZE))
+}
+
+// List the triangles (a, b, c) of R(a, b), S(b, c), T(a, c) over `data`.
+pub fn triangles(data: Arc<Adjacency>) -> Background<Vec<[u64; 3]>> {
+    spawn(move || {
+        let mut triangles = Vec::new();
+        sink::enumerate(&data, &mut triangles);
+        triangles
+    })
+}
+
+// Send the triangles (a, b, c) of R(a, b), S(b, c), T(a, c) over `data` to
+// `sink`, which is handed back once the enumeration is done.
+pub fn enumerate<S>(data: Arc<Adjacency>, mut sink: S) -> Background<S>
+where
+    S: sink::ResultSink + Send + 'static,
+{
+    spawn(move || {
+        sink::enumerate(&data, &mut sink);
+        sink
+    })
+}
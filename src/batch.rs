@@ -0,0 +1,55 @@
+// A batch-at-a-time executor for the triangle query. Rather than walking one
+// binding at a time down through every level like the leapfrog loop nest, it
+// first materializes a column of (a, b) bindings for many outer values at
+// once and then runs the innermost intersection over the whole column. The
+// per-binding work is then a tight loop over slices, which amortizes the cost
+// of moving between levels and leaves the innermost step free to be
+// vectorized.
+
+use crate::{index::Adjacency, intersect};
+
+// How many (a, b) bindings to gather before running the c-level over them.
+pub const DEFAULT_BATCH_SIZE: usize = 4096;
+
+// Q(a, b, c) <- R(a, b), S(b, c), T(a, c), with all three atoms over `data`.
+pub fn count_triangles(data: &Adjacency, batch_size: usize) -> u64 {
+    let batch_size = batch_size.max(1);
+    // The column of bindings: for each (a, b), the positions in `data` of a's
+    // and b's neighbor lists.
+    let mut a_col: Vec<usize> = Vec::with_capacity(batch_size);
+    let mut b_col: Vec<usize> = Vec::with_capacity(batch_size);
+    let mut count = 0;
+
+    for (i, (_, a_neighbors)) in data.iter().enumerate() {
+        // Bind b: every neighbor of a which has neighbors of its own. Both
+        // lists are sorted, so the matching entries in `data` are found by
+        // seeking forward from the last one.
+        let mut pos = 0;
+        for &b in a_neighbors {
+            pos += data[pos..].partition_point(|(v, _)| *v < b);
+            if pos == data.len() {
+                break;
+            }
+            if data[pos].0 == b {
+                a_col.push(i);
+                b_col.push(pos);
+                if a_col.len() == batch_size {
+                    count += flush(data, &mut a_col, &mut b_col);
+                }
+            }
+        }
+    }
+    count + flush(data, &mut a_col, &mut b_col)
+}
+
+// Bind c for every (a, b) in the batch, and empty it.
+fn flush(data: &Adjacency, a_col: &mut Vec<usize>, b_col: &mut Vec<usize>) -> u64 {
+    let count = a_col
+        .iter()
+        .zip(b_col.iter())
+        .map(|(&i, &j)| intersect::count(&data[i].1, &data[j].1))
+        .sum();
+    a_col.clear();
+    b_col.clear();
+    count
+}
@@ -0,0 +1,68 @@
+// A plain uncompressed bitset over vertex ids, for neighbor sets dense enough
+// that probing beats merging.
+pub struct Bitmap {
+    words: Vec<u64>,
+    // The words from the first with a bit set to just past the last, which
+    // is all an intersection with another bitmap has to look at.
+    span: (usize, usize),
+}
+
+impl Bitmap {
+    // A bitmap with room for ids `0..universe`, holding the given values.
+    pub fn from_sorted(values: &[u64], universe: u64) -> Self {
+        let mut words = vec![0_u64; universe.div_ceil(64) as usize];
+        for &v in values {
+            words[(v / 64) as usize] |= 1 << (v % 64);
+        }
+        let span = match (values.first(), values.last()) {
+            (Some(&lo), Some(&hi)) => ((lo / 64) as usize, (hi / 64) as usize + 1),
+            _ => (0, 0),
+        };
+        Self { words, span }
+    }
+
+    pub fn contains(&self, v: u64) -> bool {
+        self.words
+            .get((v / 64) as usize)
+            .is_some_and(|w| w & (1 << (v % 64)) != 0)
+    }
+
+    // How many of the given values are in the set.
+    pub fn count_in(&self, values: &[u64]) -> u64 {
+        values.iter().filter(|&&v| self.contains(v)).count() as u64
+    }
+
+    // How many words `count_common` with `other` would have to AND.
+    pub fn overlap_words(&self, other: &Bitmap) -> usize {
+        let lo = self.span.0.max(other.span.0);
+        let hi = self.span.1.min(other.span.1);
+        hi.saturating_sub(lo)
+    }
+
+    // How many values the two sets have in common, by ANDing the words where
+    // both have bits set and counting the bits of the result, without ever
+    // looking at the values themselves. The words go a block at a time, so
+    // that the compiler can keep several popcounts in flight.
+    pub fn count_common(&self, other: &Bitmap) -> u64 {
+        let lo = self.span.0.max(other.span.0);
+        let hi = self.span.1.min(other.span.1);
+        if lo >= hi {
+            return 0;
+        }
+        let (a, b) = (&self.words[lo..hi], &other.words[lo..hi]);
+        let mut blocks_a = a.chunks_exact(8);
+        let mut blocks_b = b.chunks_exact(8);
+        let mut count = 0;
+        for (x, y) in (&mut blocks_a).zip(&mut blocks_b) {
+            let mut block = 0;
+            for k in 0..8 {
+                block += (x[k] & y[k]).count_ones();
+            }
+            count += block as u64;
+        }
+        for (x, y) in blocks_a.remainder().iter().zip(blocks_b.remainder()) {
+            count += (x & y).count_ones() as u64;
+        }
+        count
+    }
+}
@@ -0,0 +1,101 @@
+// Caching of query results, for callers (like dashboards) that issue the same
+// queries against the same data over and over. Results are keyed by the
+// content of the relations involved rather than by their identity, so a
+// rebuilt but unchanged graph still hits the cache.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::join::{self, Atom, InvalidQuery};
+
+// A query in a canonical form: each atom's relation replaced by its content
+// hash, with the atoms in sorted order and duplicates removed (repeating an
+// atom never changes the result of a join).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct QueryKey {
+    atoms: Vec<(u64, Vec<usize>, bool)>,
+    num_vars: usize,
+}
+
+impl QueryKey {
+    // The key for a query, or None if any of its relations can't provide a
+    // content hash.
+    pub fn new(atoms: &[Atom], num_vars: usize) -> Option<Self> {
+        let mut key: Vec<(u64, Vec<usize>, bool)> = atoms
+            .iter()
+            .map(|a| Some((a.relation.content_hash()?, a.vars.clone(), a.negated)))
+            .collect::<Option<_>>()?;
+        key.sort();
+        key.dedup();
+        Some(Self {
+            atoms: key,
+            num_vars,
+        })
+    }
+}
+
+// A bounded cache of counts. Once full, the oldest entry is evicted first.
+pub struct ResultCache {
+    capacity: usize,
+    entries: HashMap<QueryKey, u64>,
+    order: VecDeque<QueryKey>,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl ResultCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn get(&self, key: &QueryKey) -> Option<u64> {
+        self.entries.get(key).copied()
+    }
+
+    pub fn insert(&mut self, key: QueryKey, count: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.insert(key.clone(), count).is_none() {
+            self.order.push_back(key);
+            if self.order.len() > self.capacity {
+                let oldest = self.order.pop_front().unwrap();
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    // Like `join::count`, but answered from the cache when the same query has
+    // already been run over the same data.
+    pub fn count(&mut self, atoms: &[Atom], num_vars: usize) -> Result<u64, InvalidQuery> {
+        let Some(key) = QueryKey::new(atoms, num_vars) else {
+            return join::count(atoms, num_vars);
+        };
+        if let Some(count) = self.get(&key) {
+            self.hits += 1;
+            return Ok(count);
+        }
+        self.misses += 1;
+        let count = join::count(atoms, num_vars)?;
+        self.insert(key, count);
+        Ok(count)
+    }
+}
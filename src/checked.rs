@@ -0,0 +1,92 @@
+// A wrapper around a trie iterator that checks, in debug builds, that it's
+// being driven the way Leapfrog Triejoin expects. The iterators themselves
+// trust their callers (seeking backwards or stepping down from the end of a
+// level just gives wrong answers), so misuse by an executor would otherwise
+// show up only as a bad count somewhere far away. In release builds, stepping
+// up from the top or down from the bottom does nothing, like the iterators.
+
+use crate::index::TrieIterator;
+
+pub struct Checked<'a> {
+    inner: Box<dyn TrieIterator + 'a>,
+    // How many levels the trie has, and which one we're at (0 is the top).
+    levels: usize,
+    depth: usize,
+}
+
+impl<'a> Checked<'a> {
+    pub fn new(inner: Box<dyn TrieIterator + 'a>, levels: usize) -> Self {
+        Self {
+            inner,
+            levels,
+            depth: 0,
+        }
+    }
+}
+
+// Wrap `iter` in a `Checked` in debug builds, and leave it alone otherwise.
+pub fn wrap<'a>(iter: Box<dyn TrieIterator + 'a>, levels: usize) -> Box<dyn TrieIterator + 'a> {
+    if cfg!(debug_assertions) {
+        Box::new(Checked::new(iter, levels))
+    } else {
+        iter
+    }
+}
+
+impl TrieIterator for Checked<'_> {
+    fn seek(&mut self, v: u64) {
+        // Within a level, positions only move forward until the next reset.
+        if let Some(current) = self.inner.value() {
+            debug_assert!(
+                v >= current,
+                "seek({}) would move backwards from {} at level {}",
+                v,
+                current,
+                self.depth
+            );
+        }
+        self.inner.seek(v);
+    }
+
+    fn up(&mut self) {
+        debug_assert!(self.depth > 0, "up() at the top level");
+        self.depth = self.depth.saturating_sub(1);
+        self.inner.up();
+    }
+
+    fn down(&mut self) {
+        debug_assert!(
+            self.depth + 1 < self.levels,
+            "down() at the last level ({})",
+            self.depth
+        );
+        debug_assert!(
+            self.inner.value().is_some(),
+            "down() with no value bound at level {}",
+            self.depth
+        );
+        self.depth = (self.depth + 1).min(self.levels.saturating_sub(1));
+        self.inner.down();
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    fn value(&self) -> Option<u64> {
+        self.inner.value()
+    }
+
+    fn next(&mut self) {
+        debug_assert!(
+            self.inner.value().is_some(),
+            "next() past the end of level {}",
+            self.depth
+        );
+        self.inner.next();
+    }
+
+    fn rest(&self) -> Option<&[u64]> {
+        self.inner.rest()
+    }
+}
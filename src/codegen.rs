@@ -0,0 +1,167 @@
+// Generates the Rust source for a Leapfrog Triejoin loop nest specialized to
+// one fixed query, like the triangle loop in `main` but for any conjunctive
+// query. The build script runs this over the queries in `build.rs` and the
+// results are compiled into `compiled`; it's also usable from other build
+// scripts.
+//
+// This module must not depend on the rest of the crate, since the build script
+// includes it directly by path.
+
+use std::fmt::Write;
+
+// A query to generate a loop nest for. Each atom is named by the parameter the
+// generated function takes its trie iterator in, and lists the variable each
+// of its columns binds. Variables are numbered in the order they are bound,
+// and the iterators passed in must already have their levels in that order.
+pub struct Query {
+    pub name: String,
+    pub atoms: Vec<(String, Vec<usize>)>,
+    pub num_vars: usize,
+}
+
+impl Query {
+    pub fn new(name: &str, atoms: &[(&str, &[usize])], num_vars: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            atoms: atoms
+                .iter()
+                .map(|(iter, vars)| (iter.to_string(), vars.to_vec()))
+                .collect(),
+            num_vars,
+        }
+    }
+}
+
+// Produce a function `fn <name>(...) -> u64` counting the results of `query`,
+// generic over the iterator type of each atom so every call is statically
+// dispatched.
+pub fn generate(query: &Query) -> String {
+    let mut out = String::new();
+    let params: Vec<String> = query
+        .atoms
+        .iter()
+        .enumerate()
+        .map(|(i, (iter, _))| format!("{}: &mut I{}", iter, i))
+        .collect();
+    let generics: Vec<String> = (0..query.atoms.len())
+        .map(|i| format!("I{}: TrieIterator", i))
+        .collect();
+    let _ = writeln!(
+        out,
+        "pub fn {}<{}>({}) -> u64 {{",
+        query.name,
+        generics.join(", "),
+        params.join(", ")
+    );
+    out.push_str("    let mut count = 0_u64;\n");
+    generate_level(query, 0, 1, &mut out);
+    out.push_str("    count\n}\n");
+    out
+}
+
+// The atoms whose trie binds `var`, and whether each one binds a later
+// variable too (and so needs to be descended into).
+fn participants(query: &Query, var: usize) -> Vec<(&str, bool)> {
+    query
+        .atoms
+        .iter()
+        .filter(|(_, vars)| vars.contains(&var))
+        .map(|(iter, vars)| (iter.as_str(), vars.iter().any(|&v| v > var)))
+        .collect()
+}
+
+fn generate_level(query: &Query, var: usize, depth: usize, out: &mut String) {
+    let pad = "    ".repeat(depth);
+    if var == query.num_vars {
+        let _ = writeln!(out, "{}count += 1;", pad);
+        return;
+    }
+    let ps = participants(query, var);
+    assert!(
+        !ps.is_empty(),
+        "variable {} does not appear in any atom",
+        var
+    );
+
+    for (iter, _) in &ps {
+        let _ = writeln!(out, "{}{}.reset();", pad, iter);
+    }
+    // At the last variable, count the overlap of the remaining lists without
+    // visiting each value whenever the iterators can hand them over whole.
+    let last = var + 1 == query.num_vars;
+    if last {
+        let rests: Vec<String> = ps
+            .iter()
+            .map(|(iter, _)| format!("{}.rest()", iter))
+            .collect();
+        let somes: Vec<String> = (0..ps.len()).map(|i| format!("Some(l{})", i)).collect();
+        let lists: Vec<String> = (0..ps.len()).map(|i| format!("l{}", i)).collect();
+        let _ = writeln!(
+            out,
+            "{}if let ({},) = ({},) {{",
+            pad,
+            somes.join(", "),
+            rests.join(", ")
+        );
+        let _ = writeln!(
+            out,
+            "{}    count += intersect::count_many(&mut [{}]);",
+            pad,
+            lists.join(", ")
+        );
+        let _ = writeln!(out, "{}}} else {{", pad);
+        generate_leapfrog(query, var, &ps, depth + 1, out);
+        let _ = writeln!(out, "{}}}", pad);
+    } else {
+        generate_leapfrog(query, var, &ps, depth, out);
+    }
+}
+
+// The value-at-a-time leapfrog over `var`'s participants, with the levels for
+// the later variables nested inside it.
+fn generate_leapfrog(
+    query: &Query,
+    var: usize,
+    ps: &[(&str, bool)],
+    depth: usize,
+    out: &mut String,
+) {
+    let pad = "    ".repeat(depth);
+    let values: Vec<String> = ps
+        .iter()
+        .map(|(iter, _)| format!("{}.value()", iter))
+        .collect();
+    let somes: Vec<String> = (0..ps.len()).map(|i| format!("Some(x{})", i)).collect();
+    let max = (1..ps.len()).fold("x0".to_string(), |acc, i| format!("{}.max(x{})", acc, i));
+    let _ = writeln!(
+        out,
+        "{}while let ({},) = ({},) {{",
+        pad,
+        somes.join(", "),
+        values.join(", ")
+    );
+    let _ = writeln!(out, "{}    let hi = {};", pad, max);
+    let lagging: Vec<String> = (0..ps.len()).map(|i| format!("x{} < hi", i)).collect();
+    let _ = writeln!(out, "{}    if {} {{", pad, lagging.join(" || "));
+    for (i, (iter, _)) in ps.iter().enumerate() {
+        let _ = writeln!(out, "{}        if x{} < hi {{", pad, i);
+        let _ = writeln!(out, "{}            {}.seek(hi);", pad, iter);
+        let _ = writeln!(out, "{}        }}", pad);
+    }
+    let _ = writeln!(out, "{}        continue;", pad);
+    let _ = writeln!(out, "{}    }}", pad);
+    let _ = writeln!(out, "{}    // v{} is now bound.", pad, var);
+    for (iter, descend) in ps {
+        if *descend {
+            let _ = writeln!(out, "{}    {}.down();", pad, iter);
+        }
+    }
+    generate_level(query, var + 1, depth + 1, out);
+    for (iter, descend) in ps {
+        if *descend {
+            let _ = writeln!(out, "{}    {}.up();", pad, iter);
+        }
+        let _ = writeln!(out, "{}    {}.next();", pad, iter);
+    }
+    let _ = writeln!(out, "{}}}", pad);
+}
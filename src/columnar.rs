@@ -0,0 +1,71 @@
+// Graph input and per-vertex output as parallel columns, the shape dataframe
+// libraries hand out and accept, so pipelines can pass their columns straight
+// through instead of writing out an edge list file.
+
+use std::fmt;
+
+use crate::{index::Adjacency, loader, parallel, weighted::WeightedGraph};
+
+// Columns meant to line up that have different lengths, in the order they
+// were given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnevenColumns(pub Vec<usize>);
+
+impl fmt::Display for UnevenColumns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lengths: Vec<String> = self.0.iter().map(|n| n.to_string()).collect();
+        write!(f, "columns differ in length: {}", lengths.join(", "))
+    }
+}
+
+fn check_lengths(lengths: &[usize]) -> Result<(), UnevenColumns> {
+    if lengths.iter().all(|&n| n == lengths[0]) {
+        Ok(())
+    } else {
+        Err(UnevenColumns(lengths.to_vec()))
+    }
+}
+
+// The graph whose i-th edge is (src[i], dst[i]).
+pub fn from_columns(src: &[u64], dst: &[u64]) -> Result<Adjacency, UnevenColumns> {
+    check_lengths(&[src.len(), dst.len()])?;
+    Ok(loader::to_adjacency(
+        src.iter().copied().zip(dst.iter().copied()).collect(),
+    ))
+}
+
+// The weighted graph whose i-th edge is (src[i], dst[i]) with weight
+// weight[i].
+pub fn from_weighted_columns(
+    src: &[u64],
+    dst: &[u64],
+    weight: &[f64],
+) -> Result<WeightedGraph, UnevenColumns> {
+    check_lengths(&[src.len(), dst.len(), weight.len()])?;
+    Ok(WeightedGraph::new(
+        src.iter()
+            .zip(dst)
+            .zip(weight)
+            .map(|((&u, &v), &w)| (u, v, w))
+            .collect(),
+    ))
+}
+
+// The number of triangles each vertex is in, as two columns: the vertices in
+// ascending order, and each one's count. Vertices in no triangle are left
+// out.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct VertexTriangles {
+    pub vertex: Vec<u64>,
+    pub triangles: Vec<u64>,
+}
+
+// Count, for every vertex, the triangles (a, b, c) of R(a, b), S(b, c),
+// T(a, c) over `data` that it is one of a, b, or c in, with
+// `parallel::triangles_per_vertex` on the given number of threads.
+pub fn triangles_per_vertex(data: &Adjacency, threads: usize) -> VertexTriangles {
+    let (vertex, triangles) = parallel::triangles_per_vertex(data, threads)
+        .into_iter()
+        .unzip();
+    VertexTriangles { vertex, triangles }
+}
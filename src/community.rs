@@ -0,0 +1,162 @@
+// Triangle counts broken down by a partition of the vertices into
+// communities, for judging a clustering: a good one keeps most triangles
+// inside its communities rather than spread across them.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{index::Adjacency, intersect, parallel};
+
+// An assignment of vertices to communities. Communities are numbered in the
+// order their labels first appear.
+pub struct Partition {
+    labels: Vec<String>,
+    community: HashMap<u64, usize>,
+}
+
+impl Partition {
+    // Build a partition from (vertex, label) pairs. If a vertex is listed more
+    // than once, its last assignment wins.
+    pub fn new(assignment: Vec<(u64, String)>) -> Self {
+        let mut labels = Vec::new();
+        let mut ids: HashMap<String, usize> = HashMap::new();
+        let mut community = HashMap::new();
+        for (u, label) in assignment {
+            let id = *ids.entry(label).or_insert_with_key(|label| {
+                labels.push(label.clone());
+                labels.len() - 1
+            });
+            community.insert(u, id);
+        }
+        Self { labels, community }
+    }
+
+    pub fn community_of(&self, u: u64) -> Option<usize> {
+        self.community.get(&u).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+// The triangles touching one community.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommunityTriangles {
+    pub label: String,
+    pub vertices: usize,
+    // Triangles with all three vertices in the community.
+    pub intra: u64,
+    // Triangles with some, but not all, of their vertices in the community.
+    pub inter: u64,
+}
+
+impl CommunityTriangles {
+    // The fraction of the possible triangles among the community's vertices
+    // which are present.
+    pub fn intra_density(&self) -> f64 {
+        let n = self.vertices as f64;
+        let possible = n * (n - 1.0) * (n - 2.0) / 6.0;
+        if possible > 0.0 {
+            self.intra as f64 / possible
+        } else {
+            0.0
+        }
+    }
+
+    // The fraction of the triangles touching the community which lie
+    // entirely inside it.
+    pub fn intra_fraction(&self) -> f64 {
+        let touching = self.intra + self.inter;
+        if touching > 0 {
+            self.intra as f64 / touching as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+pub struct CommunityReport {
+    pub communities: Vec<CommunityTriangles>,
+    // Triangles inside a single community, and all the rest (including those
+    // touching a vertex with no community).
+    pub intra: u64,
+    pub inter: u64,
+}
+
+// Classify each triangle (a, b, c) of R(a, b), S(b, c), T(a, c) over `data`
+// by the communities of its vertices.
+pub fn count_by_community(data: &Adjacency, partition: &Partition) -> CommunityReport {
+    let mut communities: Vec<CommunityTriangles> = partition
+        .labels
+        .iter()
+        .map(|label| CommunityTriangles {
+            label: label.clone(),
+            ..Default::default()
+        })
+        .collect();
+    for &c in partition.community.values() {
+        communities[c].vertices += 1;
+    }
+    let (mut intra, mut inter) = (0, 0);
+    for (i, (a, _)) in data.iter().enumerate() {
+        let ca = partition.community_of(*a);
+        parallel::for_each_b(data, i, |a_neighbors, b_neighbors, b| {
+            let cb = partition.community_of(b);
+            for c in intersect::common(a_neighbors, b_neighbors) {
+                match [ca, cb, partition.community_of(c)] {
+                    [Some(c), cb, cc] if cb == Some(c) && cc == Some(c) => {
+                        intra += 1;
+                        communities[c].intra += 1;
+                    }
+                    mut touched => {
+                        inter += 1;
+                        // Count the triangle once for each distinct community
+                        // it touches.
+                        touched.sort_unstable();
+                        for (i, c) in touched.iter().enumerate() {
+                            match c {
+                                Some(c) if i == 0 || touched[i - 1] != Some(*c) => {
+                                    communities[*c].inter += 1
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+            }
+        });
+    }
+    CommunityReport {
+        communities,
+        intra,
+        inter,
+    }
+}
+
+impl fmt::Display for CommunityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>16} {:>10} {:>12} {:>12} {:>12} {:>10}",
+            "community", "vertices", "intra", "inter", "density", "intra %"
+        )?;
+        for c in &self.communities {
+            writeln!(
+                f,
+                "{:>16} {:>10} {:>12} {:>12} {:>12.6} {:>9.2}%",
+                c.label,
+                c.vertices,
+                c.intra,
+                c.inter,
+                c.intra_density(),
+                100.0 * c.intra_fraction()
+            )?;
+        }
+        writeln!(f, "intra-community triangles: {}", self.intra)?;
+        writeln!(f, "inter-community triangles: {}", self.inter)
+    }
+}
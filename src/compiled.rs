@@ -0,0 +1,7 @@
+// Loop nests generated at build time from the queries listed in `build.rs`.
+// Each takes one trie iterator per atom, with levels already in the query's
+// variable order.
+
+use crate::{index::TrieIterator, intersect};
+
+include!(concat!(env!("OUT_DIR"), "/compiled.rs"));
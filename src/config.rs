@@ -0,0 +1,190 @@
+// Defaults read from a `triangle-counter.toml`, for running the tool over and
+// over with the same settings. Flags given on the command line override
+// whatever the file says.
+//
+// Only the small part of TOML these files need is understood: top-level
+// `key = value` lines, with string and integer values, and `#`
+// comments.
+//
+//     algorithm = "heavy-light"
+//     threads = 8
+//     memory_budget = "4GiB"
+//     cache_dir = "/var/cache/triangles"
+//     output = "json"
+//     numa = "replicate"
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{loader::LoadError, numa::Placement};
+
+// The file looked for in the working directory when no `--config` is given.
+pub const DEFAULT_FILE: &str = "triangle-counter.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+// Every setting is optional, and falls back to the built-in default when
+// missing from both the file and the command line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub threads: Option<usize>,
+    // Which engine to count with, by the name `--engine` takes.
+    pub algorithm: Option<String>,
+    // In bytes.
+    pub memory_budget: Option<u64>,
+    pub cache_dir: Option<PathBuf>,
+    pub output: Option<OutputFormat>,
+    // How to place the index across NUMA nodes when counting in parallel.
+    pub numa: Option<Placement>,
+}
+
+impl Config {
+    // Read the config at `path`.
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    // Read the default config file if there is one, or use the built-in
+    // defaults if not.
+    pub fn load_default() -> Result<Self, LoadError> {
+        match fs::read_to_string(DEFAULT_FILE) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, LoadError> {
+        let mut config = Config::default();
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: String| LoadError::Parse {
+                line: i + 1,
+                message,
+            };
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected `key = value`, found {:?}", line)))?;
+            let (key, value) = (key.trim(), Value::parse(value.trim()).map_err(error)?);
+            match key {
+                "threads" => config.threads = Some(value.integer(key).map_err(error)? as usize),
+                "algorithm" => config.algorithm = Some(value.string(key).map_err(error)?),
+                "memory_budget" => {
+                    config.memory_budget = Some(match value {
+                        Value::Integer(n) => n,
+                        Value::String(s) => parse_size(&s)
+                            .ok_or_else(|| error(format!("invalid memory budget {:?}", s)))?,
+                    })
+                }
+                "cache_dir" => config.cache_dir = Some(value.string(key).map_err(error)?.into()),
+                "output" => {
+                    let s = value.string(key).map_err(error)?;
+                    config.output = Some(OutputFormat::parse(&s).ok_or_else(|| {
+                        error(format!(
+                            "unknown output format {:?} (expected text or json)",
+                            s
+                        ))
+                    })?);
+                }
+                "numa" => {
+                    let s = value.string(key).map_err(error)?;
+                    config.numa = Some(Placement::parse(&s).ok_or_else(|| {
+                        error(format!(
+                            "unknown placement {:?} (expected replicate or partition)",
+                            s
+                        ))
+                    })?);
+                }
+                _ => return Err(error(format!("unknown setting {:?}", key))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+enum Value {
+    String(String),
+    Integer(u64),
+}
+
+impl Value {
+    fn parse(s: &str) -> Result<Self, String> {
+        if let Some(inner) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            return Ok(Value::String(
+                inner.replace("\\\"", "\"").replace("\\\\", "\\"),
+            ));
+        }
+        s.replace('_', "")
+            .parse()
+            .map(Value::Integer)
+            .map_err(|_| format!("invalid value {:?}", s))
+    }
+
+    fn string(self, key: &str) -> Result<String, String> {
+        match self {
+            Value::String(s) => Ok(s),
+            _ => Err(format!("{} expects a string", key)),
+        }
+    }
+
+    fn integer(self, key: &str) -> Result<u64, String> {
+        match self {
+            Value::Integer(n) => Ok(n),
+            _ => Err(format!("{} expects an integer", key)),
+        }
+    }
+}
+
+// Cut a trailing `#` comment off a line, unless the `#` is inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// Parse a size like "512MB", "4GiB", or "1000", in bytes.
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1_000_000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1_000_000_000,
+        "gib" => 1 << 30,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
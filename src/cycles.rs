@@ -0,0 +1,103 @@
+// Counting 4-cycles: the bindings of Q(a, b, c, d) <- R(a, b), S(b, c),
+// T(c, d), U(d, a) over the graph's edges with a, b, c and d all different.
+// Without that last condition a pair of edges u -> v -> u would close a
+// "cycle" (a, b, a, b) by itself, and every undirected graph is full of them.
+//
+// Rather than joining four atoms, this pairs up wedges. For each a, count
+// the paths a -> b -> c into one array and the paths c -> d -> a into another,
+// over every c; each path out pairs with each path back into a cycle through
+// a and c, except where b and d are the same vertex, which can only happen
+// when all four of its edges with a and c go both ways. That leaves
+// sum over a, c of out(a, c) * back(c, a) - both(a, c), at the cost of a walk
+// over every wedge, however many cycles there are.
+//
+// Like the triangle query, each cycle of an undirected graph stored with
+// both directions of its edges is found once per starting vertex and
+// direction: eight times.
+//
+// The counters are arrays indexed by vertex id, so a graph with sparse ids is
+// renumbered first; the count doesn't depend on what the vertices are called.
+
+use crate::{
+    graph::Graph,
+    index::{self, Adjacency, Csr},
+    intersect, loader, parallel,
+};
+
+pub fn count_four_cycles(graph: &Graph) -> u64 {
+    let mut data = graph.csr().to_adjacency();
+    if let Some((compacted, _)) = index::compact(&data) {
+        data = compacted;
+    }
+    let forward = Csr::from(&data);
+    let backward = Csr::from(&transpose(&data));
+    let reciprocal = Csr::from(&reciprocal(&data, &backward));
+    let universe = parallel::universe(&data);
+
+    // For the current a, indexed by c.
+    let mut out = vec![0_u64; universe];
+    let mut back = vec![0_u64; universe];
+    let mut both = vec![0_u64; universe];
+    // The values of c with any path out, which are the only ones that can
+    // close a cycle, so the only ones to sum and clear.
+    let mut touched = Vec::new();
+    let mut count = 0;
+    for (a, bs) in forward.iter() {
+        for &b in bs.iter().filter(|&&b| b != a) {
+            for &c in forward.neighbors(b) {
+                if c != a && c != b {
+                    if out[c as usize] == 0 {
+                        touched.push(c);
+                    }
+                    out[c as usize] += 1;
+                }
+            }
+        }
+        if touched.is_empty() {
+            continue;
+        }
+        for &d in backward.neighbors(a).iter().filter(|&&d| d != a) {
+            for &c in backward.neighbors(d) {
+                if c != a && c != d && out[c as usize] > 0 {
+                    back[c as usize] += 1;
+                }
+            }
+        }
+        for &b in reciprocal.neighbors(a).iter().filter(|&&b| b != a) {
+            for &c in reciprocal.neighbors(b) {
+                if c != a && c != b {
+                    both[c as usize] += 1;
+                }
+            }
+        }
+        // A path both ways is a path out too, so `touched` covers `both`.
+        for c in touched.drain(..) {
+            let c = c as usize;
+            count += out[c] * back[c] - both[c];
+            out[c] = 0;
+            back[c] = 0;
+            both[c] = 0;
+        }
+    }
+    count
+}
+
+// The edges of `data` turned around.
+fn transpose(data: &Adjacency) -> Adjacency {
+    let edges = data
+        .iter()
+        .flat_map(|(u, vs)| vs.iter().map(move |v| (*v, *u)))
+        .collect();
+    loader::to_adjacency(edges)
+}
+
+// The edges of `data` whose reverse is in it too.
+fn reciprocal(data: &Adjacency, backward: &Csr) -> Adjacency {
+    data.iter()
+        .map(|(u, vs)| {
+            let both = intersect::common(vs, backward.neighbors(*u)).collect();
+            (*u, both)
+        })
+        .filter(|(_, vs): &(u64, Vec<u64>)| !vs.is_empty())
+        .collect()
+}
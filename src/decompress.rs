@@ -0,0 +1,101 @@
+// Opening input files that may be compressed. Edge lists are usually
+// distributed gzipped or zstd-compressed, so a file starting with either
+// format's magic bytes is decompressed as it's read, by piping it through
+// `gzip -dc` or `zstd -dc`, and never needs a decompressed copy on disk.
+// Anything else is read as it is.
+//
+// Spawning processes is no business of a library call, so this is the
+// command line tool's, which opens its inputs with it and passes them to the
+// loader's readers.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+    process::{Child, ChildStdout, Command, Stdio},
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// The file at `path`, decompressed if need be.
+pub fn open(path: &Path) -> io::Result<Box<dyn Read + Send>> {
+    let mut magic = [0; 4];
+    let mut file = File::open(path)?;
+    let n = read_up_to(&mut file, &mut magic)?;
+    let tool = if n >= 2 && magic[..2] == GZIP_MAGIC {
+        "gzip"
+    } else if n == 4 && magic == ZSTD_MAGIC {
+        "zstd"
+    } else {
+        // Hand back the file with the bytes we peeked at in front.
+        return Ok(Box::new(io::Cursor::new(magic[..n].to_vec()).chain(file)));
+    };
+    let mut child = Command::new(tool)
+        .arg("-dc")
+        .stdin(File::open(path)?)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "{} is {}-compressed, and running `{}` failed: {}",
+                    path.display(),
+                    tool,
+                    tool,
+                    e
+                ),
+            )
+        })?;
+    let stdout = child.stdout.take().unwrap();
+    Ok(Box::new(Decompressed {
+        tool,
+        child,
+        stdout,
+    }))
+}
+
+fn read_up_to(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match file.read(&mut buf[n..])? {
+            0 => break,
+            k => n += k,
+        }
+    }
+    Ok(n)
+}
+
+// The output of a decompressor. Reaching the end checks that it succeeded,
+// so a truncated or corrupt file is an error rather than a short read.
+struct Decompressed {
+    tool: &'static str,
+    child: Child,
+    stdout: ChildStdout,
+}
+
+impl Read for Decompressed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stdout.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            let status = self.child.wait()?;
+            if !status.success() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("`{} -dc` failed ({})", self.tool, status),
+                ));
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for Decompressed {
+    fn drop(&mut self) {
+        // If we stopped reading early (say, on a parse error), don't leave
+        // the decompressor behind.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,55 @@
+// Reading binary edge dumps: a file of nothing but (u32, u32) pairs, each
+// id little-endian, sorted by source and then destination. A repeated pair is
+// one edge, as it is in a text edge list. A producer that
+// can write this upstream saves us all of the parsing and sorting, and the
+// index is built in a single scan, allocating once per source rather than
+// once per edge.
+
+use std::{fs, path::Path};
+
+use crate::{index::Adjacency, persist::FormatError};
+
+// The size of one edge in a dump.
+pub const EDGE_BYTES: usize = 8;
+
+pub fn load(path: &Path) -> Result<Adjacency, FormatError> {
+    build(&fs::read(path)?)
+}
+
+// Build the index from a dump's bytes (say, a memory map of the file).
+pub fn build(bytes: &[u8]) -> Result<Adjacency, FormatError> {
+    if !bytes.len().is_multiple_of(EDGE_BYTES) {
+        return Err(FormatError::Corrupt(format!(
+            "{} bytes isn't a whole number of {}-byte edges",
+            bytes.len(),
+            EDGE_BYTES
+        )));
+    }
+    let id = |i: usize| u32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap()) as u64;
+    let edges = bytes.len() / EDGE_BYTES;
+    let mut data: Adjacency = Vec::new();
+    let mut start = 0;
+    while start < edges {
+        // Find the end of this source's run, checking the order on the way.
+        let u = id(2 * start);
+        let mut end = start + 1;
+        while end < edges && id(2 * end) == u {
+            if id(2 * end + 1) < id(2 * end - 1) {
+                return Err(unsorted(end));
+            }
+            end += 1;
+        }
+        if end < edges && id(2 * end) < u {
+            return Err(unsorted(end));
+        }
+        let mut neighbors: Vec<u64> = (start..end).map(|i| id(2 * i + 1)).collect();
+        neighbors.dedup();
+        data.push((u, neighbors));
+        start = end;
+    }
+    Ok(data)
+}
+
+fn unsorted(edge: usize) -> FormatError {
+    FormatError::Corrupt(format!("edge {} is out of order", edge))
+}
@@ -0,0 +1,368 @@
+// A compressed backend for the index, for graphs whose neighbor lists don't
+// fit in memory as flat u64 arrays. Each sorted list is stored with
+// Elias-Fano encoding: every value is split into its low `l` bits, stored
+// as-is, and its high bits, stored in unary as gaps in a bit vector (a one
+// for each value, preceded by a zero for each step up in the high part). With
+// `l` chosen from the list's length and largest value, a list of n values
+// below u takes about n (2 + log(u / n)) bits, rather than 64 n.
+//
+// All of the lists share one buffer of words, with a small header each. The
+// sources (one per list, so far fewer than the edges) stay uncompressed.
+//
+// A cursor moves through a list with `next`, finding the next one bit, and
+// `seek`, which skips whole words of the high bits at a time by counting
+// their zeros. Seeks in a join only move forward, so no select index is
+// needed on top; a seek costs time in proportion to how far it moves.
+
+use std::{fmt, sync::Arc};
+
+use crate::index::{Adjacency, TrieIterator};
+
+pub struct EfGraph {
+    sources: Vec<u64>,
+    lists: Vec<List>,
+    words: Vec<u64>,
+}
+
+// Kept small, since there's one per source: on sparse graphs the headers
+// are a good part of the total.
+#[derive(Clone, Copy)]
+struct List {
+    // Where the low bits start in `words`, in bits. The high bits follow
+    // straight after.
+    low_at: u64,
+    len: u32,
+    // How many low bits each value keeps.
+    low_bits: u32,
+}
+
+impl List {
+    const EMPTY: List = List {
+        low_at: 0,
+        len: 0,
+        low_bits: 0,
+    };
+
+    fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    fn high_at(&self) -> usize {
+        self.low_at as usize + self.len() * self.low_bits as usize
+    }
+}
+
+impl EfGraph {
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    // A trie iterator over the edges, at the start of the first level.
+    pub fn iter(self: &Arc<Self>) -> EfIterator {
+        EfIterator {
+            graph: self.clone(),
+            level: Level::Upper(0),
+        }
+    }
+
+    // Roughly how many bytes of heap the graph takes up.
+    pub fn heap_size(&self) -> usize {
+        self.sources.capacity() * std::mem::size_of::<u64>()
+            + self.lists.capacity() * std::mem::size_of::<List>()
+            + self.words.capacity() * std::mem::size_of::<u64>()
+    }
+
+    fn bit(&self, at: usize) -> bool {
+        self.words[at / 64] >> (at % 64) & 1 == 1
+    }
+
+    // The `width` bits starting at bit `at`.
+    fn bits(&self, at: usize, width: u32) -> u64 {
+        if width == 0 {
+            return 0;
+        }
+        let (word, offset) = (at / 64, (at % 64) as u32);
+        let mut value = self.words[word] >> offset;
+        if offset + width > 64 {
+            value |= self.words[word + 1] << (64 - offset);
+        }
+        value & (u64::MAX >> (64 - width))
+    }
+}
+
+// A neighbor list too long to encode: lists are limited to 2^32 values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ListTooLong {
+    pub source: u64,
+    pub len: usize,
+}
+
+impl fmt::Display for ListTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} has {} neighbors, more than an ef list can hold",
+            self.source, self.len
+        )
+    }
+}
+
+impl TryFrom<&Adjacency> for EfGraph {
+    type Error = ListTooLong;
+
+    fn try_from(data: &Adjacency) -> Result<Self, ListTooLong> {
+        let mut writer = Writer::default();
+        let mut lists = Vec::with_capacity(data.len());
+        for (u, vs) in data {
+            let len = vs.len();
+            let universe = vs.last().map_or(0, |&v| v.saturating_add(1));
+            let low_bits = if len > 0 && universe > len as u64 {
+                (universe / len as u64).ilog2()
+            } else {
+                0
+            };
+            let low_at = writer.len as u64;
+            for &v in vs {
+                writer.push(v, low_bits);
+            }
+            let mut high = 0;
+            for &v in vs {
+                // Zeros up to this value's high part, then a one for it.
+                let h = v >> low_bits;
+                writer.push_zeros((h - high) as usize);
+                writer.push(1, 1);
+                high = h;
+            }
+            lists.push(List {
+                low_at,
+                len: u32::try_from(len).map_err(|_| ListTooLong { source: *u, len })?,
+                low_bits,
+            });
+        }
+        // A word of slack, so reading two words for a value near the end
+        // never runs off the end.
+        writer.words.push(0);
+        Ok(Self {
+            sources: data.iter().map(|(u, _)| *u).collect(),
+            lists,
+            words: writer.words,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    words: Vec<u64>,
+    // In bits.
+    len: usize,
+}
+
+impl Writer {
+    fn push(&mut self, value: u64, width: u32) {
+        if width == 0 {
+            return;
+        }
+        let value = value & (u64::MAX >> (64 - width));
+        let offset = (self.len % 64) as u32;
+        if offset == 0 {
+            self.words.push(0);
+        }
+        *self.words.last_mut().unwrap() |= value << offset;
+        if offset + width > 64 {
+            self.words.push(value >> (64 - offset));
+        }
+        self.len += width as usize;
+    }
+
+    fn push_zeros(&mut self, n: usize) {
+        self.len += n;
+        self.words.resize(self.len.div_ceil(64), 0);
+    }
+}
+
+// A position in one list: the `k`th value, whose one bit in the high part is
+// at bit `p`, and which is decoded into `value` as the cursor gets there.
+// Past the end, `k` is the list's length.
+#[derive(Clone, Copy)]
+struct Cursor {
+    list: List,
+    high_at: usize,
+    k: usize,
+    p: usize,
+    value: Option<u64>,
+}
+
+impl Cursor {
+    fn new(graph: &EfGraph, list: List) -> Self {
+        let high_at = list.high_at();
+        let mut cursor = Self {
+            list,
+            high_at,
+            k: 0,
+            p: 0,
+            value: None,
+        };
+        if list.len > 0 {
+            cursor.p = next_one(graph, high_at);
+            cursor.decode(graph);
+        }
+        cursor
+    }
+
+    fn value(&self) -> Option<u64> {
+        self.value
+    }
+
+    fn decode(&mut self, graph: &EfGraph) {
+        if self.k >= self.list.len() {
+            self.value = None;
+            return;
+        }
+        let high = (self.p - self.high_at - self.k) as u64;
+        let low_bits = self.list.low_bits;
+        let low = graph.bits(
+            self.list.low_at as usize + self.k * low_bits as usize,
+            low_bits,
+        );
+        self.value = Some(high << low_bits | low);
+    }
+
+    fn next(&mut self, graph: &EfGraph) {
+        self.k += 1;
+        if self.k < self.list.len() {
+            self.p = next_one(graph, self.p + 1);
+        }
+        self.decode(graph);
+    }
+
+    fn seek(&mut self, graph: &EfGraph, v: u64) {
+        let Some(current) = self.value else {
+            return;
+        };
+        if current >= v {
+            return;
+        }
+        // Skip to the first value whose high part is at least v's: past the
+        // ones before the (v >> l)th zero.
+        let target = v >> self.list.low_bits;
+        let mut zeros = (self.p - self.high_at - self.k) as u64;
+        if zeros < target {
+            let mut at = self.p + 1;
+            let mut k = self.k + 1;
+            // Whole words first, then bit by bit.
+            loop {
+                let offset = at % 64;
+                let word = graph.words[at / 64] >> offset;
+                let width = 64 - offset;
+                let ones = word.count_ones() as usize;
+                let word_zeros = (width - ones) as u64;
+                if zeros + word_zeros >= target {
+                    break;
+                }
+                zeros += word_zeros;
+                k += ones;
+                at += width;
+                if k >= self.list.len() {
+                    self.finish();
+                    return;
+                }
+            }
+            while zeros < target {
+                if graph.bit(at) {
+                    k += 1;
+                } else {
+                    zeros += 1;
+                }
+                at += 1;
+            }
+            if k >= self.list.len() {
+                self.finish();
+                return;
+            }
+            self.k = k;
+            self.p = next_one(graph, at);
+            self.decode(graph);
+        }
+        // Then step through the values sharing that high part.
+        while self.value.is_some_and(|x| x < v) {
+            self.next(graph);
+        }
+    }
+
+    fn finish(&mut self) {
+        self.k = self.list.len();
+        self.value = None;
+    }
+}
+
+// The position of the first one bit at or after bit `at`. There must be one.
+fn next_one(graph: &EfGraph, at: usize) -> usize {
+    let (mut word, offset) = (at / 64, at % 64);
+    let mut bits = graph.words[word] >> offset << offset;
+    while bits == 0 {
+        word += 1;
+        bits = graph.words[word];
+    }
+    word * 64 + bits.trailing_zeros() as usize
+}
+
+enum Level {
+    Upper(usize),
+    Lower(usize, Cursor),
+}
+
+pub struct EfIterator {
+    graph: Arc<EfGraph>,
+    level: Level,
+}
+
+impl TrieIterator for EfIterator {
+    fn seek(&mut self, v: u64) {
+        match &mut self.level {
+            Level::Upper(i) => {
+                *i = crate::index::gallop(&self.graph.sources, *i, |&x| x < v);
+            }
+            Level::Lower(_, cursor) => cursor.seek(&self.graph, v),
+        }
+    }
+
+    fn up(&mut self) {
+        // At the top level, there's nothing bound to undo.
+        if let Level::Lower(i, _) = self.level {
+            self.level = Level::Upper(i);
+        }
+    }
+
+    fn down(&mut self) {
+        // At the bottom level, there's no level below.
+        if let Level::Upper(i) = self.level {
+            let list = self.graph.lists.get(i).copied().unwrap_or(List::EMPTY);
+            self.level = Level::Lower(i, Cursor::new(&self.graph, list));
+        }
+    }
+
+    fn reset(&mut self) {
+        match &mut self.level {
+            Level::Upper(i) => *i = 0,
+            Level::Lower(_, cursor) => *cursor = Cursor::new(&self.graph, cursor.list),
+        }
+    }
+
+    fn value(&self) -> Option<u64> {
+        match &self.level {
+            Level::Upper(i) => self.graph.sources.get(*i).copied(),
+            Level::Lower(_, cursor) => cursor.value(),
+        }
+    }
+
+    fn next(&mut self) {
+        match &mut self.level {
+            Level::Upper(i) => *i += 1,
+            Level::Lower(_, cursor) => cursor.next(&self.graph),
+        }
+    }
+}
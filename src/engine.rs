@@ -0,0 +1,466 @@
+// Choosing how to count the triangles in a graph: which join implementation
+// (the engine) runs over which layout of the index (the backend). Every
+// engine runs over flat arrays; the handwritten and compiled loop nests also
+// run over the compressed and bitmap backends, since those only have trie
+// iterators to offer.
+//
+// The engines should all agree, so besides counting with one this runs them
+// all side by side, timing each, and shrinks a graph they disagree on down to
+// a few edges that show the difference.
+
+use std::{
+    fmt, panic,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    batch, compiled,
+    ef::EfGraph,
+    failure::{Failure, FailureKind},
+    graph::{self, Graph},
+    hashtrie::HashTrie,
+    index::{Adjacency, Csr, Index},
+    join::{self, Atom},
+    loader, parallel, pool, reduce,
+    relation::EdgeRelation,
+    roaring::{self, RoaringGraph},
+    skew::HeavyLight,
+    stats::{self, GraphStats},
+};
+
+// How the index stores its neighbor lists, for the engines that walk it
+// with trie iterators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    // Flat arrays, see `index::Csr`.
+    Csr,
+    // The same with ids stored as u32s, for graphs whose ids all fit.
+    Csr32,
+    // Compressed with Elias-Fano encoding, see `ef`.
+    Ef,
+    // Roaring bitmaps, intersected with bitwise ANDs, see `roaring`.
+    Roaring,
+    // Roaring for graphs dense enough to benefit, and otherwise flat arrays,
+    // of u32s if the ids fit. Engines the other backends don't run on always
+    // get flat arrays of u64s.
+    Auto,
+}
+
+impl Backend {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "csr" => Some(Backend::Csr),
+            "csr32" => Some(Backend::Csr32),
+            "ef" => Some(Backend::Ef),
+            "roaring" => Some(Backend::Roaring),
+            "auto" => Some(Backend::Auto),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Csr => "csr",
+            Backend::Csr32 => "csr32",
+            Backend::Ef => "ef",
+            Backend::Roaring => "roaring",
+            Backend::Auto => "auto",
+        }
+    }
+
+    // The backend `engine` will run on over `data`: this one, unless it's
+    // `Auto`.
+    pub fn resolve(self, engine: &Engine, data: &Adjacency, stats: &GraphStats) -> Self {
+        match self {
+            Backend::Auto if matches!(engine, Engine::Handwritten | Engine::Compiled) => {
+                if stats.avg_degree() >= roaring::MIN_AVG_DEGREE {
+                    Backend::Roaring
+                } else if fits_u32(data) {
+                    Backend::Csr32
+                } else {
+                    Backend::Csr
+                }
+            }
+            Backend::Auto => Backend::Csr,
+            backend => backend,
+        }
+    }
+}
+
+// Which join implementation to count with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Engine {
+    // The loop nest in `graph`, written out by hand for the triangle query.
+    Handwritten,
+    // The same loop nest, generated at build time by `codegen`.
+    Compiled,
+    // Binding a batch of (a, b) pairs at a time, see `batch`.
+    Batched,
+    // The generic interpreter in `join`.
+    Generic,
+    // Different plans for heavy and light vertices, see `skew`. Vertices of
+    // more than the given degree are heavy (by default, sqrt(m)).
+    HeavyLight(Option<usize>),
+    // The loop nest split across the given number of threads, see `parallel`.
+    Parallel(usize),
+    // The handwritten loop nest over trie iterators, with the values of a
+    // split across the given number of threads.
+    ParallelHandwritten(usize),
+    // The handwritten loop nest over a `hashtrie`, built from the edges in
+    // no particular order.
+    HashTrie,
+}
+
+impl Engine {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "handwritten" => Some(Engine::Handwritten),
+            "compiled" => Some(Engine::Compiled),
+            "generic" => Some(Engine::Generic),
+            "batched" => Some(Engine::Batched),
+            "heavy-light" => Some(Engine::HeavyLight(None)),
+            "parallel" => Some(Engine::Parallel(parallel::default_threads())),
+            "hash-trie" => Some(Engine::HashTrie),
+            "parallel-handwritten" => {
+                Some(Engine::ParallelHandwritten(parallel::default_threads()))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Engine::Handwritten => "handwritten",
+            Engine::Compiled => "compiled",
+            Engine::Generic => "generic",
+            Engine::Batched => "batched",
+            Engine::HeavyLight(_) => "heavy-light",
+            Engine::Parallel(_) => "parallel",
+            Engine::ParallelHandwritten(_) => "parallel-handwritten",
+            Engine::HashTrie => "hash-trie",
+        }
+    }
+
+    // The same engine with the given heavy threshold and number of threads,
+    // for those that take them.
+    pub fn with(self, heavy_threshold: Option<usize>, threads: usize) -> Self {
+        match self {
+            Engine::HeavyLight(_) => Engine::HeavyLight(heavy_threshold),
+            Engine::Parallel(_) => Engine::Parallel(threads),
+            Engine::ParallelHandwritten(_) => Engine::ParallelHandwritten(threads),
+            engine => engine,
+        }
+    }
+}
+
+// Every engine, in the order they're compared in.
+pub fn all(heavy_threshold: Option<usize>, threads: usize) -> [Engine; 8] {
+    [
+        Engine::Handwritten,
+        Engine::Compiled,
+        Engine::Generic,
+        Engine::Batched,
+        Engine::HeavyLight(heavy_threshold),
+        Engine::Parallel(threads),
+        Engine::ParallelHandwritten(threads),
+        Engine::HashTrie,
+    ]
+}
+
+// Count with `engine` over flat arrays. Only the engines that run on the
+// worker pool can fail, if it can't start its workers.
+pub fn run(engine: &Engine, data: &Arc<Adjacency>, stats: &GraphStats) -> Result<u64, Failure> {
+    Ok(match engine {
+        Engine::Handwritten => graph::count_triangles(&Graph::from(&**data)),
+        Engine::Compiled => {
+            let csr = Arc::new(Csr::from(&**data));
+            compiled::triangles(
+                &mut Index::new(csr.clone()),
+                &mut Index::new(csr.clone()),
+                &mut Index::new(csr),
+            )
+        }
+        Engine::Batched => batch::count_triangles(data, batch::DEFAULT_BATCH_SIZE),
+        Engine::HeavyLight(Some(threshold)) => {
+            HeavyLight::new(data, *threshold).count_triangles(data)
+        }
+        Engine::HeavyLight(None) => HeavyLight::from_stats(data, stats).count_triangles(data),
+        Engine::Parallel(threads) => pool::global()
+            .count_triangles(data, *threads)
+            .map_err(Failure::pool)?,
+        Engine::HashTrie => {
+            let trie = Arc::new(HashTrie::from_edges(
+                data.iter()
+                    .flat_map(|(u, vs)| vs.iter().map(move |v| (*u, *v))),
+            ));
+            let mut count = 0;
+            graph::for_each_triangle_in(
+                &mut trie.iter(),
+                &mut trie.iter(),
+                &mut trie.iter(),
+                |_, _, _| count += 1,
+            );
+            count
+        }
+        Engine::ParallelHandwritten(threads) => {
+            graph::count_triangles_parallel(&Graph::from(&**data), *threads)
+        }
+        Engine::Generic => {
+            let edges = EdgeRelation::new(data.clone());
+            // Q(a, b, c) <- R(a, b), S(b, c), T(a, c), with a, b, c numbered
+            // 0, 1, 2.
+            join::count(
+                &[
+                    Atom::new(&edges, &[0, 1]),
+                    Atom::new(&edges, &[1, 2]),
+                    Atom::new(&edges, &[0, 2]),
+                ],
+                3,
+            )
+            .expect("the triangle query is valid")
+        }
+    })
+}
+
+// A count, and what it took to get it.
+pub struct Counted {
+    pub triangles: u64,
+    // How big the compressed index was, when counting over one.
+    pub ef: Option<EfSize>,
+}
+
+// How much smaller an Elias-Fano index is than the flat one.
+pub struct EfSize {
+    pub bytes: usize,
+    pub csr_bytes: usize,
+    pub edges: usize,
+}
+
+impl fmt::Display for EfSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ef index: {} bytes, {:.1} bits per edge ({:.1}x smaller than csr)",
+            self.bytes,
+            8.0 * self.bytes as f64 / self.edges.max(1) as f64,
+            self.csr_bytes as f64 / self.bytes.max(1) as f64
+        )
+    }
+}
+
+// Count with `engine` over the index laid out by `backend`, which must
+// already be resolved. Fails if the engine doesn't run on that backend.
+pub fn run_on(
+    backend: Backend,
+    engine: &Engine,
+    data: &Arc<Adjacency>,
+    stats: &GraphStats,
+) -> Result<Counted, Failure> {
+    let counted = |triangles| Counted {
+        triangles,
+        ef: None,
+    };
+    match backend {
+        Backend::Csr | Backend::Auto => run(engine, data, stats).map(counted),
+        Backend::Csr32 => run_csr32(engine, data).map(counted),
+        Backend::Ef => run_ef(engine, data),
+        Backend::Roaring => run_roaring(engine, data).map(counted),
+    }
+}
+
+fn unsupported(backend: Backend, engine: &Engine) -> Failure {
+    Failure::usage(format!(
+        "the {} backend works with the handwritten and compiled engines, not {}",
+        backend.name(),
+        engine.name()
+    ))
+}
+
+// Count with `engine` over an Elias-Fano copy of the index, noting how much
+// smaller it is than the flat one.
+fn run_ef(engine: &Engine, data: &Adjacency) -> Result<Counted, Failure> {
+    if !matches!(engine, Engine::Handwritten | Engine::Compiled) {
+        return Err(unsupported(Backend::Ef, engine));
+    }
+    let graph = EfGraph::try_from(data)
+        .map_err(|e| Failure::new(FailureKind::ResourceLimit, e.to_string()))?;
+    let graph = Arc::new(graph);
+    // The flat layout's size, worked out rather than built: a source and an
+    // offset per list, and a target per edge.
+    let edges: usize = data.iter().map(|(_, vs)| vs.len()).sum();
+    let size = EfSize {
+        bytes: graph.heap_size(),
+        csr_bytes: (2 * data.len() + 1 + edges) * std::mem::size_of::<u64>(),
+        edges,
+    };
+    let triangles = match engine {
+        Engine::Compiled => {
+            compiled::triangles(&mut graph.iter(), &mut graph.iter(), &mut graph.iter())
+        }
+        _ => {
+            let mut count = 0;
+            graph::for_each_triangle_in(
+                &mut graph.iter(),
+                &mut graph.iter(),
+                &mut graph.iter(),
+                |_, _, _| count += 1,
+            );
+            count
+        }
+    };
+    Ok(Counted {
+        triangles,
+        ef: Some(size),
+    })
+}
+
+// Whether every vertex id is small enough for the csr32 backend. Each list
+// is sorted, so only its last value needs checking.
+pub fn fits_u32(data: &Adjacency) -> bool {
+    let fits = |v: u64| v <= u32::MAX as u64;
+    data.iter()
+        .all(|(u, vs)| fits(*u) && vs.last().is_none_or(|&v| fits(v)))
+}
+
+fn run_csr32(engine: &Engine, data: &Adjacency) -> Result<u64, Failure> {
+    if !matches!(engine, Engine::Handwritten | Engine::Compiled) {
+        return Err(unsupported(Backend::Csr32, engine));
+    }
+    let Some(csr) = Csr::<u32>::try_from_adjacency(data) else {
+        return Err(Failure::usage(
+            "the csr32 backend needs every vertex id to fit in 32 bits",
+        ));
+    };
+    let csr = Arc::new(csr);
+    Ok(match engine {
+        Engine::Compiled => compiled::triangles(
+            &mut Index::new(csr.clone()),
+            &mut Index::new(csr.clone()),
+            &mut Index::new(csr),
+        ),
+        _ => {
+            let mut count = 0;
+            graph::for_each_triangle_in(
+                &mut Index::new(csr.clone()),
+                &mut Index::new(csr.clone()),
+                &mut Index::new(csr),
+                |_, _, _| count += 1,
+            );
+            count
+        }
+    })
+}
+
+fn run_roaring(engine: &Engine, data: &Adjacency) -> Result<u64, Failure> {
+    let graph = Arc::new(RoaringGraph::from(data));
+    match engine {
+        // Rather than the loop nest, which would step through the innermost
+        // intersection a value at a time, count it with bitmap ANDs.
+        Engine::Handwritten => Ok(graph.count_triangles()),
+        Engine::Compiled => Ok(compiled::triangles(
+            &mut graph.iter(),
+            &mut graph.iter(),
+            &mut graph.iter(),
+        )),
+        _ => Err(unsupported(Backend::Roaring, engine)),
+    }
+}
+
+// One engine's count, and the best of its times.
+pub struct Timing {
+    pub engine: &'static str,
+    pub triangles: u64,
+    pub best: Duration,
+}
+
+// How many times `compare` runs each engine, keeping the best, to smooth
+// over noise.
+const RUNS: usize = 3;
+
+// Run the same query through every engine, timing each.
+pub fn compare(
+    data: &Arc<Adjacency>,
+    stats: &GraphStats,
+    heavy_threshold: Option<usize>,
+    threads: usize,
+) -> Result<Vec<Timing>, Failure> {
+    all(heavy_threshold, threads)
+        .iter()
+        .map(|engine| {
+            let mut best = Duration::MAX;
+            let mut triangles = 0;
+            for _ in 0..RUNS {
+                let start = Instant::now();
+                triangles = run(engine, data, stats)?;
+                best = best.min(start.elapsed());
+            }
+            Ok(Timing {
+                engine: engine.name(),
+                triangles,
+                best,
+            })
+        })
+        .collect()
+}
+
+// Fails if the engines compared didn't all come up with the same count.
+pub fn check_agreement(timings: &[Timing]) -> Result<(), Failure> {
+    if timings.iter().all(|t| t.triangles == timings[0].triangles) {
+        return Ok(());
+    }
+    let counts: Vec<String> = timings
+        .iter()
+        .map(|t| format!("{}={}", t.engine, t.triangles))
+        .collect();
+    Err(Failure::new(
+        FailureKind::Invariant,
+        "engines disagree on the triangle count",
+    )
+    .with("counts", counts.join(",")))
+}
+
+// How much longer the generic interpreter took than the compiled loop nest,
+// which tells us how much there is to gain from specializing a query. None
+// if the compiled one was too quick to measure.
+pub fn interpretation_overhead(timings: &[Timing]) -> Option<f64> {
+    let time = |name: &str| {
+        timings
+            .iter()
+            .find(|t| t.engine == name)
+            .map(|t| t.best.as_secs_f64())
+    };
+    let compiled = time("compiled")?;
+    let generic = time("generic")?;
+    (compiled > 0.0).then(|| generic / compiled)
+}
+
+// Shrink a graph on which the engines disagree, or one of them panics, to a
+// minimal one on which they still do. None if they agree on it to begin with.
+//
+// Engine panics are caught, and expected along the way, but the panic hook
+// still runs for each one; callers that don't want them reported should
+// swap the hook out around the call.
+pub fn reduce_disagreement(
+    edges: Vec<(u64, u64)>,
+    heavy_threshold: Option<usize>,
+    threads: usize,
+) -> Option<Vec<(u64, u64)>> {
+    let engines = all(heavy_threshold, threads);
+    let fails = |edges: &[(u64, u64)]| {
+        let data = Arc::new(loader::to_adjacency(edges.to_vec()));
+        let stats = GraphStats::collect(&data, stats::DEFAULT_TOP_K);
+        let counts = panic::catch_unwind(|| {
+            engines
+                .iter()
+                .map(|engine| run(engine, &data, &stats))
+                .collect::<Result<Vec<_>, _>>()
+        });
+        // An engine that couldn't run at all fails the graph as a panic does.
+        match counts {
+            Ok(Ok(counts)) => counts.iter().any(|&c| c != counts[0]),
+            _ => true,
+        }
+    };
+    fails(&edges).then(|| reduce::reduce(edges, fails))
+}
@@ -0,0 +1,108 @@
+// Estimating how far through its work an enumeration is, and so when it'll
+// finish. How many first-level vertices are done is a poor guide on its own:
+// on a skewed graph a few vertices can hold most of the work, and wherever
+// they fall in the order, a straight-line extrapolation from the position is
+// wrong until they're behind it.
+//
+// So before starting, we take a quick random descent through the join. The
+// first level is cut into equal slices of positions, a few values of a are
+// drawn from each, and for each of those a few of its b's, each costed by the
+// merge that binds c (the lengths of both lists). Scaled back up, the samples
+// estimate each slice's work, and the running total over the slices says how
+// much of the work lies before any position.
+
+use std::time::Duration;
+
+use crate::index::{mix, Adjacency};
+
+// How many slices to cut the first level into.
+pub const DEFAULT_SLICES: usize = 256;
+// How many values of a to draw from each slice, and of b under each of them.
+const VERTICES_PER_SLICE: usize = 4;
+const DESCENTS: usize = 8;
+
+pub struct WorkEstimate {
+    // The first position of each slice, and one past the last position.
+    bounds: Vec<usize>,
+    // The estimated work before each of `bounds`.
+    before: Vec<f64>,
+}
+
+impl WorkEstimate {
+    // Sample the work of the triangle query over `data`, R(a, b), S(b, c),
+    // T(a, c), as `sink::enumerate` walks it.
+    pub fn sample(data: &Adjacency, slices: usize, seed: u64) -> Self {
+        let slices = slices.clamp(1, data.len().max(1));
+        let bounds: Vec<usize> = (0..=slices).map(|s| data.len() * s / slices).collect();
+        let mut random = seed;
+        let mut draw = |n: usize| {
+            random = mix(random);
+            (random % n as u64) as usize
+        };
+        let mut before = Vec::with_capacity(bounds.len());
+        before.push(0.0);
+        for window in bounds.windows(2) {
+            let (lo, hi) = (window[0], window[1]);
+            let mut work = 0.0;
+            if lo < hi {
+                let mut sampled = 0.0;
+                for _ in 0..VERTICES_PER_SLICE {
+                    sampled += vertex_work(data, lo + draw(hi - lo), &mut draw);
+                }
+                work = sampled / VERTICES_PER_SLICE as f64 * (hi - lo) as f64;
+            }
+            before.push(before.last().unwrap() + work);
+        }
+        Self { bounds, before }
+    }
+
+    pub fn total(&self) -> f64 {
+        *self.before.last().unwrap()
+    }
+
+    // The estimated fraction of the work behind us once the first `done`
+    // positions are, assuming work is spread evenly within a slice.
+    pub fn fraction(&self, done: usize) -> f64 {
+        let total = self.total();
+        if total == 0.0 {
+            return 1.0;
+        }
+        let s = self.bounds.partition_point(|&b| b <= done);
+        if s == 0 {
+            return 0.0;
+        }
+        if s == self.bounds.len() {
+            return 1.0;
+        }
+        let (lo, hi) = (self.bounds[s - 1], self.bounds[s]);
+        let within = (done - lo) as f64 / (hi - lo) as f64;
+        let work = self.before[s - 1] + within * (self.before[s] - self.before[s - 1]);
+        (work / total).min(1.0)
+    }
+
+    // How much longer the enumeration should take, if the first `done`
+    // positions took `elapsed`, or None until there's been any progress.
+    pub fn remaining(&self, done: usize, elapsed: Duration) -> Option<Duration> {
+        let fraction = self.fraction(done);
+        (fraction > 0.0).then(|| elapsed.mul_f64((1.0 - fraction) / fraction))
+    }
+}
+
+// The estimated work of the a at position `i`: one step for a itself, plus
+// for a sample of its b's the work of each, scaled up to all of them.
+fn vertex_work(data: &Adjacency, i: usize, draw: &mut impl FnMut(usize) -> usize) -> f64 {
+    let a_neighbors = &data[i].1;
+    if a_neighbors.is_empty() {
+        return 1.0;
+    }
+    let mut sampled = 0.0;
+    for _ in 0..DESCENTS {
+        let b = a_neighbors[draw(a_neighbors.len())];
+        // Finding b's list is a binary search, whether or not it has one.
+        sampled += (data.len() as f64).log2().max(1.0);
+        if let Ok(pos) = data.binary_search_by_key(&b, |(v, _)| *v) {
+            sampled += (a_neighbors.len() + data[pos].1.len()) as f64;
+        }
+    }
+    1.0 + sampled / DESCENTS as f64 * a_neighbors.len() as f64
+}
@@ -0,0 +1,147 @@
+// How the command line tool reports failure, for scripts and orchestration
+// systems that need to tell kinds of failure apart: each kind exits with its
+// own code, and with `--output json` the error is written as a JSON object
+// rather than a sentence.
+//
+//     {"error":{"kind":"parse","message":"...","context":{"path":"...","line":"3"}}}
+
+use std::fmt;
+
+use crate::{loader::LoadError, persist::FormatError, pool::PoolError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+    // Bad flags or settings.
+    Usage,
+    // An input file we couldn't make sense of.
+    Parse,
+    // An input we couldn't read, or an output we couldn't write.
+    Io,
+    // The work would need more than it's been allowed, e.g. the memory
+    // budget.
+    ResourceLimit,
+    // Something that should always hold didn't: engines disagreeing, a
+    // replay not matching its transcript, or a panic.
+    Invariant,
+}
+
+impl FailureKind {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FailureKind::Usage => 2,
+            FailureKind::Parse => 3,
+            FailureKind::Io => 4,
+            FailureKind::ResourceLimit => 5,
+            FailureKind::Invariant => 6,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FailureKind::Usage => "usage",
+            FailureKind::Parse => "parse",
+            FailureKind::Io => "io",
+            FailureKind::ResourceLimit => "resource-limit",
+            FailureKind::Invariant => "invariant",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Failure {
+    pub kind: FailureKind,
+    pub message: String,
+    // Details a program might want without parsing the message, such as the
+    // file and line a parse failed at.
+    pub context: Vec<(&'static str, String)>,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            context: Vec::new(),
+        }
+    }
+
+    pub fn usage(message: impl Into<String>) -> Self {
+        Self::new(FailureKind::Usage, message)
+    }
+
+    pub fn with(mut self, key: &'static str, value: impl ToString) -> Self {
+        self.context.push((key, value.to_string()));
+        self
+    }
+
+    // A failure to load the file at `path`.
+    pub fn load(path: impl fmt::Display, e: LoadError) -> Self {
+        let kind = match e {
+            LoadError::Io(_) => FailureKind::Io,
+            LoadError::Parse { .. } => FailureKind::Parse,
+            LoadError::Arity(_) => FailureKind::Usage,
+        };
+        let failure = Self::new(kind, format!("{}: {}", path, e)).with("path", path);
+        match e {
+            LoadError::Parse { line, .. } => failure.with("line", line),
+            LoadError::Io(_) | LoadError::Arity(_) => failure,
+        }
+    }
+
+    // A failure to read the index file at `path`.
+    pub fn index(path: impl fmt::Display, e: FormatError) -> Self {
+        let kind = match e {
+            FormatError::Io(_) => FailureKind::Io,
+            _ => FailureKind::Parse,
+        };
+        Self::new(kind, format!("{}: {}", path, e)).with("path", path)
+    }
+
+    // A failure to run a count on the worker pool.
+    pub fn pool(e: PoolError) -> Self {
+        let kind = match e {
+            PoolError::Spawn(_) => FailureKind::ResourceLimit,
+            PoolError::Stopped => FailureKind::Invariant,
+        };
+        Self::new(kind, e.to_string())
+    }
+
+    pub fn to_json(&self) -> String {
+        let context: Vec<String> = self
+            .context
+            .iter()
+            .map(|(k, v)| format!("{}:{}", json_string(k), json_string(v)))
+            .collect();
+        format!(
+            "{{\"error\":{{\"kind\":{},\"message\":{},\"context\":{{{}}}}}}}",
+            json_string(self.kind.name()),
+            json_string(&self.message),
+            context.join(",")
+        )
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// `s` as a quoted JSON string.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
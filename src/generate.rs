@@ -0,0 +1,133 @@
+// A generator of endless random edge streams, for load testing anything that
+// consumes edges as they arrive. Each line is an update: `+ u v` inserts the
+// edge (u, v), and `- u v` deletes one that was inserted earlier.
+//
+// Also random graphs to count when there's no input at all, and randomized
+// copies of a graph that's already loaded.
+
+use std::{
+    io::{self, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{index::Adjacency, null, Graph};
+
+#[derive(Clone, Debug)]
+pub struct StreamConfig {
+    // Updates per second, or None to go as fast as the output will take them.
+    pub rate: Option<f64>,
+    // The fraction of updates that are deletions, as long as there's
+    // something to delete.
+    pub delete_fraction: f64,
+    // Edges are drawn between ids in `0..vertices`.
+    pub vertices: u64,
+    // New edges land within `window` ids of a hot spot, which moves forward
+    // by `drift` ids per update, so the active part of the graph wanders over
+    // time the way real workloads do. A window as large as `vertices` turns
+    // this off.
+    pub window: u64,
+    pub drift: f64,
+    // Stop after this many updates, or never.
+    pub limit: Option<u64>,
+    pub seed: u64,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            rate: None,
+            delete_fraction: 0.1,
+            vertices: 1_000_000,
+            window: 10_000,
+            drift: 1.0,
+            limit: None,
+            seed: 0,
+        }
+    }
+}
+
+// Write updates to `out` until the limit is reached or the output goes away.
+pub fn stream<W: Write>(config: &StreamConfig, mut out: W) -> io::Result<u64> {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let vertices = config.vertices.max(1);
+    let window = config.window.clamp(1, vertices);
+    // Edges inserted and not yet deleted, so deletions always hit a live edge.
+    let mut live: Vec<(u64, u64)> = Vec::new();
+    let mut hot_spot = 0.0;
+    let start = Instant::now();
+    let mut sent = 0;
+    while config.limit.is_none_or(|limit| sent < limit) {
+        if let Some(rate) = config.rate {
+            // Sleep until this update is due, rather than a fixed interval
+            // per update, so time spent writing doesn't slow the rate.
+            let due = Duration::from_secs_f64(sent as f64 / rate);
+            if let Some(wait) = due.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        if !live.is_empty() && rng.gen_bool(config.delete_fraction.clamp(0.0, 1.0)) {
+            let (u, v) = live.swap_remove(rng.gen_range(0..live.len()));
+            writeln!(out, "- {} {}", u, v)?;
+        } else {
+            let base = hot_spot as u64;
+            let u = (base + rng.gen_range(0..window)) % vertices;
+            let v = (base + rng.gen_range(0..window)) % vertices;
+            live.push((u, v));
+            writeln!(out, "+ {} {}", u, v)?;
+        }
+        hot_spot = (hot_spot + config.drift) % vertices as f64;
+        sent += 1;
+    }
+    out.flush()?;
+    Ok(sent)
+}
+
+// An Erdős–Rényi graph on `nodes` vertices, with each edge (u, v) for u < v
+// present with probability `prob`. Rather than a coin flip per pair, which
+// would take quadratic time however sparse the graph, we jump straight from
+// one edge to the next by drawing the geometrically distributed gap between
+// them.
+pub fn random_graph<R: Rng>(nodes: u64, prob: f64, rng: &mut R) -> Adjacency {
+    let mut data = Vec::new();
+    if prob <= 0.0 {
+        return data;
+    }
+    let log_miss = (1.0 - prob).ln();
+    for u in 0..nodes {
+        let mut neighbors = Vec::new();
+        let mut v = u;
+        loop {
+            let gap = if prob >= 1.0 {
+                1
+            } else {
+                // The number of pairs skipped before the next edge, plus one.
+                (rng.gen::<f64>().ln() / log_miss).floor() as u64 + 1
+            };
+            v = v.saturating_add(gap);
+            if v >= nodes {
+                break;
+            }
+            neighbors.push(v);
+        }
+        if !neighbors.is_empty() {
+            data.push((u, neighbors));
+        }
+    }
+    data
+}
+
+// A random graph with the same in- and out-degrees as `graph`, made by
+// attempting `swaps` double-edge swaps on it (see `null::rewire`). Each seed
+// gives a different member of the ensemble, and always the same one.
+pub fn rewire(graph: &Graph, swaps: usize, seed: u64) -> Graph {
+    let edges: Vec<(u64, u64)> = graph
+        .csr()
+        .iter()
+        .flat_map(|(u, vs)| vs.iter().map(move |&v| (u, v)))
+        .collect();
+    let rewired = null::rewire(&edges, swaps, &mut StdRng::seed_from_u64(seed));
+    Graph::from_edges(rewired)
+}
@@ -0,0 +1,519 @@
+// The graph representation the library's entry points take: a directed edge
+// set in the sorted two-level layout (stored as a `Csr`), shared so that any
+// number of trie iterators can walk it at once.
+
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    sync::{Arc, OnceLock},
+    thread,
+};
+
+use crate::{
+    index::{Adjacency, Csr, Index, TrieIterator},
+    intersect, loader,
+    parallel::Ranges,
+};
+
+#[derive(Clone)]
+pub struct Graph {
+    data: Arc<Csr>,
+    // The edges turned around, for walking into a vertex rather than out of
+    // it. Only built if someone asks (see `reverse`), and then shared by
+    // every clone.
+    reverse: Arc<OnceLock<Arc<Csr>>>,
+}
+
+impl Graph {
+    // Build the graph from its edges, in any order.
+    pub fn from_edges(edges: Vec<(u64, u64)>) -> Self {
+        Self::from(loader::to_adjacency(edges))
+    }
+
+    pub fn csr(&self) -> &Arc<Csr> {
+        &self.data
+    }
+
+    // A trie iterator over the edges, at the start of the first level.
+    pub fn index(&self) -> Index {
+        Index::new(self.data.clone())
+    }
+
+    // The edges turned around, built on the first call. Building them sorts
+    // every edge, so a caller that will ask for the edges into many vertices
+    // (see `in_neighbors`) should call this once up front.
+    pub fn reverse(&self) -> &Arc<Csr> {
+        self.reverse.get_or_init(|| {
+            let edges = self
+                .data
+                .iter()
+                .flat_map(|(u, vs)| vs.iter().map(move |v| (*v, u)))
+                .collect();
+            Arc::new(Csr::from(&loader::to_adjacency(edges)))
+        })
+    }
+
+    // The vertices with an edge to `x`, in ascending order. If the reversed
+    // edges have been built this is a lookup; otherwise each list is
+    // searched for x, which costs a binary search per vertex but never
+    // builds anything the size of the graph.
+    pub fn in_neighbors(&self, x: u64) -> Cow<'_, [u64]> {
+        match self.reverse.get() {
+            Some(reverse) => Cow::Borrowed(reverse.neighbors(x)),
+            None => Cow::Owned(
+                self.data
+                    .iter()
+                    .filter(|(_, vs)| vs.binary_search(&x).is_ok())
+                    .map(|(u, _)| u)
+                    .collect(),
+            ),
+        }
+    }
+
+    // The subgraph induced by the vertices within `radius` hops of `v`
+    // along the edges: every edge of the graph between two of them, with
+    // their ids unchanged. It's a graph like any other, so local analyses
+    // (the triangles of an ego network, a census of its motifs) run on it
+    // as they would on the whole graph.
+    pub fn ego_subgraph(&self, v: u64, radius: usize) -> Graph {
+        let mut vertices = vec![v];
+        let mut frontier = vec![v];
+        for _ in 0..radius {
+            let mut next: Vec<u64> = frontier
+                .iter()
+                .flat_map(|&u| self.data.neighbors(u).iter().copied())
+                .collect();
+            next.sort_unstable();
+            next.dedup();
+            next.retain(|u| vertices.binary_search(u).is_err());
+            if next.is_empty() {
+                break;
+            }
+            vertices.extend_from_slice(&next);
+            vertices.sort_unstable();
+            frontier = next;
+        }
+        let data: Adjacency = vertices
+            .iter()
+            .map(|&u| {
+                let inside = self
+                    .data
+                    .neighbors(u)
+                    .iter()
+                    .copied()
+                    .filter(|w| vertices.binary_search(w).is_ok())
+                    .collect();
+                (u, inside)
+            })
+            .filter(|(_, vs): &(u64, Vec<u64>)| !vs.is_empty())
+            .collect();
+        Graph::from(&data)
+    }
+
+    // The neighbors of every vertex in `vertices`, flattened into one list
+    // so that a whole batch comes back in two arrays rather than one per
+    // vertex: the neighbors of `vertices[i]` are
+    // `targets[offsets[i]..offsets[i + 1]]`, so the degrees are the
+    // differences between consecutive offsets.
+    pub fn neighbors_batch(&self, vertices: &[u64]) -> (Vec<usize>, Vec<u64>) {
+        let mut offsets = Vec::with_capacity(vertices.len() + 1);
+        let mut targets = Vec::new();
+        offsets.push(0);
+        for &u in vertices {
+            targets.extend_from_slice(self.data.neighbors(u));
+            offsets.push(targets.len());
+        }
+        (offsets, targets)
+    }
+}
+
+impl From<&Adjacency> for Graph {
+    fn from(data: &Adjacency) -> Self {
+        Self::from(Csr::from(data))
+    }
+}
+
+impl From<Adjacency> for Graph {
+    fn from(data: Adjacency) -> Self {
+        Self::from(&data)
+    }
+}
+
+impl From<Csr> for Graph {
+    fn from(data: Csr) -> Self {
+        Self {
+            data: Arc::new(data),
+            reverse: Arc::default(),
+        }
+    }
+}
+
+// Builds a `Graph` from edges added one at a time, cleaning them up on the
+// way: duplicate edges are always dropped, and optionally so are self-loops,
+// and each edge is given its reverse too, for an undirected graph. Any of
+// these left in the input would silently change the count.
+#[derive(Clone, Debug, Default)]
+pub struct GraphBuilder {
+    edges: Vec<(u64, u64)>,
+    undirected: bool,
+    drop_self_loops: bool,
+}
+
+impl GraphBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Store both directions of every edge.
+    pub fn undirected(mut self) -> Self {
+        self.undirected = true;
+        self
+    }
+
+    // Leave out edges from a vertex to itself.
+    pub fn drop_self_loops(mut self) -> Self {
+        self.drop_self_loops = true;
+        self
+    }
+
+    pub fn add_edge(&mut self, u: u64, v: u64) {
+        self.edges.push((u, v));
+    }
+
+    // Sort and deduplicate the edges, and build the graph from them.
+    pub fn finalize(self) -> Graph {
+        let mut edges = self.edges;
+        if self.drop_self_loops {
+            edges.retain(|(u, v)| u != v);
+        }
+        if self.undirected {
+            edges.extend_from_within(..);
+            let n = edges.len() / 2;
+            for e in &mut edges[n..] {
+                *e = (e.1, e.0);
+            }
+        }
+        edges.sort_unstable();
+        edges.dedup();
+        Graph::from(loader::to_adjacency(edges))
+    }
+}
+
+impl Extend<(u64, u64)> for GraphBuilder {
+    fn extend<I: IntoIterator<Item = (u64, u64)>>(&mut self, edges: I) {
+        self.edges.extend(edges);
+    }
+}
+
+// Count the triangles in the graph with the leapfrog loop nest written out by
+// hand for the triangle query.
+pub fn count_triangles(graph: &Graph) -> u64 {
+    let mut count = 0_u64;
+    for_each_triangle(graph, |_, _, _| count += 1);
+    count
+}
+
+// Every triangle (a, b, c) in the graph, in order.
+pub fn triangles(graph: &Graph) -> Vec<(u64, u64, u64)> {
+    let mut triangles = Vec::new();
+    for_each_triangle(graph, |a, b, c| triangles.push((a, b, c)));
+    triangles
+}
+
+// Call `f` with each triangle (a, b, c) in the graph, in order. This is the
+// loop nest `count_triangles` runs, and `f` is inlined into it, so counting
+// pays nothing for the triangles it doesn't look at.
+pub fn for_each_triangle(graph: &Graph, f: impl FnMut(u64, u64, u64)) {
+    // Since we're finding triangles in a graph, use the same data for all
+    // three.
+    for_each_triangle_in(
+        &mut graph.index(),
+        &mut graph.index(),
+        &mut graph.index(),
+        f,
+    );
+}
+
+// Count the triangles of R(a, b), S(b, c), T(a, c) with each atom over a
+// graph of its own, like three kinds of edge between the same vertices
+// (follows, mentions, replies). Over one graph three times, this is
+// `count_triangles`.
+pub fn count_triangles_across(r: &Graph, s: &Graph, t: &Graph) -> u64 {
+    let mut count = 0_u64;
+    for_each_triangle_across(r, s, t, |_, _, _| count += 1);
+    count
+}
+
+// Call `f` with each triangle (a, b, c) of the query over the three graphs,
+// in order.
+pub fn for_each_triangle_across(r: &Graph, s: &Graph, t: &Graph, f: impl FnMut(u64, u64, u64)) {
+    for_each_triangle_in(&mut r.index(), &mut s.index(), &mut t.index(), f);
+}
+
+// The loop nest behind `for_each_triangle`, over any trie iterators for the
+// three atoms, so that other index backends (or `checked::Checked` wrappers)
+// can be dropped in without touching the join.
+pub fn for_each_triangle_in(
+    r: &mut impl TrieIterator,
+    s: &mut impl TrieIterator,
+    t: &mut impl TrieIterator,
+    mut f: impl FnMut(u64, u64, u64),
+) {
+    // Q(a, b, c) <- R(a, b), S(b, c), T(a, c);
+    while let (Some(r_a), Some(t_a)) = (r.value(), t.value()) {
+        match r_a.cmp(&t_a) {
+            Ordering::Less => r.seek(t_a),
+            Ordering::Greater => t.seek(r_a),
+            Ordering::Equal => {
+                rooted(r, s, t, &mut f);
+                // Move on to the next value of a.
+                r.next();
+                t.next();
+            }
+        }
+    }
+}
+
+// Count the triangles that `x` is one of a, b, or c in, as `--per-vertex`
+// would for x alone. See `for_each_triangle_with`.
+pub fn count_triangles_with(graph: &Graph, x: u64) -> u64 {
+    let (mut r, mut s, mut t) = (graph.index(), graph.index(), graph.index());
+    let mut count = 0_u64;
+    r.seek(x);
+    t.seek(x);
+    if r.value() == Some(x) && t.value() == Some(x) {
+        rooted(&mut r, &mut s, &mut t, &mut |_, _, _| count += 1);
+    }
+    // With x as b or c, only the size of the last intersection matters.
+    let (out, into) = (graph.csr(), graph.in_neighbors(x));
+    for &a in into.iter() {
+        count += intersect::count(out.neighbors(a), out.neighbors(x));
+        count += intersect::count(out.neighbors(a), &into);
+    }
+    count
+}
+
+// Call `f` with each triangle (a, b, c) that `x` is one of a, b, or c in.
+// Rather than enumerating every triangle and keeping those with x, the join
+// starts from x: with x as a, the cursors seek straight to it and carry on
+// from there; with x as b or c, the other two vertices come from the edges
+// into x intersected with those out of x or of a. Only x's neighborhood is
+// touched if the graph's reversed edges have been built (see
+// `Graph::reverse`); if not, finding the edges into x takes a search of
+// every list, though nothing is built. A triangle with x in more than one
+// position, which takes a self-loop, is passed to `f` once for each, as
+// `--per-vertex` counts it.
+pub fn for_each_triangle_with(graph: &Graph, x: u64, mut f: impl FnMut(u64, u64, u64)) {
+    let (mut r, mut s, mut t) = (graph.index(), graph.index(), graph.index());
+    r.seek(x);
+    t.seek(x);
+    if r.value() == Some(x) && t.value() == Some(x) {
+        rooted(&mut r, &mut s, &mut t, &mut f);
+    }
+    let (out, into) = (graph.csr(), graph.in_neighbors(x));
+    // x as b: (a, x, c) for each edge a -> x, with c out of both.
+    for &a in into.iter() {
+        for c in intersect::common(out.neighbors(a), out.neighbors(x)) {
+            f(a, x, c);
+        }
+    }
+    // x as c: (a, b, x) for each edge a -> x, with b out of a and into x.
+    for &a in into.iter() {
+        for b in intersect::common(out.neighbors(a), &into) {
+            f(a, b, x);
+        }
+    }
+}
+
+// Count the triangles as `count_triangles` does, with the values of a split
+// between `threads` threads. Each thread walks the graph with cursors of its
+// own, and takes its values of a from `parallel`'s work-stealing ranges, so
+// every a is an independent unit of work.
+pub fn count_triangles_parallel(graph: &Graph, threads: usize) -> u64 {
+    let threads = threads.max(1);
+    let work = Ranges::new(graph.data.len(), threads);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|me| {
+                let work = &work;
+                scope.spawn(move || {
+                    let (mut r, mut s, mut t) = (graph.index(), graph.index(), graph.index());
+                    let mut count = 0_u64;
+                    work.run(me, |i| {
+                        let a = graph.data.source(i);
+                        r.seek(a);
+                        t.seek(a);
+                        if r.value() == Some(a) && t.value() == Some(a) {
+                            rooted(&mut r, &mut s, &mut t, &mut |_, _, _| count += 1);
+                        }
+                    });
+                    count
+                })
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).sum()
+    })
+}
+
+// The triangles for the value of a that `r` and `t` are both at, leaving
+// them back at it in the first level.
+fn rooted(
+    r: &mut impl TrieIterator,
+    s: &mut impl TrieIterator,
+    t: &mut impl TrieIterator,
+    f: &mut impl FnMut(u64, u64, u64),
+) {
+    let Some(a) = r.value() else {
+        return;
+    };
+    // a is now bound.
+    r.down();
+    t.down();
+    while let (Some(r_b), Some(s_b)) = (r.value(), s.value()) {
+        match r_b.cmp(&s_b) {
+            Ordering::Less => r.seek(s_b),
+            Ordering::Greater => s.seek(r_b),
+            Ordering::Equal => {
+                // b is now bound.
+                s.down();
+                t.reset();
+                while let (Some(s_c), Some(t_c)) = (s.value(), t.value()) {
+                    match s_c.cmp(&t_c) {
+                        Ordering::Less => {
+                            s.seek(t_c);
+                        }
+                        Ordering::Greater => {
+                            t.seek(s_c);
+                        }
+                        Ordering::Equal => {
+                            // We found a triangle!
+                            f(a, r_b, s_c);
+                            s.next();
+                            t.next();
+                        }
+                    }
+                }
+                // Move on to the next value of b.
+                s.up();
+                s.next();
+            }
+        }
+    }
+    s.reset();
+    r.up();
+    t.up();
+}
+
+// Count the k-cliques in the graph: the bindings of x1, ..., xk with an edge
+// from xi to xj for every i < j, which for k = 3 is the triangle query above
+// (and is counted with its hand-written loop nest). As with triangles, each
+// clique of an undirected graph is found once per ordering of its vertices
+// unless the graph is oriented first.
+pub fn count_cliques(graph: &Graph, k: usize) -> u64 {
+    if k == 3 {
+        return count_triangles(graph);
+    }
+    let mut count = 0_u64;
+    for_each_clique(graph, k, |_| count += 1);
+    count
+}
+
+// Call `f` with each k-clique (x1, ..., xk) in the graph, in order.
+//
+// There's one trie iterator per edge (xi, xj), i < j, and the loop nest is
+// the triangle one carried on to k levels: binding xi intersects the first
+// level of every iterator whose edge starts at xi with the second level of
+// every iterator whose edge ends there, and then steps the former down to
+// take part in binding the later variables. The empty clique is the one
+// 0-clique, and the 1-cliques are the vertices on any edge.
+pub fn for_each_clique(graph: &Graph, k: usize, mut f: impl FnMut(&[u64])) {
+    if k == 0 {
+        f(&[]);
+        return;
+    }
+    if k == 1 {
+        let mut vertices: Vec<u64> = graph
+            .data
+            .iter()
+            .flat_map(|(u, vs)| std::iter::once(u).chain(vs.iter().copied()))
+            .collect();
+        vertices.sort_unstable();
+        vertices.dedup();
+        for u in vertices {
+            f(&[u]);
+        }
+        return;
+    }
+    let edges: Vec<(usize, usize)> = (0..k)
+        .flat_map(|i| (i + 1..k).map(move |j| (i, j)))
+        .collect();
+    let levels: Vec<Vec<usize>> = (0..k)
+        .map(|x| {
+            (0..edges.len())
+                .filter(|&e| edges[e].0 == x || edges[e].1 == x)
+                .collect()
+        })
+        .collect();
+    let outgoing: Vec<Vec<usize>> = (0..k)
+        .map(|x| (0..edges.len()).filter(|&e| edges[e].0 == x).collect())
+        .collect();
+    let mut iters: Vec<Index> = edges.iter().map(|_| graph.index()).collect();
+    let mut bound = Vec::with_capacity(k);
+    bind(&mut iters, &levels, &outgoing, &mut bound, &mut f);
+}
+
+// Bind the next variable of `for_each_clique` to each value its iterators
+// agree on in turn, recursing into the rest.
+fn bind(
+    iters: &mut [Index],
+    levels: &[Vec<usize>],
+    outgoing: &[Vec<usize>],
+    bound: &mut Vec<u64>,
+    f: &mut impl FnMut(&[u64]),
+) {
+    let x = bound.len();
+    for &e in &levels[x] {
+        iters[e].reset();
+    }
+    while let Some(v) = leapfrog(iters, &levels[x]) {
+        bound.push(v);
+        if bound.len() == levels.len() {
+            f(bound);
+        } else {
+            for &e in &outgoing[x] {
+                iters[e].down();
+            }
+            bind(iters, levels, outgoing, bound, f);
+            for &e in &outgoing[x] {
+                iters[e].up();
+            }
+        }
+        bound.pop();
+        for &e in &levels[x] {
+            iters[e].next();
+        }
+    }
+}
+
+// Move the iterators in `which` forward to the first value they're all at,
+// if there is one.
+fn leapfrog(iters: &mut [Index], which: &[usize]) -> Option<u64> {
+    let mut target = 0;
+    for &e in which {
+        target = target.max(iters[e].value()?);
+    }
+    loop {
+        let mut agreed = true;
+        for &e in which {
+            iters[e].seek(target);
+            let v = iters[e].value()?;
+            if v > target {
+                target = v;
+                agreed = false;
+            }
+        }
+        if agreed {
+            return Some(target);
+        }
+    }
+}
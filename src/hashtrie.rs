@@ -0,0 +1,169 @@
+// A trie over edges kept in a hash map from each source to its (unsorted)
+// neighbors, for data that's built once per query and thrown away, where
+// sorting the whole edge set up front would cost more than the join. Building
+// it is a single O(m) pass. A neighbor list is only sorted the first time a
+// cursor moves down into it, so lists the join never binds never pay for a
+// sort; the sources are sorted the first time a cursor needs them, which
+// costs O(n log n) in the number of sources rather than the edges.
+//
+// The sorted lists are shared between all of the cursors over a trie. A
+// list is moved out of its block to be sorted, so each one is only ever held
+// once, unsorted or sorted.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+};
+
+use crate::index::{gallop, TrieIterator};
+
+pub struct HashTrie {
+    blocks: HashMap<u64, Block>,
+    keys: OnceLock<Vec<u64>>,
+}
+
+struct Block {
+    // Emptied when the list is sorted.
+    unsorted: Mutex<Vec<u64>>,
+    sorted: OnceLock<Arc<[u64]>>,
+}
+
+impl HashTrie {
+    // Group the edges by source, in any order. Duplicates are dropped when a
+    // list is sorted.
+    pub fn from_edges(edges: impl IntoIterator<Item = (u64, u64)>) -> Self {
+        let mut lists: HashMap<u64, Vec<u64>> = HashMap::new();
+        for (u, v) in edges {
+            lists.entry(u).or_default().push(v);
+        }
+        let blocks = lists
+            .into_iter()
+            .map(|(u, unsorted)| {
+                let block = Block {
+                    unsorted: Mutex::new(unsorted),
+                    sorted: OnceLock::new(),
+                };
+                (u, block)
+            })
+            .collect();
+        Self {
+            blocks,
+            keys: OnceLock::new(),
+        }
+    }
+
+    // A trie iterator over the edges, at the start of the first level.
+    pub fn iter(self: &Arc<Self>) -> HashTrieIterator {
+        HashTrieIterator {
+            trie: self.clone(),
+            level: Level::Upper(0),
+        }
+    }
+
+    // How many of the neighbor lists have been sorted so far, out of how many
+    // there are.
+    pub fn sorted_lists(&self) -> (usize, usize) {
+        let sorted = self
+            .blocks
+            .values()
+            .filter(|b| b.sorted.get().is_some())
+            .count();
+        (sorted, self.blocks.len())
+    }
+
+    fn keys(&self) -> &[u64] {
+        self.keys.get_or_init(|| {
+            let mut keys: Vec<u64> = self.blocks.keys().copied().collect();
+            keys.sort_unstable();
+            keys
+        })
+    }
+
+    // The sorted neighbors of `u`, sorting them if this is the first time
+    // anyone's asked.
+    fn neighbors(&self, u: u64) -> Arc<[u64]> {
+        match self.blocks.get(&u) {
+            Some(block) => block
+                .sorted
+                .get_or_init(|| {
+                    let mut unsorted = block
+                        .unsorted
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner);
+                    let mut list = std::mem::take(&mut *unsorted);
+                    list.sort_unstable();
+                    list.dedup();
+                    list.into()
+                })
+                .clone(),
+            None => Arc::new([]),
+        }
+    }
+}
+
+enum Level {
+    Upper(usize),
+    // The bound source's position, its sorted neighbors, and our position
+    // in them.
+    Lower(usize, Arc<[u64]>, usize),
+}
+
+pub struct HashTrieIterator {
+    trie: Arc<HashTrie>,
+    level: Level,
+}
+
+impl TrieIterator for HashTrieIterator {
+    fn seek(&mut self, v: u64) {
+        match &mut self.level {
+            Level::Upper(i) => *i = gallop(self.trie.keys(), *i, |&x| x < v),
+            Level::Lower(_, list, j) => *j = gallop(list, *j, |&x| x < v),
+        }
+    }
+
+    fn up(&mut self) {
+        // At the top level, there's nothing bound to undo.
+        if let Level::Lower(i, _, _) = self.level {
+            self.level = Level::Upper(i);
+        }
+    }
+
+    fn down(&mut self) {
+        // At the bottom level, there's no level below.
+        if let Level::Upper(i) = self.level {
+            let list = match self.trie.keys().get(i) {
+                Some(&u) => self.trie.neighbors(u),
+                None => Arc::new([]),
+            };
+            self.level = Level::Lower(i, list, 0);
+        }
+    }
+
+    fn reset(&mut self) {
+        match &mut self.level {
+            Level::Upper(i) => *i = 0,
+            Level::Lower(_, _, j) => *j = 0,
+        }
+    }
+
+    fn value(&self) -> Option<u64> {
+        match &self.level {
+            Level::Upper(i) => self.trie.keys().get(*i).copied(),
+            Level::Lower(_, list, j) => list.get(*j).copied(),
+        }
+    }
+
+    fn next(&mut self) {
+        match &mut self.level {
+            Level::Upper(i) => *i += 1,
+            Level::Lower(_, _, j) => *j += 1,
+        }
+    }
+
+    fn rest(&self) -> Option<&[u64]> {
+        match &self.level {
+            Level::Upper(_) => None,
+            Level::Lower(_, list, j) => Some(list.get(*j..).unwrap_or(&[])),
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use std::{io, path::Path, sync::Arc};
+
+use crate::persist::{self, FormatError};
+
+// The sorted two-level layout an `Index` walks: each first-level value in
+// ascending order, paired with the ascending second-level values under it.
+pub type Adjacency = Vec<(u64, Vec<u64>)>;
+
+// The operations a join needs from a trie over a relation: walk the values at
+// the current level in sorted order, skip ahead with seek, and move down into
+// (binding) or back up out of (unbinding) the current value.
+pub trait TrieIterator {
+    // In whatever level we are currently in, move the iterator to the given
+    // value, or to the next value that comes after.
+    fn seek(&mut self, v: u64);
+    // Move back up one level. This "unbinds" the variable of that level. At
+    // the top level, it does nothing.
+    fn up(&mut self);
+    // Move down one level, to the start of the values under the current one.
+    // This "binds" the current self.value(). At the bottom level, it does
+    // nothing.
+    fn down(&mut self);
+    // Reset the iterator to the start of its current level. Variables bound
+    // at the levels above remain bound.
+    fn reset(&mut self);
+    // The current value at the current level, or None if we've run off the
+    // end of it.
+    fn value(&self) -> Option<u64>;
+    // Advance to the next value.
+    fn next(&mut self);
+    // The values from the current position to the end of the current level,
+    // if they are stored contiguously. Callers that only need to know how
+    // many values a level has in common with others can then intersect whole
+    // slices rather than stepping through them one at a time.
+    fn rest(&self) -> Option<&[u64]> {
+        None
+    }
+}
+
+// The integer type a `Csr` stores vertex ids as. Ids are u64 everywhere
+// else, and only narrowed for storage, so a graph whose ids all fit in 32
+// bits can keep its arrays (and the cache lines its intersections touch) at
+// half the size.
+pub trait VertexId: Copy + Ord + Into<u64> + TryFrom<u64> + Send + Sync + 'static {
+    // The ids as u64s, if that's how they're stored, for `TrieIterator::rest`.
+    fn as_u64s(ids: &[Self]) -> Option<&[u64]>;
+}
+
+impl VertexId for u64 {
+    fn as_u64s(ids: &[Self]) -> Option<&[u64]> {
+        Some(ids)
+    }
+}
+
+impl VertexId for u32 {
+    fn as_u64s(_: &[Self]) -> Option<&[u64]> {
+        None
+    }
+}
+
+// The same two levels in compressed sparse row form, which is how an `Index`
+// stores them: the first-level values in one array, all of the second-level
+// lists back to back in another, and for each first-level value the offset
+// its list starts at. Three allocations in all, however many lists there
+// are, and walking from one list to the next walks straight through memory.
+#[derive(Clone, Debug, Default)]
+pub struct Csr<V: VertexId = u64> {
+    sources: Vec<V>,
+    // One more than there are sources: the list under `sources[i]` is
+    // `targets[offsets[i]..offsets[i + 1]]`.
+    offsets: Vec<usize>,
+    targets: Vec<V>,
+    // Each list's smallest and largest value, parallel to `sources` and None
+    // for an empty list, so a seek past a list's last value can skip the
+    // search instead of galloping to the end.
+    bounds: Vec<Option<(V, V)>>,
+}
+
+impl<V: VertexId> Csr<V> {
+    // The two levels of `data` with their ids stored as `V`, or None if
+    // one of them doesn't fit.
+    pub fn try_from_adjacency(data: &Adjacency) -> Option<Self> {
+        let narrow = |v: u64| V::try_from(v).ok();
+        let mut offsets = Vec::with_capacity(data.len() + 1);
+        let mut bounds = Vec::with_capacity(data.len());
+        let mut targets = Vec::with_capacity(data.iter().map(|(_, vs)| vs.len()).sum());
+        offsets.push(0);
+        for (_, vs) in data {
+            for &v in vs {
+                targets.push(narrow(v)?);
+            }
+            let list = &targets[*offsets.last().unwrap()..];
+            bounds.push(list.first().zip(list.last()).map(|(&lo, &hi)| (lo, hi)));
+            offsets.push(targets.len());
+        }
+        Some(Self {
+            sources: data
+                .iter()
+                .map(|(u, _)| narrow(*u))
+                .collect::<Option<_>>()?,
+            offsets,
+            targets,
+            bounds,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    // The `i`th first-level value.
+    pub fn source(&self, i: usize) -> u64 {
+        self.sources[i].into()
+    }
+
+    // The second-level values under the `i`th first-level value.
+    pub fn targets(&self, i: usize) -> &[V] {
+        &self.targets[self.offsets[i]..self.offsets[i + 1]]
+    }
+
+    // The second-level values under `u`, which is empty if it isn't in the
+    // first level.
+    pub fn neighbors(&self, u: u64) -> &[V] {
+        match self.sources.binary_search_by(|&x| x.into().cmp(&u)) {
+            Ok(i) => self.targets(i),
+            Err(_) => &[],
+        }
+    }
+
+    // Each first-level value with the values under it, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &[V])> + '_ {
+        (0..self.len()).map(|i| (self.source(i), self.targets(i)))
+    }
+
+    pub fn to_adjacency(&self) -> Adjacency {
+        self.iter()
+            .map(|(u, vs)| (u, vs.iter().map(|&v| v.into()).collect()))
+            .collect()
+    }
+
+    // Roughly how many bytes of heap the arrays take up.
+    pub fn heap_size(&self) -> usize {
+        (self.sources.capacity() + self.targets.capacity()) * std::mem::size_of::<V>()
+            + self.offsets.capacity() * std::mem::size_of::<usize>()
+            + self.bounds.capacity() * std::mem::size_of::<Option<(V, V)>>()
+    }
+}
+
+impl From<&Adjacency> for Csr {
+    fn from(data: &Adjacency) -> Self {
+        Self::try_from_adjacency(data).expect("every id fits in a u64")
+    }
+}
+
+// Points at either a first-level entry we're located at (Upper), or a
+// second-level entry we're located at along with the parent in the first level
+// (Lower). In the second level, the position is an offset into the flat array
+// of targets, within the bounds of the parent's list.
+enum Position {
+    Upper(usize),
+    Lower {
+        parent: usize,
+        start: usize,
+        at: usize,
+        end: usize,
+    },
+}
+
+// A trie iterator as described in the Leapfrog Triejoin paper, which can
+// iterate across the first variable and then drop down to the values where that
+// first variable is bound.
+pub struct Index<V: VertexId = u64> {
+    level: Position,
+    // Data is stored in a two-level index:
+    //
+    //    1     2     3      4    5 6 7
+    //   /|\   / \   /|\    /|\   | | |
+    //  2 3 4 4   5 4 6 7  5 7 8  8 7 8
+    //
+    data: Arc<Csr<V>>,
+}
+
+impl<V: VertexId> Index<V> {
+    pub fn new(data: Arc<Csr<V>>) -> Self {
+        Self {
+            level: Position::Upper(0),
+            data,
+        }
+    }
+}
+
+impl Index {
+    // A fingerprint of the edges in the index. See `fingerprint`.
+    pub fn fingerprint(&self) -> u64 {
+        self.data
+            .iter()
+            .map(|(u, vs)| fingerprint_list(u, vs))
+            .fold(0, u64::wrapping_add)
+    }
+
+    // Write the index's data to `path` in the format of `persist`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        persist::save(&self.data.to_adjacency(), path)
+    }
+
+    // An index over the data saved at `path` by `save` (or `--save-index`).
+    pub fn load(path: &Path) -> Result<Self, FormatError> {
+        Ok(Self::new(Arc::new(Csr::from(&persist::load(path)?))))
+    }
+
+    // The second-level values under `u`. See `neighbors`.
+    pub fn neighbors(&self, u: u64) -> &[u64] {
+        self.data.neighbors(u)
+    }
+
+    // The values under both `u` and `v`. See `intersect`.
+    pub fn intersect(&self, u: u64, v: u64) -> impl Iterator<Item = u64> + '_ {
+        crate::intersect::common(self.neighbors(u), self.neighbors(v))
+    }
+}
+
+// The neighbors of `u`: the values under it in the second level, which is
+// empty if it isn't in the first.
+pub fn neighbors(data: &Adjacency, u: u64) -> &[u64] {
+    match data.binary_search_by_key(&u, |(x, _)| *x) {
+        Ok(i) => &data[i].1,
+        Err(_) => &[],
+    }
+}
+
+// The common neighbors of `u` and `v`, in ascending order. For an undirected
+// graph (both directions of each edge stored) these are the third vertices of
+// the triangles the edge (u, v) is in, or would close if it were added.
+pub fn intersect(data: &Adjacency, u: u64, v: u64) -> impl Iterator<Item = u64> + '_ {
+    crate::intersect::common(neighbors(data, u), neighbors(data, v))
+}
+
+// The graph with its ids renumbered onto 0..n in the same order, along with
+// the original id of each new one, if its ids are sparse enough that an
+// array indexed by them would be larger than the graph itself. Keeping the
+// order keeps every list sorted, and any orientation by id unchanged, so
+// counts over the renumbered graph are the same.
+pub(crate) fn compact(data: &Adjacency) -> Option<(Adjacency, Vec<u64>)> {
+    let edges: usize = data.iter().map(|(_, vs)| vs.len()).sum();
+    if crate::parallel::universe(data) <= data.len() + edges {
+        return None;
+    }
+    let mut original: Vec<u64> = data
+        .iter()
+        .flat_map(|(u, vs)| std::iter::once(*u).chain(vs.iter().copied()))
+        .collect();
+    original.sort_unstable();
+    original.dedup();
+    let new = |u: &u64| original.binary_search(u).unwrap() as u64;
+    let compacted = data
+        .iter()
+        .map(|(u, vs)| (new(u), vs.iter().map(new).collect()))
+        .collect();
+    Some((compacted, original))
+}
+
+// Roughly how many bytes of heap the index's data takes up.
+pub fn heap_size(data: &Adjacency) -> usize {
+    data.capacity() * std::mem::size_of::<(u64, Vec<u64>)>()
+        + data
+            .iter()
+            .map(|(_, vs)| vs.capacity() * std::mem::size_of::<u64>())
+            .sum::<usize>()
+}
+
+// A fast fingerprint of a set of edges that doesn't depend on the order they
+// were inserted or stored in: the wrapping sum of a strong hash of each edge.
+// Unlike `std`'s hashers it's stable across platforms and releases, so it can
+// be recorded and compared later.
+pub fn fingerprint(data: &Adjacency) -> u64 {
+    data.iter()
+        .map(|(u, vs)| fingerprint_list(*u, vs))
+        .fold(0, u64::wrapping_add)
+}
+
+// The contribution of the edges from `u` to each of `vs` to a fingerprint.
+// Fingerprints of disjoint edge sets combine with a wrapping add.
+pub fn fingerprint_list(u: u64, vs: &[u64]) -> u64 {
+    let hu = mix(u);
+    vs.iter().map(|v| mix(hu ^ v)).fold(0, u64::wrapping_add)
+}
+
+// The splitmix64 finalizer.
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+// The first position at or after `from` whose element isn't `before` the
+// target, for a slice sorted so that `before` holds for a prefix of it. As the
+// Leapfrog Triejoin paper prescribes, this gallops forward from `from`,
+// doubling the step until it overshoots and then binary searching the last
+// step, so a seek costs O(log d) for a distance d moved rather than O(log n)
+// over the whole level. Seeking backwards (a target before `from`) falls back
+// to searching the whole slice.
+pub(crate) fn gallop<T>(slice: &[T], from: usize, before: impl Fn(&T) -> bool) -> usize {
+    if from > 0 && !slice.get(from - 1).is_some_and(&before) {
+        return slice.partition_point(before);
+    }
+    let mut lo = from;
+    let mut step = 1;
+    while lo + step <= slice.len() && before(&slice[lo + step - 1]) {
+        lo += step;
+        step *= 2;
+    }
+    let hi = (lo + step).min(slice.len());
+    lo + slice[lo..hi].partition_point(before)
+}
+
+impl<V: VertexId> TrieIterator for Index<V> {
+    // In whatever level we are currently in, move the iterator to the given
+    // value, or to the next value that comes after.
+    fn seek(&mut self, v: u64) {
+        match &mut self.level {
+            Position::Upper(i) => *i = gallop(&self.data.sources, *i, |&x| x.into() < v),
+            Position::Lower {
+                parent,
+                start,
+                at,
+                end,
+            } => {
+                // Past the list's last value there's nothing to search for.
+                if self
+                    .data
+                    .bounds
+                    .get(*parent)
+                    .is_none_or(|b| b.is_none_or(|(_, hi)| hi.into() < v))
+                {
+                    *at = *end;
+                    return;
+                }
+                let list = &self.data.targets[*start..*end];
+                *at = *start + gallop(list, *at - *start, |&x| x.into() < v);
+            }
+        }
+    }
+
+    // Move from the lower position back up to the upper position. This
+    // "unbinds" the first variable.
+    fn up(&mut self) {
+        // At the top level, there's nothing bound to undo.
+        if let Position::Lower { parent, .. } = self.level {
+            self.level = Position::Upper(parent);
+        }
+    }
+
+    // Move from the upper position down to the lower position. This "binds" the
+    // current self.value().
+    fn down(&mut self) {
+        // At the bottom level, there's no level below.
+        if let Position::Upper(i) = self.level {
+            // Past the end of the first level there's nothing to bind, so
+            // the level below is empty.
+            let (start, end) = match self.data.offsets.get(i..i + 2) {
+                Some(&[start, end]) => (start, end),
+                _ => (0, 0),
+            };
+            self.level = Position::Lower {
+                parent: i,
+                start,
+                at: start,
+                end,
+            };
+        }
+    }
+
+    // Reset the iterator to the start, at its current level. A bound variable
+    // (i.e., if we are in the Lower position) remains bound.
+    fn reset(&mut self) {
+        match &mut self.level {
+            Position::Upper(i) => *i = 0,
+            Position::Lower { start, at, .. } => *at = *start,
+        }
+    }
+
+    // The current value we are pointing at, at whatever level we're at.
+    fn value(&self) -> Option<u64> {
+        match self.level {
+            Position::Upper(i) => self.data.sources.get(i).map(|&u| u.into()),
+            Position::Lower { at, end, .. } => (at < end).then(|| self.data.targets[at].into()),
+        }
+    }
+
+    // Both levels are plain sorted arrays, though only of u64s when the ids
+    // aren't narrowed.
+    fn rest(&self) -> Option<&[u64]> {
+        match self.level {
+            Position::Upper(_) => None,
+            Position::Lower { at, end, .. } => {
+                V::as_u64s(self.data.targets.get(at..end).unwrap_or(&[]))
+            }
+        }
+    }
+
+    // Advance to the next value.
+    fn next(&mut self) {
+        match &mut self.level {
+            Position::Upper(i) => {
+                *i += 1;
+            }
+            Position::Lower { at, .. } => {
+                *at += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+// Kernels for intersecting sorted, duplicate-free lists of vertex ids. These
+// work on plain slices rather than trie iterators so that executors which have
+// already located both lists can skip the per-step dispatch.
+
+// With the `simd` feature, lists within this factor of each other's length
+// are merged four values at a time with AVX2, where the CPU has it. Past it,
+// the block merge mostly compares blocks of the long list that can't match,
+// and the plain merge does as well.
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+const SIMD_MAX_SKEW: usize = 8;
+
+// The number of values the two sorted lists have in common.
+pub fn count(a: &[u64], b: &[u64]) -> u64 {
+    let Some([a, b]) = overlap([a, b]) else {
+        return 0;
+    };
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        let (short, long) = (a.len().min(b.len()), a.len().max(b.len()));
+        if short >= 4 && long <= short * SIMD_MAX_SKEW && is_x86_feature_detected!("avx2") {
+            // SAFETY: we just checked that the CPU supports AVX2.
+            return unsafe { simd::count(a, b) };
+        }
+    }
+    merge_count(a, b)
+}
+
+fn merge_count(a: &[u64], b: &[u64]) -> u64 {
+    let (mut i, mut j) = (0, 0);
+    let mut count = 0;
+    while i < a.len() && j < b.len() {
+        let (x, y) = (a[i], b[j]);
+        // Advance whichever side is behind (or both on a match) without
+        // branching on the comparison, which the merge can't predict.
+        count += (x == y) as u64;
+        i += (x <= y) as usize;
+        j += (y <= x) as usize;
+    }
+    count
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod simd {
+    use std::arch::x86_64::*;
+
+    // The block merge of Schlegel et al.: compare four values of `a` with
+    // four of `b`, all sixteen pairs at once, then move past whichever block
+    // ends lower (or both, if they end on the same value). Since neither list
+    // has duplicates, each value of `a`'s block matches at most one of `b`'s,
+    // so the matches are the set bits of the OR of the four comparisons. The
+    // tails are left to the plain merge.
+    //
+    // SAFETY: the caller must make sure the CPU supports AVX2.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn count(a: &[u64], b: &[u64]) -> u64 {
+        let (mut i, mut j) = (0, 0);
+        let mut count = 0;
+        while i + 4 <= a.len() && j + 4 <= b.len() {
+            // SAFETY: both blocks are in bounds, and the loads are unaligned.
+            let (va, mut vb) = unsafe {
+                (
+                    _mm256_loadu_si256(a.as_ptr().add(i) as *const __m256i),
+                    _mm256_loadu_si256(b.as_ptr().add(j) as *const __m256i),
+                )
+            };
+            let mut matched = _mm256_cmpeq_epi64(va, vb);
+            for _ in 0..3 {
+                // Rotate `b`'s block by one lane.
+                vb = _mm256_permute4x64_epi64::<0b00_11_10_01>(vb);
+                matched = _mm256_or_si256(matched, _mm256_cmpeq_epi64(va, vb));
+            }
+            count += _mm256_movemask_pd(_mm256_castsi256_pd(matched)).count_ones() as u64;
+            let (x, y) = (a[i + 3], b[j + 3]);
+            i += 4 * (x <= y) as usize;
+            j += 4 * (y <= x) as usize;
+        }
+        count + super::merge_count(&a[i..], &b[j..])
+    }
+}
+
+// The values the two sorted lists have in common, in ascending order.
+pub fn common<'a>(a: &'a [u64], b: &'a [u64]) -> impl Iterator<Item = u64> + 'a {
+    let [mut a, mut b] = overlap([a, b]).unwrap_or([&[], &[]]);
+    std::iter::from_fn(move || {
+        while let (Some(&x), Some(&y)) = (a.first(), b.first()) {
+            a = &a[(x <= y) as usize..];
+            b = &b[(y <= x) as usize..];
+            if x == y {
+                return Some(x);
+            }
+        }
+        None
+    })
+}
+
+// Where each value the two sorted lists have in common is in each of them,
+// in ascending order of the values.
+pub fn common_positions<'a>(
+    a: &'a [u64],
+    b: &'a [u64],
+) -> impl Iterator<Item = (usize, usize)> + 'a {
+    let (mut i, mut j) = (0, 0);
+    std::iter::from_fn(move || {
+        while i < a.len() && j < b.len() {
+            let (x, y) = (a[i], b[j]);
+            i += (x <= y) as usize;
+            j += (y <= x) as usize;
+            if x == y {
+                return Some((i - 1, j - 1));
+            }
+        }
+        None
+    })
+}
+
+// The number of values all of the sorted lists have in common. The lists are
+// cut down in place as they're walked, rather than copied, so this allocates
+// nothing; the caller's slices are left pointing part-way in.
+pub fn count_many(lists: &mut [&[u64]]) -> u64 {
+    match lists {
+        [] => 0,
+        [a] => a.len() as u64,
+        [a, b] => count(a, b),
+        _ => {
+            if !overlap_many(lists) {
+                return 0;
+            }
+            // Walk the shortest list, and look for each of its values in the
+            // others. Every list only moves forward, so each is binary
+            // searched over just the part we haven't passed yet.
+            let shortest = (0..lists.len()).min_by_key(|&i| lists[i].len()).unwrap();
+            let walked = lists[shortest];
+            let mut count = 0;
+            'values: for &v in walked {
+                for (i, list) in lists.iter_mut().enumerate() {
+                    if i == shortest {
+                        continue;
+                    }
+                    let pos = list.partition_point(|&x| x < v);
+                    *list = &list[pos..];
+                    match list.first() {
+                        None => break 'values,
+                        Some(&x) if x != v => continue 'values,
+                        Some(_) => {}
+                    }
+                }
+                count += 1;
+            }
+            count
+        }
+    }
+}
+
+// Each list's smallest and largest values are its ends, so we can check
+// whether two lists' ranges overlap at all before merging them, and if they
+// do, cut each down to the part inside the other's range. On graphs whose ids
+// have some locality this rules out most pairs without a single seek.
+fn overlap(lists: [&[u64]; 2]) -> Option<[&[u64]; 2]> {
+    let [a, b] = lists;
+    let (lo, hi) = (a.first()?.max(b.first()?), a.last()?.min(b.last()?));
+    (lo <= hi).then(|| [clamp(a, *lo, *hi), clamp(b, *lo, *hi)])
+}
+
+// `overlap`, for any number of lists, cutting them down in place. False if
+// they don't all overlap, in which case they're left as they were.
+fn overlap_many(lists: &mut [&[u64]]) -> bool {
+    let mut lo = 0;
+    let mut hi = u64::MAX;
+    for list in lists.iter() {
+        let (Some(first), Some(last)) = (list.first(), list.last()) else {
+            return false;
+        };
+        lo = lo.max(*first);
+        hi = hi.min(*last);
+    }
+    if lo > hi {
+        return false;
+    }
+    for list in lists.iter_mut() {
+        *list = clamp(list, lo, hi);
+    }
+    true
+}
+
+// The part of the sorted list between `lo` and `hi`, inclusive. Usually at
+// least one end of the overlap is the list's own end, which needs no search.
+fn clamp(list: &[u64], lo: u64, hi: u64) -> &[u64] {
+    let start = match list.first() {
+        Some(&first) if first < lo => list.partition_point(|&x| x < lo),
+        _ => 0,
+    };
+    let end = match list.last() {
+        Some(&last) if last > hi => list.partition_point(|&x| x <= hi),
+        _ => list.len(),
+    };
+    &list[start..end]
+}
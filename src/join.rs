@@ -0,0 +1,472 @@
+// A generic Leapfrog Triejoin interpreter. Where the loop nest in `main` is
+// written out by hand for one query, this walks any conjunctive query over any
+// set of `Relation`s, at the cost of dynamic dispatch on every step.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{checked, index::TrieIterator, intersect, relation::Relation, scratch::Lists};
+
+// One atom of a query: a relation together with the variable each of its
+// columns binds. Variables are numbered by the order in which the join binds
+// them, so variable 0 is the outermost loop.
+//
+// A negated atom filters out bindings whose tuple *is* in the relation (an
+// anti-join). It never binds anything itself, so each of its variables must
+// also appear in some positive atom.
+pub struct Atom<'a> {
+    pub relation: &'a dyn Relation,
+    pub vars: Vec<usize>,
+    pub negated: bool,
+}
+
+impl<'a> Atom<'a> {
+    pub fn new(relation: &'a dyn Relation, vars: &[usize]) -> Self {
+        Self {
+            relation,
+            vars: vars.to_vec(),
+            negated: false,
+        }
+    }
+
+    pub fn not(relation: &'a dyn Relation, vars: &[usize]) -> Self {
+        Self {
+            negated: true,
+            ..Self::new(relation, vars)
+        }
+    }
+}
+
+// Why a join can't be run as asked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidQuery {
+    // An atom lists a different number of variables than its relation has
+    // columns.
+    Arity {
+        atom: usize,
+        vars: usize,
+        arity: usize,
+    },
+    // A variable numbered past the last, in an atom if `atom` is set, and
+    // otherwise in the variables to group by or keep.
+    UnknownVariable {
+        atom: Option<usize>,
+        var: usize,
+    },
+    // A variable that only negated atoms (or none) mention, so nothing binds
+    // it.
+    Unbound(usize),
+    // A prefix deeper than there are variables.
+    Depth {
+        depth: usize,
+        num_vars: usize,
+    },
+    // Something other than a permutation of the variables, given as the order
+    // to bind them in.
+    Order(Vec<usize>),
+}
+
+impl fmt::Display for InvalidQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidQuery::Arity { atom, vars, arity } => write!(
+                f,
+                "atom {} binds {} variables but its relation has arity {}",
+                atom, vars, arity
+            ),
+            InvalidQuery::UnknownVariable {
+                atom: Some(atom),
+                var,
+            } => write!(f, "atom {} uses unknown variable {}", atom, var),
+            InvalidQuery::UnknownVariable { atom: None, var } => {
+                write!(f, "unknown variable {}", var)
+            }
+            InvalidQuery::Unbound(var) => {
+                write!(f, "variable {} does not appear in any positive atom", var)
+            }
+            InvalidQuery::Depth { depth, num_vars } => {
+                write!(f, "cannot bind {} of {} variables", depth, num_vars)
+            }
+            InvalidQuery::Order(order) => {
+                write!(f, "{:?} is not an ordering of the variables", order)
+            }
+        }
+    }
+}
+
+// Check that the join of `atoms` over variables `0..num_vars` can be run:
+// every atom fits its relation, and every variable is bound by some
+// positive atom.
+pub fn validate(atoms: &[Atom], num_vars: usize) -> Result<(), InvalidQuery> {
+    let mut bound = vec![false; num_vars];
+    for (i, atom) in atoms.iter().enumerate() {
+        if atom.vars.len() != atom.relation.arity() {
+            return Err(InvalidQuery::Arity {
+                atom: i,
+                vars: atom.vars.len(),
+                arity: atom.relation.arity(),
+            });
+        }
+        for &var in &atom.vars {
+            if var >= num_vars {
+                return Err(InvalidQuery::UnknownVariable { atom: Some(i), var });
+            }
+            bound[var] |= !atom.negated;
+        }
+    }
+    match bound.iter().position(|b| !b) {
+        Some(var) => Err(InvalidQuery::Unbound(var)),
+        None => Ok(()),
+    }
+}
+
+// Check that each of `vars` is one of `0..num_vars`.
+pub(crate) fn known(vars: &[usize], num_vars: usize) -> Result<(), InvalidQuery> {
+    match vars.iter().find(|&&v| v >= num_vars) {
+        Some(&var) => Err(InvalidQuery::UnknownVariable { atom: None, var }),
+        None => Ok(()),
+    }
+}
+
+// Where a variable appears in one atom's trie.
+struct Participant {
+    iter: usize,
+    // Whether there are further levels below this one that later variables
+    // will need us to descend into.
+    descend: bool,
+}
+
+// A negated atom, to be checked once all of its variables are bound.
+struct Negation {
+    iter: usize,
+    // The atom's variables, in the order of its trie's levels.
+    vars: Vec<usize>,
+}
+
+struct Executor<'a> {
+    iters: Vec<Box<dyn TrieIterator + 'a>>,
+    // For each variable, the iterators whose current level binds it.
+    participants: Vec<Vec<Participant>>,
+    // For each variable, the negated atoms whose last variable it is.
+    negations: Vec<Vec<Negation>>,
+    // The value currently bound to each variable.
+    binding: Vec<u64>,
+    // Where the grouped or kept variables' values are gathered to look them
+    // up, so that only a new group or binding allocates a key of its own.
+    key: Vec<u64>,
+}
+
+// Count the tuples in the join of `atoms`, binding variables `0..num_vars` in
+// order.
+pub fn count(atoms: &[Atom], num_vars: usize) -> Result<u64, InvalidQuery> {
+    Ok(Executor::new(atoms, num_vars)?.count_from(0))
+}
+
+// Count the tuples in the join of `atoms` per distinct binding of the `group_by`
+// variables, like a `GROUP BY` with a `COUNT(*)`. The counts are accumulated
+// during the join rather than by materializing its result. Groups come back in
+// ascending order of their values, and groups with no tuples are left out.
+pub fn count_grouped(
+    atoms: &[Atom],
+    num_vars: usize,
+    group_by: &[usize],
+) -> Result<Vec<(Vec<u64>, u64)>, InvalidQuery> {
+    known(group_by, num_vars)?;
+    let mut executor = Executor::new(atoms, num_vars)?;
+    // Once every grouped variable is bound, the rest of the join only
+    // contributes to that one group, so it can be counted as a whole.
+    let until = group_by.iter().map(|v| v + 1).max().unwrap_or(0);
+    let mut groups = HashMap::new();
+    executor.group_from(0, until, group_by, &mut groups);
+    let mut groups: Vec<(Vec<u64>, u64)> = groups.into_iter().collect();
+    groups.sort_unstable();
+    Ok(groups)
+}
+
+// Whether the join of `atoms` has any tuples at all, stopping at the first.
+pub fn exists(atoms: &[Atom], num_vars: usize) -> Result<bool, InvalidQuery> {
+    Ok(Executor::new(atoms, num_vars)?.exists_from(0))
+}
+
+// The distinct bindings of the `keep` variables that extend to at least one
+// tuple of the join, with every other variable projected away (existentially
+// quantified). As soon as the kept variables are all bound, the rest of the
+// join only has to find a single witness rather than enumerate them, so e.g.
+// asking which vertices are in some triangle stops at the first triangle for
+// each vertex. Bindings come back in ascending order.
+pub fn project(
+    atoms: &[Atom],
+    num_vars: usize,
+    keep: &[usize],
+) -> Result<Vec<Vec<u64>>, InvalidQuery> {
+    known(keep, num_vars)?;
+    let mut executor = Executor::new(atoms, num_vars)?;
+    let until = keep.iter().map(|v| v + 1).max().unwrap_or(0);
+    let mut found = HashSet::new();
+    executor.project_from(0, until, keep, &mut found);
+    let mut found: Vec<Vec<u64>> = found.into_iter().collect();
+    found.sort_unstable();
+    Ok(found)
+}
+
+// The number of distinct bindings of the `keep` variables that extend to at
+// least one tuple of the join, like `project(..).len()`. When the kept
+// variables are the first ones bound, as a planner would arrange, each binding
+// of them comes up exactly once, so nothing needs remembering: we count the
+// ones the rest of the join can witness, stopping at the first witness each.
+pub fn count_distinct(
+    atoms: &[Atom],
+    num_vars: usize,
+    keep: &[usize],
+) -> Result<u64, InvalidQuery> {
+    let mut sorted = keep.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    if sorted.iter().enumerate().any(|(i, &v)| i != v) {
+        return Ok(project(atoms, num_vars, keep)?.len() as u64);
+    }
+    known(&sorted, num_vars)?;
+    Ok(Executor::new(atoms, num_vars)?.distinct_from(0, sorted.len()))
+}
+
+// The number of distinct bindings of the variables `0..depth` that the join
+// produces on its way to the rest, counting no further than `limit`. This is
+// the size of the intermediate result at that depth, for checking a plan's
+// estimates against without paying for the whole join.
+pub fn count_prefix(
+    atoms: &[Atom],
+    num_vars: usize,
+    depth: usize,
+    limit: u64,
+) -> Result<u64, InvalidQuery> {
+    if depth > num_vars {
+        return Err(InvalidQuery::Depth { depth, num_vars });
+    }
+    Ok(Executor::new(atoms, num_vars)?.prefix_from(0, depth, limit))
+}
+
+impl<'a> Executor<'a> {
+    fn new(atoms: &[Atom<'a>], num_vars: usize) -> Result<Self, InvalidQuery> {
+        validate(atoms, num_vars)?;
+        let mut participants: Vec<Vec<Participant>> = (0..num_vars).map(|_| Vec::new()).collect();
+        let mut negations: Vec<Vec<Negation>> = (0..num_vars).map(|_| Vec::new()).collect();
+        let mut iters = Vec::with_capacity(atoms.len());
+        for (i, atom) in atoms.iter().enumerate() {
+            // Visit the columns in the order their variables get bound.
+            let mut order: Vec<usize> = (0..atom.vars.len()).collect();
+            order.sort_by_key(|&col| atom.vars[col]);
+            let vars: Vec<usize> = order.iter().map(|&col| atom.vars[col]).collect();
+            if atom.negated {
+                if let Some(&last) = vars.last() {
+                    negations[last].push(Negation { iter: i, vars });
+                }
+            } else {
+                for (level, &var) in vars.iter().enumerate() {
+                    participants[var].push(Participant {
+                        iter: i,
+                        descend: level + 1 < vars.len(),
+                    });
+                }
+            }
+            // In debug builds, catch the executor breaking the iterators'
+            // contract where it happens.
+            iters.push(checked::wrap(atom.relation.trie_iter(&order), order.len()));
+        }
+
+        Ok(Executor {
+            iters,
+            participants,
+            negations,
+            binding: vec![0; num_vars],
+            key: Vec::new(),
+        })
+    }
+
+    fn count_from(&mut self, var: usize) -> u64 {
+        if var == self.participants.len() {
+            return 1;
+        }
+        // We're only counting, so at the last variable there's no need to
+        // actually visit each value: if every list is contiguous, let the
+        // intersection kernel count their overlap directly. That's only
+        // possible if no value needs to be checked against a negated atom.
+        if var + 1 == self.participants.len() && self.negations[var].is_empty() {
+            for p in &self.participants[var] {
+                self.iters[p.iter].reset();
+            }
+            let lists: Option<Lists> = self.participants[var]
+                .iter()
+                .map(|p| self.iters[p.iter].rest())
+                .collect();
+            if let Some(mut lists) = lists {
+                return intersect::count_many(lists.as_mut_slice());
+            }
+        }
+        self.bind(var, &mut |ex| ex.count_from(var + 1))
+    }
+
+    fn group_from(
+        &mut self,
+        var: usize,
+        until: usize,
+        group_by: &[usize],
+        groups: &mut HashMap<Vec<u64>, u64>,
+    ) -> u64 {
+        if var == until {
+            let count = self.count_from(var);
+            if count > 0 {
+                self.key.clear();
+                self.key.extend(group_by.iter().map(|&v| self.binding[v]));
+                match groups.get_mut(&self.key[..]) {
+                    Some(total) => *total += count,
+                    None => {
+                        groups.insert(self.key.clone(), count);
+                    }
+                }
+            }
+            return count;
+        }
+        self.bind(var, &mut |ex| {
+            ex.group_from(var + 1, until, group_by, groups)
+        })
+    }
+
+    fn distinct_from(&mut self, var: usize, until: usize) -> u64 {
+        if var == until {
+            return self.exists_from(var) as u64;
+        }
+        self.bind(var, &mut |ex| ex.distinct_from(var + 1, until))
+    }
+
+    fn prefix_from(&mut self, var: usize, depth: usize, limit: u64) -> u64 {
+        if var == depth {
+            return 1;
+        }
+        self.bind_until(var, limit, &mut |ex| ex.prefix_from(var + 1, depth, limit))
+    }
+
+    fn exists_from(&mut self, var: usize) -> bool {
+        if var == self.participants.len() {
+            return true;
+        }
+        self.bind_until(var, 1, &mut |ex| ex.exists_from(var + 1) as u64) > 0
+    }
+
+    fn project_from(
+        &mut self,
+        var: usize,
+        until: usize,
+        keep: &[usize],
+        found: &mut HashSet<Vec<u64>>,
+    ) -> u64 {
+        if var == until {
+            self.key.clear();
+            self.key.extend(keep.iter().map(|&v| self.binding[v]));
+            // If the kept variables aren't all bound first, the same binding
+            // of them can come up under different values of the others, but
+            // it only needs to be witnessed once.
+            if !found.contains(&self.key[..]) && self.exists_from(var) {
+                found.insert(self.key.clone());
+                return 1;
+            }
+            return 0;
+        }
+        self.bind(var, &mut |ex| ex.project_from(var + 1, until, keep, found))
+    }
+
+    // Run the leapfrog over `var`'s participants, calling `body` with each
+    // value that survives the negated atoms bound (and every participant
+    // moved down into it), and summing what it returns.
+    fn bind(&mut self, var: usize, body: &mut dyn FnMut(&mut Self) -> u64) -> u64 {
+        self.bind_until(var, u64::MAX, body)
+    }
+
+    // Like `bind`, but stop once the sum reaches `limit`.
+    fn bind_until(
+        &mut self,
+        var: usize,
+        limit: u64,
+        body: &mut dyn FnMut(&mut Self) -> u64,
+    ) -> u64 {
+        for p in &self.participants[var] {
+            self.iters[p.iter].reset();
+        }
+        let mut total = 0;
+        // The leapfrog: repeatedly seek every iterator up to the largest
+        // current value until they all agree, which is a binding for `var`.
+        while let Some(hi) = self.max_value(var) {
+            let mut agreed = true;
+            for p in &self.participants[var] {
+                let it = &mut self.iters[p.iter];
+                if it.value() != Some(hi) {
+                    it.seek(hi);
+                    agreed = false;
+                }
+            }
+            if !agreed {
+                continue;
+            }
+            self.binding[var] = hi;
+            if (0..self.negations[var].len()).all(|n| self.absent(var, n)) {
+                for p in &self.participants[var] {
+                    if p.descend {
+                        self.iters[p.iter].down();
+                    }
+                }
+                total += body(self);
+                for p in &self.participants[var] {
+                    if p.descend {
+                        self.iters[p.iter].up();
+                    }
+                }
+                if total >= limit {
+                    break;
+                }
+            }
+            for p in &self.participants[var] {
+                self.iters[p.iter].next();
+            }
+        }
+        total
+    }
+
+    // Whether the current binding of the `n`th negated atom checked at `var`
+    // is missing from its relation. We look for it by seeking down through
+    // the levels of the atom's trie, and then climb back up to the top so the
+    // next check starts from the same place.
+    fn absent(&mut self, var: usize, n: usize) -> bool {
+        let negation = &self.negations[var][n];
+        let it = &mut self.iters[negation.iter];
+        let mut found = true;
+        let mut depth = 0;
+        for (level, &v) in negation.vars.iter().enumerate() {
+            if level > 0 {
+                it.down();
+                depth += 1;
+            }
+            let value = self.binding[v];
+            it.reset();
+            it.seek(value);
+            if it.value() != Some(value) {
+                found = false;
+                break;
+            }
+        }
+        for _ in 0..depth {
+            it.up();
+        }
+        !found
+    }
+
+    // The largest value any participant in `var` is currently at, or None if
+    // any of them is exhausted (in which case there are no more bindings).
+    fn max_value(&self, var: usize) -> Option<u64> {
+        let mut hi = 0;
+        for p in &self.participants[var] {
+            hi = hi.max(self.iters[p.iter].value()?);
+        }
+        Some(hi)
+    }
+}
@@ -0,0 +1,106 @@
+// Multi-relational graphs, with a label on every edge (say "friend" or
+// "colleague"), and triangle counts broken down by the labels of their three
+// edges.
+//
+// The labels sit in a third level under the usual two: each neighbor list is
+// paired with a list of the label sets of those edges, the way `weighted`
+// pairs it with weights. A pair of vertices can be joined by edges of several
+// labels, so each edge has a set of them rather than just one.
+
+use std::collections::HashMap;
+
+use crate::index::Adjacency;
+
+pub struct LabeledGraph {
+    pub adjacency: Adjacency,
+    // For each neighbor in `adjacency`, the ids of the edge's labels, in
+    // ascending order.
+    pub labels: Vec<Vec<Vec<u32>>>,
+    // The label with each id. Ids are given in sorted order of the names.
+    pub names: Vec<String>,
+}
+
+impl LabeledGraph {
+    // Build the graph from (src, dst, label) triples, in any order. An edge
+    // listed more than once with different labels has all of them.
+    pub fn new(edges: Vec<(u64, u64, String)>) -> Self {
+        let mut names: Vec<String> = edges.iter().map(|(_, _, l)| l.clone()).collect();
+        names.sort_unstable();
+        names.dedup();
+        let ids: HashMap<&str, u32> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i as u32))
+            .collect();
+        let mut edges: Vec<(u64, u64, u32)> = edges
+            .iter()
+            .map(|(u, v, l)| (*u, *v, ids[l.as_str()]))
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+
+        let mut adjacency: Adjacency = Vec::new();
+        let mut labels: Vec<Vec<Vec<u32>>> = Vec::new();
+        for (u, v, l) in edges {
+            match adjacency.last_mut() {
+                Some((last, vs)) if *last == u => {
+                    let ls = labels.last_mut().unwrap();
+                    if vs.last() == Some(&v) {
+                        ls.last_mut().unwrap().push(l);
+                    } else {
+                        vs.push(v);
+                        ls.push(vec![l]);
+                    }
+                }
+                _ => {
+                    adjacency.push((u, vec![v]));
+                    labels.push(vec![vec![l]]);
+                }
+            }
+        }
+        Self {
+            adjacency,
+            labels,
+            names,
+        }
+    }
+}
+
+// The number of triangles (a, b, c) of R(a, b), S(b, c), T(a, c) for each
+// combination of the labels of (a, b), (b, c), and (a, c), in that order,
+// found in one pass. A triangle whose edges have several labels counts once
+// towards every combination of them. Combinations are listed most common
+// first.
+pub fn count_by_labels(graph: &LabeledGraph) -> Vec<([u32; 3], u64)> {
+    let data = &graph.adjacency;
+    let mut counts: HashMap<[u32; 3], u64> = HashMap::new();
+    for (i, (_, a_neighbors)) in data.iter().enumerate() {
+        let a_labels = &graph.labels[i];
+        for (x, &b) in a_neighbors.iter().enumerate() {
+            let Ok(j) = data.binary_search_by_key(&b, |(v, _)| *v) else {
+                continue;
+            };
+            let (b_neighbors, b_labels) = (&data[j].1, &graph.labels[j]);
+            // Merge N(a) and N(b), keeping track of where we are in both so
+            // we can pick up the labels of (a, c) and (b, c).
+            let (mut y, mut z) = (0, 0);
+            while y < a_neighbors.len() && z < b_neighbors.len() {
+                let (u, v) = (a_neighbors[y], b_neighbors[z]);
+                if u == v {
+                    for &ab in &a_labels[x] {
+                        for &bc in &b_labels[z] {
+                            for &ac in &a_labels[y] {
+                                *counts.entry([ab, bc, ac]).or_default() += 1;
+                            }
+                        }
+                    }
+                }
+                y += (u <= v) as usize;
+                z += (v <= u) as usize;
+            }
+        }
+    }
+    let mut counts: Vec<([u32; 3], u64)> = counts.into_iter().collect();
+    counts.sort_unstable_by(|(k1, c1), (k2, c2)| c2.cmp(c1).then(k1.cmp(k2)));
+    counts
+}
@@ -0,0 +1,70 @@
+pub mod anonymize;
+pub mod background;
+pub mod batch;
+pub mod bitmap;
+pub mod cache;
+pub mod checked;
+pub mod codegen;
+pub mod columnar;
+pub mod community;
+pub mod compiled;
+pub mod config;
+pub mod cycles;
+#[cfg(feature = "loaders")]
+pub mod dump;
+pub mod ef;
+pub mod engine;
+pub mod eta;
+pub mod failure;
+#[cfg(feature = "random")]
+pub mod generate;
+pub mod graph;
+pub mod hashtrie;
+pub mod index;
+pub mod intersect;
+pub mod join;
+pub mod labels;
+pub mod lint;
+pub mod loader;
+#[cfg(feature = "random")]
+pub mod null;
+pub mod numa;
+pub mod orient;
+pub mod parallel;
+pub mod pattern;
+pub mod persist;
+#[cfg(feature = "loaders")]
+pub mod pipeline;
+pub mod plan;
+pub mod pool;
+pub mod reduce;
+pub mod relation;
+pub mod reorder;
+pub mod roaring;
+#[cfg(feature = "random")]
+pub mod sample;
+#[cfg(feature = "server")]
+pub mod scheduler;
+pub mod scratch;
+pub mod similarity;
+pub mod sink;
+pub mod skew;
+pub mod snapshot;
+#[cfg(feature = "random")]
+pub mod sparsify;
+pub mod stats;
+pub mod stream;
+#[cfg(feature = "server")]
+pub mod swap;
+pub mod temporal;
+pub mod transcript;
+pub mod trie;
+pub mod weighted;
+
+pub use cycles::count_four_cycles;
+pub use graph::{
+    count_cliques, count_triangles, count_triangles_across, count_triangles_with, for_each_clique,
+    for_each_triangle, for_each_triangle_across, for_each_triangle_in, for_each_triangle_with,
+    triangles, Graph, GraphBuilder,
+};
+pub use index::Index;
@@ -0,0 +1,136 @@
+// Checks for signs that an edge list isn't what it claims to be, run before
+// counting so that bad data is caught up front rather than showing up as a
+// surprising count.
+
+use std::{collections::HashMap, fmt};
+
+use crate::loader::IndexBase;
+
+// Flag duplicate edges once they make up more than this fraction of the file.
+pub const DUPLICATE_FRACTION: f64 = 0.01;
+// Flag unused ids once they make up more than this fraction of the id range.
+pub const ISOLATED_FRACTION: f64 = 0.1;
+// Flag a component other than the largest once it has more than this
+// fraction of the vertices.
+pub const CHUNK_FRACTION: f64 = 0.1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Finding {
+    SelfLoops(usize),
+    Duplicates { duplicates: usize, edges: usize },
+    // Ids up to the largest one mentioned which never appear in an edge. The
+    // index doesn't care, but it often means ids were mangled on export, and
+    // they skew anything sized by the largest id (like bitmaps).
+    Isolated { unused: u64, range: u64 },
+    // The sizes of components big enough to suggest the file holds several
+    // unrelated graphs, largest first.
+    Chunks(Vec<usize>),
+    // The file was read as zero-based, but never mentions vertex 0 and does
+    // mention vertex 1.
+    LooksOneBased,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::SelfLoops(n) => write!(f, "{} self-loops", n),
+            Finding::Duplicates { duplicates, edges } => write!(
+                f,
+                "{} of {} edges are duplicates ({:.1}%)",
+                duplicates,
+                edges,
+                100.0 * *duplicates as f64 / *edges as f64
+            ),
+            Finding::Isolated { unused, range } => write!(
+                f,
+                "{} of the {} ids up to the largest are never used",
+                unused, range
+            ),
+            Finding::Chunks(sizes) => write!(
+                f,
+                "{} large disconnected components, of sizes {:?}",
+                sizes.len(),
+                sizes
+            ),
+            Finding::LooksOneBased => write!(
+                f,
+                "ids start at 1 but were read as zero-based (try --index-base 1)"
+            ),
+        }
+    }
+}
+
+// Look over `edges`, as read with the given index base, for likely problems.
+pub fn lint(edges: &[(u64, u64)], base: IndexBase) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if edges.is_empty() {
+        return findings;
+    }
+
+    let loops = edges.iter().filter(|(u, v)| u == v).count();
+    if loops > 0 {
+        findings.push(Finding::SelfLoops(loops));
+    }
+
+    let mut sorted = edges.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let duplicates = edges.len() - sorted.len();
+    if duplicates as f64 > DUPLICATE_FRACTION * edges.len() as f64 {
+        findings.push(Finding::Duplicates {
+            duplicates,
+            edges: edges.len(),
+        });
+    }
+
+    let mut vertices: Vec<u64> = edges.iter().flat_map(|&(u, v)| [u, v]).collect();
+    vertices.sort_unstable();
+    vertices.dedup();
+    // With an id of u64::MAX the range of ids doesn't fit in a u64, so
+    // there's no fraction of it to report.
+    if let Some(range) = vertices[vertices.len() - 1].checked_add(1) {
+        let unused = range - vertices.len() as u64;
+        if unused as f64 > ISOLATED_FRACTION * range as f64 {
+            findings.push(Finding::Isolated { unused, range });
+        }
+    }
+
+    let mut sizes = component_sizes(&vertices, edges);
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    let big: Vec<usize> = sizes
+        .into_iter()
+        .take_while(|&s| s as f64 > CHUNK_FRACTION * vertices.len() as f64)
+        .collect();
+    if big.len() > 1 {
+        findings.push(Finding::Chunks(big));
+    }
+
+    if base == IndexBase::Zero && vertices[0] == 1 {
+        findings.push(Finding::LooksOneBased);
+    }
+    findings
+}
+
+// The sizes of the connected components of the graph, ignoring direction.
+fn component_sizes(vertices: &[u64], edges: &[(u64, u64)]) -> Vec<usize> {
+    let dense = |u: u64| vertices.binary_search(&u).unwrap();
+    let mut parent: Vec<usize> = (0..vertices.len()).collect();
+    fn find(parent: &mut [usize], mut u: usize) -> usize {
+        while parent[u] != u {
+            parent[u] = parent[parent[u]];
+            u = parent[u];
+        }
+        u
+    }
+    for &(u, v) in edges {
+        let (ru, rv) = (find(&mut parent, dense(u)), find(&mut parent, dense(v)));
+        if ru != rv {
+            parent[ru.max(rv)] = ru.min(rv);
+        }
+    }
+    let mut sizes: HashMap<usize, usize> = HashMap::new();
+    for u in 0..vertices.len() {
+        *sizes.entry(find(&mut parent, u)).or_default() += 1;
+    }
+    sizes.into_values().collect()
+}
@@ -0,0 +1,422 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+// How the fields on a line of an edge list are separated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delimiter {
+    // Any run of spaces and/or tabs, as in SNAP-style files.
+    Whitespace,
+    Tab,
+    Comma,
+}
+
+impl Delimiter {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "space" | "whitespace" => Some(Delimiter::Whitespace),
+            "tab" => Some(Delimiter::Tab),
+            "comma" | "csv" => Some(Delimiter::Comma),
+            _ => None,
+        }
+    }
+
+    fn split<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        match self {
+            Delimiter::Whitespace => line.split_whitespace().collect(),
+            Delimiter::Tab => line.split('\t').map(str::trim).collect(),
+            Delimiter::Comma => line.split(',').map(str::trim).collect(),
+        }
+    }
+}
+
+// Whether vertex ids in the file count from zero or from one. Ids are always
+// zero-based internally, so one-based files (like Matrix Market) are shifted
+// down by one on load.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexBase {
+    Zero,
+    One,
+    // Treat the file as one-based if it never mentions vertex 0 but does
+    // mention vertex 1.
+    Auto,
+}
+
+impl IndexBase {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "0" | "zero" => Some(IndexBase::Zero),
+            "1" | "one" => Some(IndexBase::One),
+            "auto" => Some(IndexBase::Auto),
+            _ => None,
+        }
+    }
+}
+
+// Describes the layout of a text edge list. Every lab exports these slightly
+// differently, so rather than guessing we let the caller spell it out.
+#[derive(Clone, Debug)]
+pub struct Dialect {
+    pub delimiter: Delimiter,
+    // Lines starting with any of these (after leading whitespace) are skipped.
+    pub comment_prefixes: Vec<String>,
+    // If set, the first non-comment line is a header and is skipped.
+    pub header: bool,
+    // Which (zero-based) columns hold the source and destination of the edge.
+    // Any other columns are ignored.
+    pub columns: (usize, usize),
+    pub base: IndexBase,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            delimiter: Delimiter::Whitespace,
+            comment_prefixes: vec!["#".to_string()],
+            header: false,
+            columns: (0, 1),
+            base: IndexBase::Zero,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    // A line we couldn't make sense of, along with its (one-based) line number.
+    Parse { line: usize, message: String },
+    // Tuples asked for with fewer values than the two endpoints every line
+    // has.
+    Arity(usize),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            LoadError::Arity(arity) => {
+                write!(f, "tuples need at least their two endpoints, not {}", arity)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+// Read an edge list from the given file. The library reads files as they
+// are; the command line tool decompresses its inputs itself and hands them
+// to the readers below.
+pub fn load(path: &Path, dialect: &Dialect) -> Result<Vec<(u64, u64)>, LoadError> {
+    read_edges(BufReader::new(File::open(path)?), dialect)
+}
+
+// Read an edge list from any buffered reader, returning the edges in the order
+// they appear, with ids shifted to be zero-based.
+//
+// A file starting with a `%%MatrixMarket` banner is always read as one-based
+// with `%` comments, and its size line is skipped, whatever the dialect says.
+pub fn read_edges<R: BufRead>(reader: R, dialect: &Dialect) -> Result<Vec<(u64, u64)>, LoadError> {
+    let records = read_records(reader, dialect, |_| Ok(()))?;
+    Ok(records.into_iter().map(|(u, v, ())| (u, v)).collect())
+}
+
+// Read a weighted edge list from the given file, taking each edge's weight
+// from column `weight_column`.
+pub fn load_weighted(
+    path: &Path,
+    dialect: &Dialect,
+    weight_column: usize,
+) -> Result<Vec<(u64, u64, f64)>, LoadError> {
+    read_weighted_edges(BufReader::new(File::open(path)?), dialect, weight_column)
+}
+
+// Like `read_edges`, but also parse a weight for each edge from column
+// `weight_column`.
+pub fn read_weighted_edges<R: BufRead>(
+    reader: R,
+    dialect: &Dialect,
+    weight_column: usize,
+) -> Result<Vec<(u64, u64, f64)>, LoadError> {
+    read_records(reader, dialect, |fields| {
+        let s = fields.get(weight_column).ok_or_else(|| {
+            format!(
+                "expected a weight in column {}, found {} columns",
+                weight_column,
+                fields.len()
+            )
+        })?;
+        s.parse().map_err(|_| format!("invalid weight {:?}", s))
+    })
+}
+
+// Read a stream of timestamped edges, taking each edge's time from column
+// `time_column` as a whole number (in whatever unit the file uses).
+pub fn load_timestamped(
+    path: &Path,
+    dialect: &Dialect,
+    time_column: usize,
+) -> Result<Vec<(u64, u64, u64)>, LoadError> {
+    read_timestamped_edges(BufReader::new(File::open(path)?), dialect, time_column)
+}
+
+pub fn read_timestamped_edges<R: BufRead>(
+    reader: R,
+    dialect: &Dialect,
+    time_column: usize,
+) -> Result<Vec<(u64, u64, u64)>, LoadError> {
+    read_records(reader, dialect, |fields| {
+        let s = fields.get(time_column).ok_or_else(|| {
+            format!(
+                "expected a time in column {}, found {} columns",
+                time_column,
+                fields.len()
+            )
+        })?;
+        s.parse().map_err(|_| format!("invalid time {:?}", s))
+    })
+}
+
+// Read tuples of `arity` values, for relations beyond edges. The first two
+// values are the endpoints, from the dialect's columns, and shifted like any
+// other ids; the rest come from the columns after the destination's and are
+// kept as written, like times.
+pub fn load_tuples(
+    path: &Path,
+    dialect: &Dialect,
+    arity: usize,
+) -> Result<Vec<Vec<u64>>, LoadError> {
+    read_tuples(BufReader::new(File::open(path)?), dialect, arity)
+}
+
+pub fn read_tuples<R: BufRead>(
+    reader: R,
+    dialect: &Dialect,
+    arity: usize,
+) -> Result<Vec<Vec<u64>>, LoadError> {
+    if arity < 2 {
+        return Err(LoadError::Arity(arity));
+    }
+    let first = dialect.columns.1 + 1;
+    let records = read_records(reader, dialect, |fields| {
+        (first..first + arity - 2)
+            .map(|c| {
+                let s = fields.get(c).ok_or_else(|| {
+                    format!(
+                        "expected a value in column {}, found {} columns",
+                        c,
+                        fields.len()
+                    )
+                })?;
+                s.parse().map_err(|_| format!("invalid value {:?}", s))
+            })
+            .collect::<Result<Vec<u64>, String>>()
+    })?;
+    Ok(records
+        .into_iter()
+        .map(|(u, v, rest)| [u, v].into_iter().chain(rest).collect())
+        .collect())
+}
+
+// Read an edge list with a label on every edge, taking each edge's label
+// from column `label_column`. Labels are kept as written.
+pub fn load_labeled(
+    path: &Path,
+    dialect: &Dialect,
+    label_column: usize,
+) -> Result<Vec<(u64, u64, String)>, LoadError> {
+    read_labeled_edges(BufReader::new(File::open(path)?), dialect, label_column)
+}
+
+pub fn read_labeled_edges<R: BufRead>(
+    reader: R,
+    dialect: &Dialect,
+    label_column: usize,
+) -> Result<Vec<(u64, u64, String)>, LoadError> {
+    read_records(reader, dialect, |fields| {
+        fields
+            .get(label_column)
+            .map(|s| s.to_string())
+            .ok_or_else(|| {
+                format!(
+                    "expected a label in column {}, found {} columns",
+                    label_column,
+                    fields.len()
+                )
+            })
+    })
+}
+
+// Read a vertex-to-community assignment, one vertex per line. The dialect's
+// `columns` give the column of the vertex id and of its community's label;
+// labels are kept as written, and only the vertex ids are shifted to be
+// zero-based.
+pub fn load_communities(path: &Path, dialect: &Dialect) -> Result<Vec<(u64, String)>, LoadError> {
+    read_communities(BufReader::new(File::open(path)?), dialect)
+}
+
+pub fn read_communities<R: BufRead>(
+    reader: R,
+    dialect: &Dialect,
+) -> Result<Vec<(u64, String)>, LoadError> {
+    let (vertex, label) = dialect.columns;
+    // Read the vertex as both endpoints, so it gets the same treatment as any
+    // other id.
+    let dialect = Dialect {
+        columns: (vertex, vertex),
+        ..dialect.clone()
+    };
+    let records = read_records(reader, &dialect, |fields| {
+        fields.get(label).map(|s| s.to_string()).ok_or_else(|| {
+            format!(
+                "expected a community in column {}, found {} columns",
+                label,
+                fields.len()
+            )
+        })
+    })?;
+    Ok(records.into_iter().map(|(u, _, c)| (u, c)).collect())
+}
+
+// The shared line-by-line parser behind the readers above. Besides the two
+// endpoints, `extra` pulls whatever else the caller wants out of each line's
+// fields.
+fn read_records<R: BufRead, T>(
+    reader: R,
+    dialect: &Dialect,
+    extra: impl Fn(&[&str]) -> Result<T, String>,
+) -> Result<Vec<(u64, u64, T)>, LoadError> {
+    let mut records = Vec::new();
+    let mut parser = LineParser::new(dialect);
+    for line in reader.lines() {
+        if let Some(record) = parser.parse(&line?, &extra)? {
+            records.push(record);
+        }
+    }
+    if parser.base() == IndexBase::One {
+        for (u, v, _) in records.iter_mut() {
+            *u -= 1;
+            *v -= 1;
+        }
+    }
+    Ok(records)
+}
+
+// Parses an edge list one line at a time, keeping track of where in the file
+// it is (header, banner, etc.). Ids come out as written; once every line has
+// been seen, `base` says whether they need shifting down.
+pub struct LineParser<'a> {
+    dialect: &'a Dialect,
+    line: usize,
+    seen_header: bool,
+    base: IndexBase,
+    matrix_market: bool,
+    min_id: Option<u64>,
+}
+
+impl<'a> LineParser<'a> {
+    pub fn new(dialect: &'a Dialect) -> Self {
+        Self {
+            dialect,
+            line: 0,
+            seen_header: !dialect.header,
+            base: dialect.base,
+            matrix_market: false,
+            min_id: None,
+        }
+    }
+
+    // Parse the next line of the file, returning the edge on it (along with
+    // whatever `extra` pulls out of its fields), if there is one.
+    pub fn parse<T>(
+        &mut self,
+        line: &str,
+        extra: impl Fn(&[&str]) -> Result<T, String>,
+    ) -> Result<Option<(u64, u64, T)>, LoadError> {
+        self.line += 1;
+        let dialect = self.dialect;
+        let trimmed = line.trim();
+        if self.line == 1 && trimmed.starts_with("%%MatrixMarket") {
+            self.matrix_market = true;
+            self.seen_header = false;
+            self.base = IndexBase::One;
+            return Ok(None);
+        }
+        if trimmed.is_empty()
+            || (self.matrix_market && trimmed.starts_with('%'))
+            || dialect
+                .comment_prefixes
+                .iter()
+                .any(|p| trimmed.starts_with(p.as_str()))
+        {
+            return Ok(None);
+        }
+        if !self.seen_header {
+            self.seen_header = true;
+            return Ok(None);
+        }
+        let fields = dialect.delimiter.split(trimmed);
+        let line = self.line;
+        let error = |message| LoadError::Parse { line, message };
+        let field = |col: usize| -> Result<u64, LoadError> {
+            let s = fields.get(col).ok_or_else(|| {
+                error(format!(
+                    "expected at least {} columns, found {}",
+                    col + 1,
+                    fields.len()
+                ))
+            })?;
+            s.parse()
+                .map_err(|_| error(format!("invalid vertex id {:?}", s)))
+        };
+        let (src, dst) = dialect.columns;
+        let (u, v) = (field(src)?, field(dst)?);
+        if self.base == IndexBase::One && (u == 0 || v == 0) {
+            return Err(error("vertex id 0 in a one-based file".to_string()));
+        }
+        let extra = extra(&fields).map_err(error)?;
+        self.min_id = Some(self.min_id.map_or(u.min(v), |m| m.min(u).min(v)));
+        Ok(Some((u, v, extra)))
+    }
+
+    // The base of the ids seen so far: `Auto` is settled one way or the
+    // other.
+    pub fn base(&self) -> IndexBase {
+        match self.base {
+            IndexBase::Auto if looks_one_based(self.min_id) => IndexBase::One,
+            IndexBase::Auto => IndexBase::Zero,
+            base => base,
+        }
+    }
+}
+
+// The auto-detection heuristic: a file whose smallest id is exactly 1 was
+// almost certainly written by something that counts from one.
+fn looks_one_based(min_id: Option<u64>) -> bool {
+    min_id == Some(1)
+}
+
+// Group a list of edges into the sorted two-level structure that `Index`
+// expects: each source vertex followed by its sorted list of destinations.
+// A repeated edge is kept once; engines that count over the lists and ones
+// that seek through them would otherwise disagree on how many times it
+// closes a triangle.
+pub fn to_adjacency(mut edges: Vec<(u64, u64)>) -> Vec<(u64, Vec<u64>)> {
+    edges.sort_unstable();
+    edges.dedup();
+    let mut data: Vec<(u64, Vec<u64>)> = Vec::new();
+    for (u, v) in edges {
+        match data.last_mut() {
+            Some((last, neighbors)) if *last == u => neighbors.push(v),
+            _ => data.push((u, vec![v])),
+        }
+    }
+    data
+}
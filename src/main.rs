@@ -1,177 +1,1596 @@
-use std::{cmp::Ordering, rc::Rc, time::Instant};
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream},
+    panic,
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
 
-use rand::Rng;
+use find_triangles::{
+    anonymize,
+    community::{self, Partition},
+    config::{self, Config, OutputFormat},
+    dump,
+    engine::{self, Backend, Engine},
+    eta::{self, WorkEstimate},
+    failure::{Failure, FailureKind},
+    generate::{self, StreamConfig},
+    graph::Graph,
+    index::{self, Adjacency},
+    labels::{self, LabeledGraph},
+    lint,
+    loader::{self, Delimiter, Dialect, IndexBase, LoadError},
+    null,
+    numa::{self, Placement, Topology},
+    orient::{Orientation, Oriented},
+    parallel,
+    pattern::{Semantics, Union},
+    persist::{self, ByteOrder},
+    pipeline,
+    plan::{self, Participation},
+    pool,
+    relation::{EdgeRelation, Relation, TupleRelation},
+    reorder::{self, Relabeling},
+    sample,
+    similarity::{self, Measure},
+    sink::{EnumerationStats, ListFormat, Progress, ResultSink, WriteSink},
+    snapshot, sparsify,
+    stats::{self, GraphStats},
+    stream::{self, Overflow},
+    temporal::{self, Constraint, TemporalGraph},
+    transcript::{self, Event, Recorder, Step},
+    weighted::{self, Aggregate, Reduction, WeightedGraph},
+};
+use rand::{rngs::StdRng, SeedableRng};
 
-// Points at either a first-level entry we're located at (Upper), or a
-// second-level entry we're located at along with the parent in the first level
-// (Lower).
-enum Position {
-    Upper(usize),
-    Lower(usize, usize),
-}
+mod decompress;
 
-// A trie iterator as described in the Leapfrog Triejoin paper, which can
-// iterate across the first variable and then drop down to the values where that
-// first variable is bound.
-struct Index {
-    level: Position,
-    // Data is stored in a two-level index:
-    //
-    //    1     2     3      4    5 6 7
-    //   /|\   / \   /|\    /|\   | | |
-    //  2 3 4 4   5 4 6 7  5 7 8  8 7 8
-    //
-    data: Rc<Vec<(u64, Vec<u64>)>>,
-}
+fn main() {
+    // let data = Arc::new(vec![
+    //     (1, vec![2, 3, 4]),
+    //     (2, vec![4, 5]),
+    //     (3, vec![4, 6, 7]),
+    //     (4, vec![5, 7, 8]),
+    //     (5, vec![8]),
+    //     (6, vec![7]),
+    //     (7, vec![8]),
+    // ]);
 
-impl Index {
-    fn new(data: Rc<Vec<(u64, Vec<u64>)>>) -> Self {
-        Self {
-            level: Position::Upper(0),
-            data,
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // The output format decides how failures are reported, so it's settled
+    // before anything else can fail: from the command line if it's given
+    // there, and otherwise once the config is read.
+    let cli_output = args.iter().position(|a| a == "--output").map(|i| {
+        match args.get(i + 1).and_then(|v| OutputFormat::parse(v)) {
+            Some(format) => format,
+            None => fail(Failure::usage("--output expects a format (text or json)")),
         }
+    });
+    if let Some(format) = cli_output {
+        OUTPUT.set(format).unwrap();
     }
+    let config = load_config(&args);
+    let output = *OUTPUT.get_or_init(|| config.output.unwrap_or(OutputFormat::Text));
+    install_panic_hook();
 
-    // In whatever level we are currently in, move the iterator to the given
-    // value, or to the next value that comes after.
-    fn seek(&mut self, v: u64) {
-        match &mut self.level {
-            Position::Upper(i) => {
-                let (Ok(idx) | Err(idx)) = self.data.binary_search_by_key(&v, |(x, _)| *x);
-                *i = idx;
+    let mut input: Option<PathBuf> = None;
+    let mut nodes = 1000;
+    let mut prob = 0.5;
+    let mut seed = None;
+    let mut dialect = Dialect::default();
+    let mut custom_comments = false;
+    let mut engine = match &config.algorithm {
+        Some(name) => Engine::parse(name).unwrap_or_else(|| {
+            fail(Failure::usage(format!(
+                "unknown algorithm {:?} in config",
+                name
+            )));
+        }),
+        None => Engine::Handwritten,
+    };
+    let mut memory_budget = config.memory_budget;
+    let mut threads = config.threads.unwrap_or_else(parallel::default_threads);
+    let mut per_vertex = false;
+    let mut seed_vertices: Vec<u64> = Vec::new();
+    // The files for R, S and T, when each atom has its own.
+    let mut atom_files: Option<Vec<PathBuf>> = None;
+    let mut ego: Option<(u64, usize)> = None;
+    let mut numa = config.numa;
+    let mut participating = false;
+    let mut approximate = None;
+    let mut stratify = false;
+    let mut relative_error = None;
+    let mut keep_fraction = None;
+    let mut save_index: Option<PathBuf> = None;
+    let mut byte_order = ByteOrder::Little;
+    let mut binary = false;
+    let mut load_index = false;
+    let mut compare = false;
+    let mut heavy_threshold = None;
+    let mut show_stats = false;
+    let mut top_k = stats::DEFAULT_TOP_K;
+    let mut weight_column = None;
+    let mut label_column: Option<usize> = None;
+    let mut time_column: Option<usize> = None;
+    let mut series_interval: Option<u64> = None;
+    // With no --delta, events any distance apart still make a triangle.
+    let mut temporal = Constraint {
+        delta: u64::MAX,
+        order: None,
+    };
+    let mut heaviest = None;
+    let mut total_weight = false;
+    let mut expected_triangles = false;
+    let mut reduction = Reduction::Fast;
+    let mut aggregate = Aggregate::Sum;
+    let mut communities: Option<PathBuf> = None;
+    let mut similar_pairs: Option<PathBuf> = None;
+    let mut measure = Measure::Jaccard;
+    let mut candidate_pairs: Option<PathBuf> = None;
+    let mut recommend_for = None;
+    let mut order = None;
+    let mut orientation = None;
+    let mut clique_size: Option<usize> = None;
+    let mut four_cycles = false;
+    let mut pattern: Option<Union> = None;
+    let mut semantics = Semantics::Set;
+    // Each extra relation's name, arity and file.
+    let mut relations: Vec<(String, usize, PathBuf)> = Vec::new();
+    let mut params: Vec<(String, Vec<u64>)> = Vec::new();
+    let mut list_triangles = false;
+    let mut show_progress = false;
+    let mut backend = Backend::Csr;
+    let mut null_samples: Option<usize> = None;
+    let mut rewire_swaps: Option<usize> = None;
+    let mut list_output: Option<PathBuf> = None;
+    let mut list_format: Option<ListFormat> = None;
+    let mut degree_format = None;
+    let mut lint_only = false;
+    let mut anonymize_salt: Option<Option<u64>> = None;
+    let mut reduce_input = false;
+    // The snapshot directory, and whether to rewrite it rather than check it.
+    let mut snapshots: Option<(PathBuf, bool)> = None;
+    let mut generate = false;
+    let mut stream = StreamConfig::default();
+    let mut connect: Option<String> = None;
+    let mut ingest = false;
+    let mut listen: Option<String> = None;
+    let mut queue_capacity = stream::DEFAULT_QUEUE_CAPACITY;
+    let mut overflow = Overflow::Block;
+    let mut recorder: Option<Recorder> = None;
+    let mut replay_from: Option<PathBuf> = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next().unwrap_or_else(|| {
+                fail(Failure::usage(format!("{} requires a value", name)));
+            })
+        };
+        match arg.as_str() {
+            "--delimiter" => {
+                let v = value("--delimiter");
+                dialect.delimiter = Delimiter::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown delimiter {:?} (expected space, tab, or comma)",
+                        v
+                    )));
+                });
+            }
+            "--comment" => {
+                // The first explicit prefix replaces the default "#".
+                if !custom_comments {
+                    dialect.comment_prefixes.clear();
+                    custom_comments = true;
+                }
+                dialect.comment_prefixes.push(value("--comment"));
+            }
+            "--header" => dialect.header = true,
+            "--compare-engines" => compare = true,
+            "--list-triangles" => list_triangles = true,
+            "--progress" => show_progress = true,
+            "--backend" => {
+                let v = value("--backend");
+                backend = Backend::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown backend {:?} (expected csr, csr32, ef, roaring or auto)",
+                        v
+                    )));
+                });
+            }
+            "--null-model" => {
+                null_samples = Some(parsed(
+                    "--null-model",
+                    &value("--null-model"),
+                    "a number of samples",
+                ))
+            }
+            "--rewire" => {
+                rewire_swaps = Some(parsed("--rewire", &value("--rewire"), "a number of swaps"))
+            }
+            "--list-output" => {
+                list_triangles = true;
+                list_output = Some(PathBuf::from(value("--list-output")));
+            }
+            "--list-format" => {
+                let v = value("--list-format");
+                list_format = Some(ListFormat::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown list format {:?} (expected lines, csv, tsv, or binary)",
+                        v
+                    )));
+                }));
+            }
+            "--lint" => lint_only = true,
+            "--anonymize" => anonymize_salt = Some(None),
+            "--reduce" => reduce_input = true,
+            "--snapshots" => snapshots = Some((PathBuf::from(value("--snapshots")), false)),
+            "--update-snapshots" => {
+                snapshots = Some((PathBuf::from(value("--update-snapshots")), true))
+            }
+            "--salt" => anonymize_salt = Some(Some(parsed("--salt", &value("--salt"), "a number"))),
+            "--generate" => generate = true,
+            "--rate" => {
+                stream.rate = Some(parsed("--rate", &value("--rate"), "updates per second"))
+            }
+            "--delete-fraction" => {
+                stream.delete_fraction = parsed(
+                    "--delete-fraction",
+                    &value("--delete-fraction"),
+                    "a fraction",
+                )
+            }
+            "--vertices" => stream.vertices = parsed("--vertices", &value("--vertices"), "a count"),
+            "--window" => stream.window = parsed("--window", &value("--window"), "a count"),
+            "--drift" => stream.drift = parsed("--drift", &value("--drift"), "ids per update"),
+            "--limit" => stream.limit = Some(parsed("--limit", &value("--limit"), "a count")),
+            "--seed" => {
+                stream.seed = parsed("--seed", &value("--seed"), "a number");
+                seed = Some(stream.seed);
+            }
+            "--nodes" => nodes = parsed("--nodes", &value("--nodes"), "a count"),
+            "--prob" => {
+                prob = parsed("--prob", &value("--prob"), "a probability");
+                if !(0.0..=1.0).contains(&prob) {
+                    fail(Failure::usage(format!(
+                        "--prob expects a probability in [0, 1], got {}",
+                        prob
+                    )));
+                }
+            }
+            "--input" => input = Some(PathBuf::from(value("--input"))),
+            "--connect" => connect = Some(value("--connect")),
+            "--ingest" => ingest = true,
+            // Already read by `load_config`.
+            "--config" => {
+                value("--config");
+            }
+            // Already read above.
+            "--output" => {
+                value("--output");
+            }
+            "--threads" => threads = parsed("--threads", &value("--threads"), "a count"),
+            "--per-vertex" => per_vertex = true,
+            "--vertex" => seed_vertices.extend(
+                value("--vertex")
+                    .split(',')
+                    .map(|x| parsed::<u64>("--vertex", x.trim(), "a vertex")),
+            ),
+            "--numa" => {
+                let v = value("--numa");
+                numa = Some(Placement::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown placement {:?} (expected replicate or partition)",
+                        v
+                    )));
+                }));
+            }
+            "--participating" => participating = true,
+            "--approximate" => {
+                approximate = Some(parsed(
+                    "--approximate",
+                    &value("--approximate"),
+                    "a sample size",
+                ))
+            }
+            "--stratify" => stratify = true,
+            "--sparsify" => {
+                let v: f64 = parsed("--sparsify", &value("--sparsify"), "a probability");
+                if !(v > 0.0 && v <= 1.0) {
+                    fail(Failure::usage(format!(
+                        "--sparsify expects a probability in (0, 1], got {}",
+                        v
+                    )));
+                }
+                keep_fraction = Some(v);
+            }
+            "--relative-error" => {
+                relative_error = Some(parsed(
+                    "--relative-error",
+                    &value("--relative-error"),
+                    "a fraction",
+                ))
+            }
+            "--binary" => binary = true,
+            "--byte-order" => {
+                let v = value("--byte-order");
+                byte_order = ByteOrder::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown byte order {:?} (expected little, big, or native)",
+                        v
+                    )));
+                });
+            }
+            // Index files are recognized whatever flag they come in with, so
+            // this only makes it an error for the input not to be one.
+            "--load-index" => {
+                input = Some(PathBuf::from(value("--load-index")));
+                load_index = true;
+            }
+            "--save-index" => save_index = Some(PathBuf::from(value("--save-index"))),
+            "--memory-budget" => {
+                let v = value("--memory-budget");
+                memory_budget = Some(config::parse_size(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "--memory-budget expects a size like 512MB, got {:?}",
+                        v
+                    )))
+                }));
+            }
+            "--record" => {
+                let path = PathBuf::from(value("--record"));
+                recorder = Some(Recorder::create(&path).unwrap_or_else(|e| {
+                    fail(
+                        io_failure(format!("{}: {}", path.display(), e))
+                            .with("path", path.display()),
+                    )
+                }));
+            }
+            "--replay" => replay_from = Some(PathBuf::from(value("--replay"))),
+            "--listen" => listen = Some(value("--listen")),
+            "--queue-capacity" => {
+                queue_capacity = parsed("--queue-capacity", &value("--queue-capacity"), "a count")
+            }
+            "--overflow" => {
+                let v = value("--overflow");
+                overflow = Overflow::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown overflow policy {:?} (expected block or shed)",
+                        v
+                    )));
+                });
+            }
+            "--degree-histogram" => {
+                let v = value("--degree-histogram");
+                if v != "csv" && v != "json" {
+                    fail(Failure::usage(format!(
+                        "unknown format {:?} (expected csv or json)",
+                        v
+                    )));
+                }
+                degree_format = Some(v);
+            }
+            "--stats" => show_stats = true,
+            "--top-k" => top_k = parsed("--top-k", &value("--top-k"), "a count"),
+            "--time-column" => {
+                time_column = Some(parsed(
+                    "--time-column",
+                    &value("--time-column"),
+                    "a column index",
+                ))
+            }
+            "--series" => {
+                let v = value("--series");
+                series_interval = Some(temporal::parse_interval(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "--series expects an interval (hour, day, week, or a length), got {:?}",
+                        v
+                    )));
+                }));
+            }
+            "--delta" => temporal.delta = parsed("--delta", &value("--delta"), "a time span"),
+            "--edge-order" => {
+                let v = value("--edge-order");
+                temporal.order = Some(Constraint::parse_order(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "--edge-order expects each of ab, bc, and ac once, earliest first, e.g. ab,bc,ac; got {:?}",
+                        v
+                    )));
+                }));
+            }
+            "--label-column" => {
+                label_column = Some(parsed(
+                    "--label-column",
+                    &value("--label-column"),
+                    "a column index",
+                ))
+            }
+            "--weight-column" => {
+                weight_column = Some(parsed(
+                    "--weight-column",
+                    &value("--weight-column"),
+                    "a column index",
+                ))
+            }
+            "--total-weight" => total_weight = true,
+            "--expected-triangles" => expected_triangles = true,
+            "--deterministic" => reduction = Reduction::Deterministic,
+            "--heaviest" => heaviest = Some(parsed("--heaviest", &value("--heaviest"), "a count")),
+            "--aggregate" => {
+                let v = value("--aggregate");
+                aggregate = Aggregate::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown aggregate {:?} (expected sum, min, or product)",
+                        v
+                    )));
+                });
+            }
+            "--reorder" => {
+                let v = value("--reorder");
+                order = Some(reorder::Order::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown ordering {:?} (expected bfs, rcm, degree, or gorder)",
+                        v
+                    )));
+                }));
+            }
+            "--orient" => {
+                let v = value("--orient");
+                orientation = Some(Orientation::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown orientation {:?} (expected degree)",
+                        v
+                    )));
+                }));
+            }
+            "--atoms" => {
+                let v = value("--atoms");
+                let paths: Vec<PathBuf> = v.split(',').map(PathBuf::from).collect();
+                if paths.len() != 3 {
+                    fail(Failure::usage(format!(
+                        "--atoms expects the files for R, S and T, got {:?}",
+                        v
+                    )));
+                }
+                atom_files = Some(paths);
+            }
+            "--ego" => {
+                let v = value("--ego");
+                let Some((center, radius)) = v.split_once(',') else {
+                    fail(Failure::usage(format!(
+                        "--ego expects VERTEX,RADIUS, got {:?}",
+                        v
+                    )));
+                };
+                ego = Some((
+                    parsed("--ego", center, "a vertex"),
+                    parsed("--ego", radius, "a radius"),
+                ));
+            }
+            "--cliques" => clique_size = Some(parsed("--cliques", &value("--cliques"), "a size")),
+            "--four-cycles" => four_cycles = true,
+            "--query" => {
+                let v = value("--query");
+                pattern = Some(Union::parse(&v).unwrap_or_else(|e| {
+                    fail(Failure::usage(format!("invalid query {:?}: {}", v, e)));
+                }));
+            }
+            "--relation" => {
+                let v = value("--relation");
+                let Some((name, path)) = v.split_once('=') else {
+                    fail(Failure::usage(format!(
+                        "--relation expects NAME=PATH or NAME/ARITY=PATH, got {:?}",
+                        v
+                    )));
+                };
+                // Relations are binary unless their arity is given.
+                let (name, arity) = match name.split_once('/') {
+                    Some((name, arity)) => (name, parsed("--relation", arity, "an arity")),
+                    None => (name, 2),
+                };
+                relations.push((name.to_string(), arity, PathBuf::from(path)));
+            }
+            "--param" => {
+                let v = value("--param");
+                let Some((name, values)) = v.split_once('=') else {
+                    fail(Failure::usage(format!(
+                        "--param expects NAME=V1,V2,..., got {:?}",
+                        v
+                    )));
+                };
+                let name = format!("${}", name.trim_start_matches('$'));
+                let values = values
+                    .split(',')
+                    .map(|x| parsed("--param", x.trim(), "a vertex"))
+                    .collect();
+                params.push((name, values));
+            }
+            "--union" => {
+                let v = value("--union");
+                semantics = Semantics::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown union semantics {:?} (expected set or bag)",
+                        v
+                    )));
+                });
+            }
+            "--communities" => communities = Some(PathBuf::from(value("--communities"))),
+            "--similarity" => similar_pairs = Some(PathBuf::from(value("--similarity"))),
+            "--link-features" => candidate_pairs = Some(PathBuf::from(value("--link-features"))),
+            "--recommend" => {
+                recommend_for = Some(parsed("--recommend", &value("--recommend"), "a vertex"))
             }
-            Position::Lower(i, j) => {
-                let (Ok(idx) | Err(idx)) = self.data[*i].1.binary_search(&v);
-                *j = idx;
+            "--measure" => {
+                let v = value("--measure");
+                measure = Measure::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown measure {:?} (expected jaccard or cosine)",
+                        v
+                    )));
+                });
             }
+            "--heavy-threshold" => {
+                heavy_threshold = Some(parsed(
+                    "--heavy-threshold",
+                    &value("--heavy-threshold"),
+                    "a degree",
+                ))
+            }
+            "--engine" => {
+                let v = value("--engine");
+                engine = Engine::parse(&v).unwrap_or_else(|| {
+                    let names = engine::all(None, 1).map(|e| e.name());
+                    let (last, rest) = names.split_last().unwrap();
+                    fail(Failure::usage(format!(
+                        "unknown engine {:?} (expected {}, or {})",
+                        v,
+                        rest.join(", "),
+                        last
+                    )));
+                });
+            }
+            "--index-base" => {
+                let v = value("--index-base");
+                dialect.base = IndexBase::parse(&v).unwrap_or_else(|| {
+                    fail(Failure::usage(format!(
+                        "unknown index base {:?} (expected 0, 1, or auto)",
+                        v
+                    )));
+                });
+            }
+            "--columns" => {
+                let v = value("--columns");
+                let cols: Vec<_> = v.split(',').map(|c| c.trim().parse::<usize>()).collect();
+                dialect.columns = match cols.as_slice() {
+                    [Ok(s), Ok(d)] => (*s, *d),
+                    _ => {
+                        fail(Failure::usage(format!(
+                            "--columns expects two column indices like 0,1, got {:?}",
+                            v
+                        )));
+                    }
+                };
+            }
+            _ if !arg.starts_with("--") && input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => {
+                fail(Failure::usage(format!("unexpected argument {:?}", arg)));
+            }
+        }
+    }
+
+    if series_interval.is_some() && time_column.is_none() {
+        fail(Failure::usage("--series requires --time-column"));
+    }
+    if let Some(column) = time_column {
+        let Some(path) = &input else {
+            fail(Failure::usage("--time-column requires an input file"));
+        };
+        let events = open_input(path)
+            .and_then(|r| loader::read_timestamped_edges(r, &dialect, column))
+            .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+        if let Some(interval) = series_interval {
+            let series = temporal::series(events, interval)
+                .unwrap_or_else(|e| fail(Failure::usage(e.to_string())));
+            for point in series {
+                println!("{} {} {}", point.start, point.edges, point.triangles);
+            }
+            return;
+        }
+        let graph = TemporalGraph::new(events);
+        let start = Instant::now();
+        let count = temporal::count(&graph, &temporal);
+        println!(
+            "found {} temporal triangles ({}) in {:?}",
+            count,
+            temporal,
+            start.elapsed()
+        );
+        return;
+    }
+    if let Some(column) = label_column {
+        let Some(path) = &input else {
+            fail(Failure::usage("--label-column requires an input file"));
+        };
+        let edges = open_input(path)
+            .and_then(|r| loader::read_labeled_edges(r, &dialect, column))
+            .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+        let graph = LabeledGraph::new(edges);
+        let start = Instant::now();
+        let counts = labels::count_by_labels(&graph);
+        for ([ab, bc, ac], count) in &counts {
+            let name = |l: &u32| &graph.names[*l as usize];
+            println!("{} {} {} {}", name(ab), name(bc), name(ac), count);
+        }
+        println!(
+            "counted {} label combinations in {:?}",
+            counts.len(),
+            start.elapsed()
+        );
+        return;
+    }
+    if expected_triangles {
+        let (Some(path), Some(column)) = (&input, weight_column) else {
+            fail(Failure::usage(
+                "--expected-triangles requires an input file and --weight-column",
+            ));
+        };
+        let edges = open_input(path)
+            .and_then(|r| loader::read_weighted_edges(r, &dialect, column))
+            .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+        if let Some((u, v, p)) = edges.iter().find(|(_, _, p)| !(0.0..=1.0).contains(p)) {
+            fail(
+                Failure::new(
+                    FailureKind::Parse,
+                    format!(
+                        "{}: edge {} {} has probability {}, outside [0, 1]",
+                        path.display(),
+                        u,
+                        v,
+                        p
+                    ),
+                )
+                .with("path", path.display()),
+            );
+        }
+        let graph = WeightedGraph::new(edges);
+        let start = Instant::now();
+        let expected = weighted::expected_triangles(&graph, threads, reduction);
+        println!("expected triangles {} in {:?}", expected, start.elapsed());
+        return;
+    }
+    if total_weight {
+        let (Some(path), Some(column)) = (&input, weight_column) else {
+            fail(Failure::usage(
+                "--total-weight requires an input file and --weight-column",
+            ));
+        };
+        let edges = open_input(path)
+            .and_then(|r| loader::read_weighted_edges(r, &dialect, column))
+            .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+        let graph = WeightedGraph::new(edges);
+        let start = Instant::now();
+        let total = weighted::total_weight(&graph, aggregate, threads, reduction);
+        println!("total triangle weight {} in {:?}", total, start.elapsed());
+        return;
+    }
+    if let Some(k) = heaviest {
+        let (Some(path), Some(column)) = (&input, weight_column) else {
+            fail(Failure::usage(
+                "--heaviest requires an input file and --weight-column",
+            ));
+        };
+        let edges = open_input(path)
+            .and_then(|r| loader::read_weighted_edges(r, &dialect, column))
+            .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+        let graph = WeightedGraph::new(edges);
+        let start = Instant::now();
+        let top = weighted::top_k_triangles(&graph, k, aggregate);
+        for (w, [a, b, c]) in &top {
+            println!("{} {} {} {}", a, b, c, w);
+        }
+        println!(
+            "found the {} heaviest triangles in {:?}",
+            top.len(),
+            start.elapsed()
+        );
+        return;
+    }
+
+    if let Some(path) = replay_from {
+        let steps = File::open(&path)
+            .map_err(LoadError::from)
+            .and_then(|f| transcript::read(BufReader::new(f)))
+            .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+        let steps = transcript::replay(steps, |path, dialect| {
+            open_input(path).and_then(|r| loader::read_edges(r, dialect))
+        })
+        .unwrap_or_else(|e| fail(e));
+        for step in &steps {
+            println!("{}", step);
         }
+        if !steps.iter().all(Step::matches) {
+            fail(
+                Failure::new(
+                    FailureKind::Invariant,
+                    "replay did not match the transcript",
+                )
+                .with("path", path.display()),
+            );
+        }
+        return;
     }
 
-    // Move from the lower position back up to the upper position. This
-    // "unbinds" the first variable.
-    fn up(&mut self) {
-        match self.level {
-            Position::Lower(i, _) => self.level = Position::Upper(i),
-            _ => panic!(),
+    // Check the CLI's JSON outputs against the snapshots in `dir`, or rewrite
+    // them, by running this binary over the built-in corpus.
+    if let Some((dir, update)) = snapshots {
+        let exe = env::current_exe().unwrap_or_else(|e| fail(io_failure(e.to_string())));
+        let io_error = |e: io::Error| io_failure(format!("{}: {}", dir.display(), e));
+        if update {
+            let n = snapshot::update(&exe, &dir).unwrap_or_else(|e| fail(io_error(e)));
+            println!("updated {} snapshots in {}", n, dir.display());
+            return;
+        }
+        let mismatches = snapshot::check(&exe, &dir).unwrap_or_else(|e| fail(io_error(e)));
+        for m in &mismatches {
+            print!("{}", m);
         }
+        if !mismatches.is_empty() {
+            fail(Failure::new(
+                FailureKind::Invariant,
+                format!(
+                    "{} of {} snapshots differ (rerun with --update-snapshots {} if that's intended)",
+                    mismatches.len(),
+                    snapshot::CASES.len(),
+                    dir.display()
+                ),
+            ));
+        }
+        println!("{} snapshots match", snapshot::CASES.len());
+        return;
     }
 
-    // Move from the upper position down to the lower position. This "binds" the
-    // current self.value().
-    fn down(&mut self) {
-        match self.level {
-            Position::Upper(i) => self.level = Position::Lower(i, 0),
-            _ => panic!(),
+    if generate {
+        let result = match &connect {
+            Some(addr) => TcpStream::connect(addr).and_then(|s| generate::stream(&stream, s)),
+            None => generate::stream(&stream, io::stdout().lock()),
+        };
+        match result {
+            Ok(_) => {}
+            // Stop quietly when whoever is reading goes away.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+            Err(e) => fail(io_failure(e.to_string())),
         }
+        return;
     }
 
-    // Reset the iterator to the start, at its current level. A bound variable
-    // (i.e., if we are in the Lower position) remains bound.
-    fn reset(&mut self) {
-        match &mut self.level {
-            Position::Upper(i) => *i = 0,
-            Position::Lower(_, j) => *j = 0,
+    if ingest {
+        let on_apply = |update| record(&mut recorder, &Event::Update(update));
+        // Read updates from stdin, or from the first connection to `listen`.
+        let result = match &listen {
+            Some(addr) => TcpListener::bind(addr)
+                .and_then(|l| l.accept())
+                .and_then(|(s, _)| {
+                    stream::ingest(BufReader::new(s), queue_capacity, overflow, on_apply)
+                }),
+            None => stream::ingest(
+                BufReader::new(io::stdin()),
+                queue_capacity,
+                overflow,
+                on_apply,
+            ),
+        };
+        match result {
+            Ok(report) => {
+                record(&mut recorder, &Event::Triangles(report.triangles));
+                finish(recorder);
+                print!("{}", report);
+            }
+            Err(e) => fail(io_failure(e.to_string())),
         }
+        return;
     }
 
-    // The current value we are pointing at, at whatever level we're at.
-    fn value(&self) -> Option<u64> {
-        match self.level {
-            Position::Upper(i) => Some(self.data.get(i)?.0),
-            Position::Lower(i, j) => self.data.get(i)?.1.get(j).cloned(),
+    if let Some(paths) = atom_files {
+        if input.is_some() {
+            fail(Failure::usage("--atoms replaces the input file"));
         }
+        let start = Instant::now();
+        let graphs: Vec<Graph> = paths
+            .iter()
+            .map(|path| {
+                let edges = open_input(path)
+                    .and_then(|r| loader::read_edges(r, &dialect))
+                    .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+                Graph::from_edges(edges)
+            })
+            .collect();
+        println!("loaded in {:?}", start.elapsed());
+        let start = Instant::now();
+        let count = find_triangles::count_triangles_across(&graphs[0], &graphs[1], &graphs[2]);
+        println!("found {} triangles in {:?}", count, start.elapsed());
+        return;
     }
 
-    // Advance to the next value.
-    fn next(&mut self) {
-        match &mut self.level {
-            Position::Upper(i) => {
-                *i += 1;
+    if recorder.is_some() && input.is_none() {
+        fail(Failure::usage(
+            "--record needs an input file, since a random graph can't be replayed",
+        ));
+    }
+    let data = match &input {
+        Some(path) if binary => {
+            dump::load(path).unwrap_or_else(|e| fail(Failure::index(path.display(), e)))
+        }
+        Some(path) if load_index || persist::is_index_file(path) => {
+            persist::load(path).unwrap_or_else(|e| fail(Failure::index(path.display(), e)))
+        }
+        // Nothing needs the edges themselves, so load them straight into the
+        // index.
+        Some(path)
+            if !lint_only && order.is_none() && anonymize_salt.is_none() && !reduce_input =>
+        {
+            record(&mut recorder, &Event::Dialect(dialect.clone()));
+            open_input(path)
+                .and_then(|r| pipeline::build(r, &dialect))
+                .unwrap_or_else(|e| fail(Failure::load(path.display(), e)))
+        }
+        Some(path) => match open_input(path).and_then(|r| loader::read_edges(r, &dialect)) {
+            Ok(edges) => {
+                // Shrink a graph on which the engines disagree, or one of them
+                // panics, to a minimal one on which they still do.
+                if reduce_input {
+                    let start = Instant::now();
+                    let before = edges.len();
+                    // Panics are expected while reducing, and would only be
+                    // noise.
+                    let hook = panic::take_hook();
+                    panic::set_hook(Box::new(|_| {}));
+                    let reduced = engine::reduce_disagreement(edges, heavy_threshold, threads);
+                    panic::set_hook(hook);
+                    let Some(reduced) = reduced else {
+                        fail(Failure::usage(
+                            "the engines agree on this graph, so there's nothing to reduce",
+                        ));
+                    };
+                    for (u, v) in &reduced {
+                        println!("{} {}", u, v);
+                    }
+                    eprintln!(
+                        "reduced {} edges to {} in {:?}",
+                        before,
+                        reduced.len(),
+                        start.elapsed()
+                    );
+                    return;
+                }
+                if let Some(salt) = anonymize_salt {
+                    // Without a salt of the user's own, use one nobody knows.
+                    let salt = salt.unwrap_or_else(rand::random);
+                    let mut out = io::BufWriter::new(io::stdout().lock());
+                    for (u, v) in anonymize::anonymize(&edges, salt) {
+                        if let Err(e) = writeln!(out, "{} {}", u, v) {
+                            if e.kind() == io::ErrorKind::BrokenPipe {
+                                return;
+                            }
+                            fail(io_failure(e.to_string()));
+                        }
+                    }
+                    if let Err(e) = out.flush() {
+                        fail(io_failure(e.to_string()));
+                    }
+                    return;
+                }
+                if lint_only {
+                    let findings = lint::lint(&edges, dialect.base);
+                    for finding in &findings {
+                        println!("warning: {}", finding);
+                    }
+                    if findings.is_empty() {
+                        println!("no problems found");
+                    }
+                    return;
+                }
+                record(&mut recorder, &Event::Dialect(dialect.clone()));
+                if let Some(order) = order {
+                    record(&mut recorder, &Event::Reorder(order));
+                }
+                build(edges, order)
             }
-            Position::Lower(_, j) => {
-                *j += 1;
+            Err(e) => fail(Failure::load(path.display(), e)),
+        },
+        None => {
+            // Generate a random graph, reproducibly if there's a seed.
+            match seed {
+                Some(seed) => generate::random_graph(nodes, prob, &mut StdRng::seed_from_u64(seed)),
+                None => generate::random_graph(nodes, prob, &mut rand::thread_rng()),
             }
         }
+    };
+    let data = Arc::new(data);
+    if let Some(budget) = memory_budget {
+        let size = index::heap_size(&data);
+        if size as u64 > budget {
+            fail(
+                Failure::new(
+                    FailureKind::ResourceLimit,
+                    format!(
+                        "the index takes {} bytes, over the memory budget of {}",
+                        size, budget
+                    ),
+                )
+                .with("budget", budget)
+                .with("required", size),
+            );
+        }
     }
-}
 
-fn main() {
-    // let data = Rc::new(vec![
-    //     (1, vec![2, 3, 4]),
-    //     (2, vec![4, 5]),
-    //     (3, vec![4, 6, 7]),
-    //     (4, vec![5, 7, 8]),
-    //     (5, vec![8]),
-    //     (6, vec![7]),
-    //     (7, vec![8]),
-    // ]);
+    if let Some(path) = save_index {
+        let start = Instant::now();
+        persist::save_in(&data, &path, byte_order).unwrap_or_else(|e| {
+            fail(io_failure(format!("{}: {}", path.display(), e)).with("path", path.display()))
+        });
+        println!("wrote {} in {:?}", path.display(), start.elapsed());
+        return;
+    }
 
-    // Generate a random graph.
-    let mut data = Vec::new();
-    let mut rng = rand::thread_rng();
-    for i in 1_u64..1000 {
-        data.push((i, (i + 1..1000).filter(|_| rng.gen_bool(0.5)).collect()));
+    if let Some(placement) = numa {
+        let (count, measurement) =
+            numa::count_triangles(&data, threads, placement, &Topology::detect());
+        print!("{}", measurement);
+        println!("found {} triangles", count);
+        return;
     }
-    let data = Rc::new(data);
 
-    let start = Instant::now();
+    if per_vertex {
+        let start = Instant::now();
+        let counts = pool::global()
+            .triangles_per_vertex(&data, threads)
+            .unwrap_or_else(|e| fail(Failure::pool(e)));
+        for (u, c) in counts {
+            println!("{} {}", u, c);
+        }
+        println!("counted per vertex in {:?}", start.elapsed());
+        return;
+    }
 
-    // Q(a, b, c) <- R(a, b), S(b, c), T(a, c);
-
-    // Since we're finding triangles in a graph, use the same data for all
-    // three.
-    let mut r = Index::new(data.clone());
-    let mut s = Index::new(data.clone());
-    let mut t = Index::new(data);
-
-    let mut count = 0_u64;
-
-    while let (Some(r_a), Some(t_a)) = (r.value(), t.value()) {
-        match r_a.cmp(&t_a) {
-            Ordering::Less => r.seek(t_a),
-            Ordering::Greater => t.seek(r_a),
-            Ordering::Equal => {
-                // a is now bound.
-                r.down();
-                t.down();
-                while let (Some(r_b), Some(s_b)) = (r.value(), s.value()) {
-                    match r_b.cmp(&s_b) {
-                        Ordering::Less => r.seek(s_b),
-                        Ordering::Greater => s.seek(r_b),
-                        Ordering::Equal => {
-                            // b is now bound.
-                            s.down();
-                            t.reset();
-                            while let (Some(s_c), Some(t_c)) = (s.value(), t.value()) {
-                                match s_c.cmp(&t_c) {
-                                    Ordering::Less => {
-                                        s.seek(t_c);
-                                    }
-                                    Ordering::Greater => {
-                                        t.seek(s_c);
-                                    }
-                                    Ordering::Equal => {
-                                        // We found a triangle!
-                                        count += 1;
-                                        s.next();
-                                        t.next();
-                                    }
-                                }
-                            }
-                            // Move on to the next value of b.
-                            s.up();
-                            s.next();
-                        }
+    if let Some((center, radius)) = ego {
+        // The vertex is given by its id in the file.
+        if order.is_some() {
+            fail(Failure::usage("--ego can't be combined with --reorder"));
+        }
+        let start = Instant::now();
+        let subgraph = Graph::from(&*data).ego_subgraph(center, radius);
+        let edges: usize = subgraph.csr().iter().map(|(_, vs)| vs.len()).sum();
+        println!(
+            "extracted {} edges within {} hops of {} in {:?}",
+            edges,
+            radius,
+            center,
+            start.elapsed()
+        );
+        let start = Instant::now();
+        let count = find_triangles::count_triangles(&subgraph);
+        println!("found {} triangles in {:?}", count, start.elapsed());
+        return;
+    }
+
+    if !seed_vertices.is_empty() {
+        // The vertices are given by their ids in the file.
+        if order.is_some() {
+            fail(Failure::usage("--vertex can't be combined with --reorder"));
+        }
+        let graph = Graph::from(&*data);
+        // For one vertex a search of every list for the edges into it is
+        // cheaper than turning every edge around; for more, reversing once
+        // leaves each query only its neighborhood to look at.
+        if seed_vertices.len() > 1 {
+            let start = Instant::now();
+            graph.reverse();
+            println!("reversed edges in {:?}", start.elapsed());
+        }
+        for x in seed_vertices {
+            let start = Instant::now();
+            let count = find_triangles::count_triangles_with(&graph, x);
+            println!(
+                "found {} triangles containing {} in {:?}",
+                count,
+                x,
+                start.elapsed()
+            );
+        }
+        return;
+    }
+
+    if participating {
+        let start = Instant::now();
+        let Participation { vertices, roles } = plan::participating(&data);
+        println!(
+            "{} vertices in some triangle in {:?}",
+            vertices.len(),
+            start.elapsed()
+        );
+        println!(
+            "of which as a: {}, as b: {}, as c: {}",
+            roles[0], roles[1], roles[2]
+        );
+        return;
+    }
+
+    if let Some(samples) = null_samples {
+        let edges: Vec<(u64, u64)> = data
+            .iter()
+            .flat_map(|(u, vs)| vs.iter().map(move |v| (*u, *v)))
+            .collect();
+        let start = Instant::now();
+        print!("{}", null::test(&edges, samples, stream.seed));
+        println!("tested in {:?}", start.elapsed());
+        return;
+    }
+
+    if let Some(swaps) = rewire_swaps {
+        // Write out one randomized copy of the graph; run again with other
+        // seeds for more of the ensemble.
+        let rewired = generate::rewire(&Graph::from(&*data), swaps, stream.seed);
+        let mut out = io::BufWriter::new(io::stdout().lock());
+        for (u, vs) in rewired.csr().iter() {
+            for v in vs {
+                if let Err(e) = writeln!(out, "{} {}", u, v) {
+                    if e.kind() == io::ErrorKind::BrokenPipe {
+                        return;
+                    }
+                    fail(io_failure(e.to_string()));
+                }
+            }
+        }
+        if let Err(e) = out.flush() {
+            fail(io_failure(e.to_string()));
+        }
+        return;
+    }
+
+    if list_triangles {
+        // Treat the graph as undirected, and list each triangle once.
+        let edges: Vec<(u64, u64)> = data
+            .iter()
+            .flat_map(|(u, vs)| vs.iter().map(move |v| (*u, *v)))
+            .collect();
+        let start = Instant::now();
+        let oriented = Oriented::new(&edges);
+        // Only sampled if it'll be reported.
+        let estimate = show_progress
+            .then(|| WorkEstimate::sample(oriented.adjacency(), eta::DEFAULT_SLICES, stream.seed));
+        let count = match &list_output {
+            // The triangles go straight to the file as they're found, since
+            // there can be far too many to hold.
+            Some(path) => {
+                let format = list_format
+                    .or_else(|| ListFormat::from_extension(path))
+                    .unwrap_or(ListFormat::Csv);
+                let file = File::create(path)
+                    .unwrap_or_else(|e| fail(io_failure(format!("{}: {}", path.display(), e))));
+                let mut sink = CountingSink(
+                    ProgressSink::new(
+                        WriteSink::with_format(BufWriter::new(file), format),
+                        estimate,
+                    ),
+                    0,
+                );
+                oriented.enumerate(&mut sink);
+                let CountingSink(ProgressSink { inner: sink, .. }, count) = sink;
+                if let Err(e) = sink.finish() {
+                    fail(io_failure(format!("{}: {}", path.display(), e)));
+                }
+                count
+            }
+            None => {
+                let format = list_format.unwrap_or(ListFormat::Lines);
+                let mut sink = CountingSink(
+                    ProgressSink::new(
+                        WriteSink::with_format(io::stdout().lock(), format),
+                        estimate,
+                    ),
+                    0,
+                );
+                oriented.enumerate(&mut sink);
+                let CountingSink(ProgressSink { inner: sink, .. }, count) = sink;
+                if let Err(e) = sink.finish() {
+                    if e.kind() != io::ErrorKind::BrokenPipe {
+                        fail(io_failure(e.to_string()));
                     }
                 }
-                // Move on to the next value of a.
-                s.reset();
-                r.up();
-                r.next();
-                t.up();
-                t.next();
+                count
+            }
+        };
+        println!(
+            "found {} undirected triangles in {:?}",
+            count,
+            start.elapsed()
+        );
+        return;
+    }
+
+    let engine = engine.with(heavy_threshold, threads);
+    let stats = GraphStats::collect(&data, top_k);
+    if show_stats {
+        print!("{}", stats);
+    }
+    if let Some(p) = keep_fraction {
+        let start = Instant::now();
+        let estimate = sparsify::estimate(&data, p, stream.seed)
+            .unwrap_or_else(|e| fail(Failure::usage(e.to_string())));
+        print!("{}", estimate);
+        println!("estimated in {:?}", start.elapsed());
+        return;
+    }
+    if let Some(target) = relative_error {
+        let start = Instant::now();
+        let threshold = heavy_threshold.unwrap_or((stats.edges as f64).sqrt() as usize);
+        let estimate = sample::until(&data, target, sample::Z_95, threshold, stream.seed);
+        print!("{}", estimate);
+        let (lo, hi) = estimate.interval(sample::Z_95);
+        println!(
+            "95% interval [{:.0}, {:.0}] from {} samples in {:?}",
+            lo,
+            hi,
+            estimate.sampled(),
+            start.elapsed()
+        );
+        return;
+    }
+    if let Some(samples) = approximate {
+        let start = Instant::now();
+        let estimate = if stratify {
+            let threshold = heavy_threshold.unwrap_or((stats.edges as f64).sqrt() as usize);
+            sample::stratified(&data, samples, threshold, stream.seed)
+        } else {
+            sample::uniform(&data, samples, stream.seed)
+        };
+        print!("{}", estimate);
+        println!("estimated in {:?}", start.elapsed());
+        return;
+    }
+
+    if let Some(format) = degree_format {
+        if format == "csv" {
+            print!("{}", stats.degree_csv());
+        } else {
+            print!("{}", stats.degree_json());
+        }
+        return;
+    }
+    if let Some(path) = communities {
+        // The assignment file shares the graph's dialect, with the vertex in
+        // the first column and its community in the second.
+        let dialect = Dialect {
+            columns: (0, 1),
+            ..dialect
+        };
+        let assignment = open_input(&path)
+            .and_then(|r| loader::read_communities(r, &dialect))
+            .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+        print!(
+            "{}",
+            community::count_by_community(&data, &Partition::new(assignment))
+        );
+        return;
+    }
+    if let Some(path) = similar_pairs {
+        // Like the assignment file, the pairs share the graph's dialect.
+        let dialect = Dialect {
+            columns: (0, 1),
+            ..dialect
+        };
+        let pairs = open_input(&path)
+            .and_then(|r| loader::read_edges(r, &dialect))
+            .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+        let start = Instant::now();
+        let scores = similarity::similarity(&data, &pairs, measure);
+        for ((u, v), score) in pairs.iter().zip(&scores) {
+            println!("{} {} {}", u, v, score);
+        }
+        println!(
+            "computed {} {} similarities in {:?}",
+            scores.len(),
+            measure.name(),
+            start.elapsed()
+        );
+        return;
+    }
+    if let Some(u) = recommend_for {
+        let start = Instant::now();
+        let ranked = similarity::recommend(&data, u, top_k);
+        for (v, closed) in &ranked {
+            println!("{} {}", v, closed);
+        }
+        println!(
+            "ranked {} candidates for {} in {:?}",
+            ranked.len(),
+            u,
+            start.elapsed()
+        );
+        return;
+    }
+    if let Some(path) = candidate_pairs {
+        let dialect = Dialect {
+            columns: (0, 1),
+            ..dialect
+        };
+        let pairs = open_input(&path)
+            .and_then(|r| loader::read_edges(r, &dialect))
+            .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+        let start = Instant::now();
+        let features = similarity::link_features(&data, &pairs);
+        println!("u v common adamic_adar closes_triangles");
+        for ((u, v), f) in pairs.iter().zip(&features) {
+            println!(
+                "{} {} {} {} {}",
+                u,
+                v,
+                f.common,
+                f.adamic_adar,
+                f.closes_triangles()
+            );
+        }
+        println!(
+            "computed features for {} pairs in {:?}",
+            features.len(),
+            start.elapsed()
+        );
+        return;
+    }
+    // Count over an oriented copy from here on, if asked to, which finds each
+    // triangle once however its edges were stored.
+    let fingerprint = stats.fingerprint;
+    let (data, stats) = match orientation {
+        Some(orientation) => {
+            let start = Instant::now();
+            let oriented = Arc::new(Oriented::from_adjacency(&data).into_adjacency());
+            let oriented_stats = GraphStats::collect(&oriented, top_k);
+            if output == OutputFormat::Text {
+                println!(
+                    "oriented by {} in {:?}: largest out-degree {} -> {}",
+                    orientation.name(),
+                    start.elapsed(),
+                    stats.max_degree,
+                    oriented_stats.max_degree
+                );
+            }
+            (oriented, oriented_stats)
+        }
+        None => (data, stats),
+    };
+    // Run the same query through every engine and report how much the generic
+    // interpreter costs over the compiled loop nest, which tells us how much
+    // there is to gain from specializing a query.
+    if compare {
+        let timings =
+            engine::compare(&data, &stats, heavy_threshold, threads).unwrap_or_else(|e| fail(e));
+        for t in &timings {
+            println!(
+                "{:>20}: {} triangles in {:?}",
+                t.engine, t.triangles, t.best
+            );
+        }
+        if let Err(e) = engine::check_agreement(&timings) {
+            fail(e);
+        }
+        if let Some(overhead) = engine::interpretation_overhead(&timings) {
+            println!(
+                "interpretation overhead: {:.2}x the compiled plan",
+                overhead
+            );
+        }
+        return;
+    }
+
+    if let Some(k) = clique_size {
+        let start = Instant::now();
+        let count = find_triangles::count_cliques(&Graph::from(&*data), k);
+        println!("found {} {}-cliques in {:?}", count, k, start.elapsed());
+        return;
+    }
+
+    if let Some(pattern) = pattern {
+        // Other relations are read as they are, so they'd no longer share ids
+        // with a renumbered graph.
+        if order.is_some() && !relations.is_empty() {
+            fail(Failure::usage(
+                "--relation can't be combined with --reorder",
+            ));
+        }
+        // Likewise the values of parameters, which are vertex ids.
+        if order.is_some() && !params.is_empty() {
+            fail(Failure::usage("--param can't be combined with --reorder"));
+        }
+        let edges = EdgeRelation::new(data.clone());
+        let extra: Vec<Box<dyn Relation>> = relations
+            .iter()
+            .map(|(_, arity, path)| -> Box<dyn Relation> {
+                if *arity == 2 {
+                    let loaded = open_input(path)
+                        .and_then(|r| loader::read_edges(r, &dialect))
+                        .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+                    Box::new(EdgeRelation::new(Arc::new(loader::to_adjacency(loaded))))
+                } else {
+                    let loaded = open_input(path)
+                        .and_then(|r| loader::read_tuples(r, &dialect, *arity))
+                        .unwrap_or_else(|e| fail(Failure::load(path.display(), e)));
+                    Box::new(TupleRelation::new(*arity, loaded))
+                }
+            })
+            .collect();
+        let mut named: Vec<(&str, &dyn Relation)> = vec![("E", &edges)];
+        for ((name, _, _), relation) in relations.iter().zip(&extra) {
+            named.push((name, &**relation));
+        }
+        let rule = &pattern.rules[0];
+        if !rule.params.is_empty() || !params.is_empty() {
+            if pattern.rules.len() > 1 {
+                fail(Failure::usage(
+                    "a query with parameters must be a single rule",
+                ));
+            }
+            let runs = rule
+                .runs(&params)
+                .unwrap_or_else(|e| fail(Failure::usage(format!("invalid --param: {}", e))));
+            let start = Instant::now();
+            let prepared = rule
+                .prepare(&named, &mut plan::Statistics::default())
+                .unwrap_or_else(|e| fail(Failure::usage(format!("invalid query: {}", e))));
+            let order: Vec<&str> = prepared.order().iter().map(|&v| &*rule.vars[v]).collect();
+            println!("prepared {} in {:?}", pattern.name(), start.elapsed());
+            println!("bound in order {}", order.join(", "));
+            let start = Instant::now();
+            for values in &runs {
+                let count = prepared
+                    .count(&named, values)
+                    .unwrap_or_else(|e| fail(Failure::usage(format!("invalid query: {}", e))));
+                let given: Vec<String> = rule
+                    .params
+                    .iter()
+                    .zip(values)
+                    .map(|(&p, v)| format!("{}={}", rule.vars[p], v))
+                    .collect();
+                println!(
+                    "{}({}): {} results",
+                    pattern.name(),
+                    given.join(", "),
+                    count
+                );
+            }
+            println!("ran {} times in {:?}", runs.len(), start.elapsed());
+            return;
+        }
+        let start = Instant::now();
+        let (count, plan) = pattern
+            .count(&named, semantics, &mut plan::Statistics::default())
+            .unwrap_or_else(|e| fail(Failure::usage(format!("invalid query: {}", e))));
+        println!(
+            "found {} results for {} in {:?}",
+            count,
+            pattern.name(),
+            start.elapsed()
+        );
+        // Only a single rule comes back with its plan.
+        if let Some(plan) = plan {
+            let vars = &pattern.rules[0].vars;
+            let order: Vec<&str> = plan.order.iter().map(|&v| &*vars[v]).collect();
+            println!("bound in order {}", order.join(", "));
+        }
+        return;
+    }
+
+    if four_cycles {
+        let start = Instant::now();
+        let count = find_triangles::count_four_cycles(&Graph::from(&*data));
+        println!("found {} 4-cycles in {:?}", count, start.elapsed());
+        return;
+    }
+
+    let start = Instant::now();
+    let backend = backend.resolve(&engine, &data, &stats);
+    let counted = engine::run_on(backend, &engine, &data, &stats).unwrap_or_else(|e| fail(e));
+    if let Some(size) = &counted.ef {
+        eprintln!("{}", size);
+    }
+    let count = counted.triangles;
+    let elapsed = start.elapsed();
+    match output {
+        OutputFormat::Text => println!("found {} triangles in {:?}", count, elapsed),
+        OutputFormat::Json => println!(
+            "{{\"triangles\":{},\"engine\":\"{}\",\"seconds\":{}}}",
+            count,
+            engine.name(),
+            elapsed.as_secs_f64()
+        ),
+    }
+    if let Some(path) = input {
+        record(&mut recorder, &Event::Load { path, fingerprint });
+    }
+    if let Some(orientation) = orientation {
+        record(&mut recorder, &Event::Orient(orientation));
+    }
+    record(
+        &mut recorder,
+        &Event::Count {
+            engine: engine.name().to_string(),
+            triangles: count,
+        },
+    );
+    finish(recorder);
+}
+
+// Renumber the edges in the given order, if any, and build the index.
+fn build(mut edges: Vec<(u64, u64)>, order: Option<reorder::Order>) -> Adjacency {
+    if let Some(order) = order {
+        let start = Instant::now();
+        let before = reorder::mean_edge_span(&edges);
+        Relabeling::new(&edges, order).apply(&mut edges);
+        println!(
+            "reordered {:?} in {:?}: mean edge span {:.1} -> {:.1}",
+            order,
+            start.elapsed(),
+            before,
+            reorder::mean_edge_span(&edges)
+        );
+    }
+    loader::to_adjacency(edges)
+}
+
+fn record(recorder: &mut Option<Recorder>, event: &Event) {
+    if let Some(r) = recorder {
+        if let Err(e) = r.record(event) {
+            fail(io_failure(format!("recording the session: {}", e)));
+        }
+    }
+}
+
+fn finish(recorder: Option<Recorder>) {
+    if let Some(Err(e)) = recorder.map(Recorder::finish) {
+        fail(io_failure(format!("recording the session: {}", e)));
+    }
+}
+
+// The settings from `--config <path>` if given, or from the default config
+// file if there is one.
+fn load_config(args: &[String]) -> Config {
+    let path = args
+        .iter()
+        .position(|a| a == "--config")
+        .and_then(|i| args.get(i + 1));
+    let result = match path {
+        Some(path) => Config::load(Path::new(path)),
+        None => Config::load_default(),
+    };
+    result.unwrap_or_else(|e| {
+        let path = path.map_or(config::DEFAULT_FILE, |p| p.as_str());
+        fail(Failure::load(path, e));
+    })
+}
+
+// How output (including failures) is formatted, once it's known.
+static OUTPUT: OnceLock<OutputFormat> = OnceLock::new();
+
+// Report the failure the way the output format calls for, and exit with its
+// kind's exit code.
+fn fail(failure: Failure) -> ! {
+    match OUTPUT.get() {
+        Some(OutputFormat::Json) => eprintln!("{}", failure.to_json()),
+        _ => eprintln!("{}", failure),
+    }
+    process::exit(failure.kind.exit_code());
+}
+
+fn io_failure(message: String) -> Failure {
+    Failure::new(FailureKind::Io, message)
+}
+
+// Treat a panic as the invariant violation it is: still print the usual
+// message (or the JSON envelope), but exit with the invariant code rather
+// than the generic one.
+fn install_panic_hook() {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if OUTPUT.get() == Some(&OutputFormat::Json) {
+            let message = info
+                .payload()
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| info.payload().downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panic".to_string());
+            let mut failure = Failure::new(FailureKind::Invariant, message);
+            if let Some(location) = info.location() {
+                failure = failure.with("location", location);
             }
+            eprintln!("{}", failure.to_json());
+        } else {
+            default(info);
+        }
+        process::exit(FailureKind::Invariant.exit_code());
+    }));
+}
+
+// Passes triangles through to another sink, keeping count of them.
+struct CountingSink<S>(S, u64);
+
+impl<S: ResultSink> ResultSink for CountingSink<S> {
+    fn on_triangle(&mut self, a: u64, b: u64, c: u64) {
+        self.0.on_triangle(a, b, c);
+    }
+
+    fn on_progress(&mut self, progress: &Progress) {
+        self.0.on_progress(progress);
+    }
+
+    fn on_done(&mut self, stats: &EnumerationStats) {
+        self.1 = stats.triangles;
+    }
+}
+
+// Passes triangles through to another sink, reporting on stderr how far
+// through the estimated work the enumeration is, at most once a second.
+struct ProgressSink<S> {
+    inner: S,
+    estimate: Option<WorkEstimate>,
+    start: Instant,
+    reported: Instant,
+}
+
+impl<S> ProgressSink<S> {
+    fn new(inner: S, estimate: Option<WorkEstimate>) -> Self {
+        let now = Instant::now();
+        Self {
+            inner,
+            estimate,
+            start: now,
+            reported: now,
+        }
+    }
+}
+
+impl<S: ResultSink> ResultSink for ProgressSink<S> {
+    fn on_triangle(&mut self, a: u64, b: u64, c: u64) {
+        self.inner.on_triangle(a, b, c);
+    }
+
+    fn on_progress(&mut self, progress: &Progress) {
+        let Some(estimate) = &self.estimate else {
+            return;
+        };
+        if self.reported.elapsed() < Duration::from_secs(1) || progress.done == progress.total {
+            return;
         }
+        self.reported = Instant::now();
+        let elapsed = self.start.elapsed();
+        match estimate.remaining(progress.done, elapsed) {
+            Some(left) => eprintln!(
+                "{:.0}% done, {} triangles so far, about {:.0?} left",
+                estimate.fraction(progress.done) * 100.0,
+                progress.triangles,
+                left
+            ),
+            None => eprintln!("{} triangles so far", progress.triangles),
+        }
+    }
+
+    fn on_done(&mut self, stats: &EnumerationStats) {
+        self.inner.on_done(stats);
     }
-    println!("found {} triangles in {:?}", count, start.elapsed());
+}
+
+// Parse a flag's value, or exit explaining what was expected.
+fn parsed<T: std::str::FromStr>(flag: &str, v: &str, expected: &str) -> T {
+    v.parse().unwrap_or_else(|_| {
+        fail(Failure::usage(format!(
+            "{} expects {}, got {:?}",
+            flag, expected, v
+        )));
+    })
+}
+
+// The file at `path` to read an input from, decompressed if need be.
+fn open_input(path: &Path) -> Result<BufReader<Box<dyn Read + Send>>, LoadError> {
+    Ok(BufReader::new(decompress::open(path)?))
 }
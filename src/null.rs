@@ -0,0 +1,119 @@
+// Whether a graph has more (or fewer) triangles than its degrees alone would
+// explain. The observed count is compared against an ensemble of random
+// graphs with the same degrees, each made by rewiring the original with
+// degree-preserving edge swaps (the configuration model, sampled the usual
+// way by Markov chain), and summarized as a z-score and an empirical p-value.
+
+use std::{collections::HashSet, fmt};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{count_triangles, Graph};
+
+// How many swaps to attempt per edge when randomizing a graph. Around ten per
+// edge is the usual rule of thumb for the chain to forget where it started.
+pub const SWAPS_PER_EDGE: usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct Significance {
+    pub observed: u64,
+    // The triangle counts of the randomized graphs.
+    pub samples: Vec<u64>,
+}
+
+impl Significance {
+    pub fn mean(&self) -> f64 {
+        self.samples.iter().sum::<u64>() as f64 / self.samples.len().max(1) as f64
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        let n = self.samples.len();
+        if n < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let ss: f64 = self
+            .samples
+            .iter()
+            .map(|&c| (c as f64 - mean).powi(2))
+            .sum();
+        (ss / (n - 1) as f64).sqrt()
+    }
+
+    // How many standard deviations the observed count is from the ensemble's
+    // mean.
+    pub fn z_score(&self) -> f64 {
+        let diff = self.observed as f64 - self.mean();
+        match self.std_dev() {
+            sd if sd > 0.0 => diff / sd,
+            _ if diff == 0.0 => 0.0,
+            _ => diff.signum() * f64::INFINITY,
+        }
+    }
+
+    // The chance of a random graph having at least as many triangles as the
+    // observed one, estimated from the ensemble with the usual add-one
+    // correction, so that it's never zero from a finite sample.
+    pub fn p_value(&self) -> f64 {
+        let at_least = self.samples.iter().filter(|&&c| c >= self.observed).count();
+        (at_least + 1) as f64 / (self.samples.len() + 1) as f64
+    }
+}
+
+impl fmt::Display for Significance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "observed triangles: {}", self.observed)?;
+        writeln!(
+            f,
+            "null model:         {:.1} ± {:.1} over {} degree-preserving samples",
+            self.mean(),
+            self.std_dev(),
+            self.samples.len()
+        )?;
+        writeln!(f, "z-score:            {:.2}", self.z_score())?;
+        writeln!(f, "p-value:            {:.4}", self.p_value())
+    }
+}
+
+// Compare the triangle count of the graph with `edges` against `samples`
+// randomized copies of it.
+pub fn test(edges: &[(u64, u64)], samples: usize, seed: u64) -> Significance {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let observed = count_triangles(&Graph::from_edges(edges.to_vec()));
+    let samples = (0..samples)
+        .map(|_| {
+            let randomized = rewire(edges, SWAPS_PER_EDGE * edges.len(), &mut rng);
+            count_triangles(&Graph::from_edges(randomized))
+        })
+        .collect();
+    Significance { observed, samples }
+}
+
+// Randomize the directed graph with `edges` by attempting `swaps` swaps, each
+// of which picks two edges (a, b) and (c, d) and replaces them with (a, d)
+// and (c, b). That keeps every vertex's in- and out-degree. Swaps that would
+// make a self-loop or an edge that's already there are skipped, so the graph
+// stays simple.
+pub fn rewire(edges: &[(u64, u64)], swaps: usize, rng: &mut impl Rng) -> Vec<(u64, u64)> {
+    let mut edges: Vec<(u64, u64)> = edges.to_vec();
+    edges.sort_unstable();
+    edges.dedup();
+    if edges.len() < 2 {
+        return edges;
+    }
+    let mut present: HashSet<(u64, u64)> = edges.iter().copied().collect();
+    for _ in 0..swaps {
+        let (i, j) = (rng.gen_range(0..edges.len()), rng.gen_range(0..edges.len()));
+        let ((a, b), (c, d)) = (edges[i], edges[j]);
+        if a == d || c == b || present.contains(&(a, d)) || present.contains(&(c, b)) {
+            continue;
+        }
+        present.remove(&(a, b));
+        present.remove(&(c, d));
+        present.insert((a, d));
+        present.insert((c, b));
+        edges[i] = (a, d);
+        edges[j] = (c, b);
+    }
+    edges
+}
@@ -0,0 +1,242 @@
+// Where the index lives when counting on a machine with several NUMA nodes
+// (sockets, roughly). Memory on another node is slower to reach, and the
+// count reads neighbor lists from all over the index, so there's a trade-off:
+//
+// - Replicate gives every node a full copy of the index, so every read is
+//   local, at the cost of one copy per node.
+// - Partition splits the index's first level between the nodes, each
+//   holding the part its threads start from. There's only one copy, but
+//   looking up a `b` often means reading another node's part.
+//
+// Threads are pinned to their node's CPUs, and each node's memory is copied
+// by a thread on that node, so the OS's first-touch policy places it there.
+// Both placements report how much memory they took and how long each phase
+// took, so the choice can be made from measurements.
+
+use std::{
+    fmt, fs, thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    index::{self, Adjacency},
+    intersect,
+    parallel::Ranges,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Placement {
+    Replicate,
+    Partition,
+}
+
+impl Placement {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "replicate" => Some(Placement::Replicate),
+            "partition" => Some(Placement::Partition),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Placement::Replicate => "replicate",
+            Placement::Partition => "partition",
+        }
+    }
+}
+
+// The CPUs of each NUMA node.
+#[derive(Clone, Debug)]
+pub struct Topology {
+    pub nodes: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    // Read the nodes from sysfs on Linux. Anywhere else, or if that fails,
+    // treat the machine as a single node.
+    pub fn detect() -> Self {
+        let mut nodes = Vec::new();
+        if let Ok(entries) = fs::read_dir("/sys/devices/system/node") {
+            let mut ids: Vec<usize> = entries
+                .filter_map(|e| {
+                    e.ok()?
+                        .file_name()
+                        .to_str()?
+                        .strip_prefix("node")?
+                        .parse()
+                        .ok()
+                })
+                .collect();
+            ids.sort_unstable();
+            for id in ids {
+                let path = format!("/sys/devices/system/node/node{}/cpulist", id);
+                if let Some(cpus) = fs::read_to_string(path)
+                    .ok()
+                    .and_then(|s| parse_cpulist(&s))
+                {
+                    if !cpus.is_empty() {
+                        nodes.push(cpus);
+                    }
+                }
+            }
+        }
+        if nodes.is_empty() {
+            let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+            nodes.push((0..cpus).collect());
+        }
+        Self { nodes }
+    }
+}
+
+// Parse a CPU list like "0-3,8-11".
+fn parse_cpulist(s: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in s.trim().split(',').filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((lo, hi)) => cpus.extend(lo.parse::<usize>().ok()?..=hi.parse().ok()?),
+            None => cpus.push(part.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+// Run the calling thread only on the given CPUs, if the platform lets us.
+#[cfg(all(target_os = "linux", feature = "numa"))]
+fn pin(cpus: &[usize]) {
+    // SAFETY: the set is zeroed before use, CPU_SET only writes within it,
+    // and sched_setaffinity only reads it. Failure just leaves the thread
+    // unpinned.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            if cpu < libc::CPU_SETSIZE as usize {
+                libc::CPU_SET(cpu, &mut set);
+            }
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set);
+    }
+}
+
+#[cfg(not(all(target_os = "linux", feature = "numa")))]
+fn pin(_cpus: &[usize]) {}
+
+#[derive(Clone, Debug)]
+pub struct Measurement {
+    pub placement: Placement,
+    pub nodes: usize,
+    // Heap taken by the copies (or parts) of the index.
+    pub bytes: usize,
+    // Time spent placing the index, and counting with it.
+    pub place: Duration,
+    pub count: Duration,
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "placement: {}", self.placement.name())?;
+        writeln!(f, "nodes:     {}", self.nodes)?;
+        writeln!(f, "memory:    {} bytes", self.bytes)?;
+        writeln!(f, "placed in  {:?}", self.place)?;
+        writeln!(f, "counted in {:?}", self.count)
+    }
+}
+
+// Count the triangles of `parallel::count_triangles` with the index placed
+// across the nodes of `topology` as `placement` says.
+pub fn count_triangles(
+    data: &Adjacency,
+    threads: usize,
+    placement: Placement,
+    topology: &Topology,
+) -> (u64, Measurement) {
+    let threads = threads.max(1);
+    let nodes = topology.nodes.len().min(threads);
+    // Threads are spread over the nodes in contiguous groups, so each node's
+    // threads start out on one contiguous range of the first level.
+    let node_of = |t: usize| t * nodes / threads;
+    let work = Ranges::new(data.len(), threads);
+    let bounds: Vec<usize> = (0..=nodes)
+        .map(|k| {
+            let first = (0..threads).find(|&t| node_of(t) >= k).unwrap_or(threads);
+            data.len() * first / threads
+        })
+        .collect();
+
+    let start = Instant::now();
+    let copies: Vec<Adjacency> = thread::scope(|scope| {
+        let builders: Vec<_> = (0..nodes)
+            .map(|k| {
+                let cpus = &topology.nodes[k];
+                let part = match placement {
+                    Placement::Replicate => &data[..],
+                    Placement::Partition => &data[bounds[k]..bounds[k + 1]],
+                };
+                scope.spawn(move || {
+                    pin(cpus);
+                    part.to_vec()
+                })
+            })
+            .collect();
+        builders.into_iter().map(|b| b.join().unwrap()).collect()
+    });
+    let place = start.elapsed();
+    let bytes = copies.iter().map(index::heap_size).sum();
+
+    let start = Instant::now();
+    let count = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|me| {
+                let (work, copies, bounds) = (&work, &copies, &bounds);
+                let cpus = &topology.nodes[node_of(me)];
+                let local = &copies[node_of(me).min(copies.len() - 1)];
+                scope.spawn(move || {
+                    pin(cpus);
+                    let mut count = 0;
+                    work.run(me, |i| {
+                        count += match placement {
+                            Placement::Replicate => {
+                                rooted(local, i, |b| index::neighbors(local, b))
+                            }
+                            Placement::Partition => {
+                                let k = bounds.partition_point(|&lo| lo <= i) - 1;
+                                rooted(&copies[k], i - bounds[k], |b| find(copies, b))
+                            }
+                        };
+                    });
+                    count
+                })
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).sum()
+    });
+    let measurement = Measurement {
+        placement,
+        nodes,
+        bytes,
+        place,
+        count: start.elapsed(),
+    };
+    (count, measurement)
+}
+
+// The triangles whose `a` is at position `i` of `data`, looking up each `b`'s
+// neighbors with `neighbors`.
+fn rooted<'a>(data: &'a Adjacency, i: usize, neighbors: impl Fn(u64) -> &'a [u64]) -> u64 {
+    let a_neighbors = &data[i].1;
+    a_neighbors
+        .iter()
+        .map(|&b| intersect::count(a_neighbors, neighbors(b)))
+        .sum()
+}
+
+// The neighbors of `u` in whichever part holds it. There's one part per node,
+// so they're few enough to scan.
+fn find(parts: &[Adjacency], u: u64) -> &[u64] {
+    parts
+        .iter()
+        .rev()
+        .find(|part| part.first().is_some_and(|(v, _)| *v <= u))
+        .map_or(&[], |part| index::neighbors(part, u))
+}
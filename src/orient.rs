@@ -0,0 +1,140 @@
+// Counting and listing undirected triangles over an oriented copy of the
+// graph. Each edge is kept only once, pointing from the lower-degree end to
+// the higher-degree end, which leaves every triangle exactly one way to be
+// found and keeps every vertex's out-list short (at most sqrt(2m) long), so
+// hubs no longer dominate the intersections.
+//
+// To make that order line up with the order of the ids, the copy is built
+// over ids renumbered by degree (see `reorder`). That numbering never leaves
+// this module: triangles are translated back to the original ids as they are
+// found.
+
+use crate::{
+    index::Adjacency,
+    intersect, loader,
+    reorder::{Order, Relabeling},
+    sink::{self, ResultSink},
+};
+
+// Which way to point each edge. Only by degree for now.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Degree,
+}
+
+impl Orientation {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "degree" => Some(Orientation::Degree),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Orientation::Degree => "degree",
+        }
+    }
+}
+
+pub struct Oriented {
+    // Over the renumbered ids, each out-list holding only ids smaller than
+    // its source (that is, of vertices of at least the source's degree).
+    data: Adjacency,
+    labels: Relabeling,
+}
+
+impl Oriented {
+    // Orient the edges, ignoring their direction. Self-loops and repeated
+    // edges (in either direction) are dropped.
+    pub fn new(edges: &[(u64, u64)]) -> Self {
+        let labels = Relabeling::new(edges, Order::Degree);
+        let mut oriented: Vec<(u64, u64)> = edges
+            .iter()
+            .filter(|(u, v)| u != v)
+            .map(|&(u, v)| {
+                let (u, v) = (labels.new_id(u).unwrap(), labels.new_id(v).unwrap());
+                (u.max(v), u.min(v))
+            })
+            .collect();
+        oriented.sort_unstable();
+        oriented.dedup();
+        Self {
+            data: loader::to_adjacency(oriented),
+            labels,
+        }
+    }
+
+    // Orient the edges of an index, as `new` does.
+    pub fn from_adjacency(data: &Adjacency) -> Self {
+        let edges: Vec<(u64, u64)> = data
+            .iter()
+            .flat_map(|(u, vs)| vs.iter().map(move |&v| (*u, v)))
+            .collect();
+        Self::new(&edges)
+    }
+
+    // The oriented copy itself, over the renumbered ids. Every edge points
+    // from a larger id to a smaller one, so any join for R(a, b), S(b, c),
+    // T(a, c) run over it finds each triangle exactly once, as a > b > c,
+    // with the short out-lists to intersect.
+    pub fn into_adjacency(self) -> Adjacency {
+        self.data
+    }
+
+    // The oriented copy, as `into_adjacency` would give it, and as
+    // `enumerate` walks it.
+    pub fn adjacency(&self) -> &Adjacency {
+        &self.data
+    }
+
+    pub fn count_triangles(&self) -> u64 {
+        let mut count = 0;
+        self.walk(|a_neighbors, b_neighbors, _, _| {
+            count += intersect::count(a_neighbors, b_neighbors);
+        });
+        count
+    }
+
+    // Call `f` with each triangle, in terms of the original ids. Which vertex
+    // comes first in each triangle is unspecified.
+    pub fn for_each_triangle(&self, mut f: impl FnMut([u64; 3])) {
+        let original = |u| self.labels.original_id(u).unwrap();
+        self.walk(|a_neighbors, b_neighbors, a, b| {
+            let (mut x, mut y) = (0, 0);
+            while x < a_neighbors.len() && y < b_neighbors.len() {
+                let (u, v) = (a_neighbors[x], b_neighbors[y]);
+                if u == v {
+                    f([original(a), original(b), original(u)]);
+                }
+                x += (u <= v) as usize;
+                y += (v <= u) as usize;
+            }
+        });
+    }
+
+    pub fn triangles(&self) -> Vec<[u64; 3]> {
+        let mut triangles = Vec::new();
+        self.enumerate(&mut triangles);
+        triangles
+    }
+
+    // Send each triangle to `sink`, in terms of the original ids.
+    pub fn enumerate(&self, sink: &mut dyn ResultSink) {
+        sink::enumerate_mapped(&self.data, sink, |u| self.labels.original_id(u).unwrap());
+    }
+
+    // Bind a and b of R(a, b), S(b, c), T(a, c), and hand `f` the two lists
+    // whose intersection binds c, along with (renumbered) a and b.
+    fn walk(&self, mut f: impl FnMut(&[u64], &[u64], u64, u64)) {
+        let data = &self.data;
+        for (a, a_neighbors) in data {
+            for &b in a_neighbors {
+                // Out-lists point to smaller ids, so b's entry is behind a's.
+                if let Ok(pos) = data.binary_search_by_key(&b, |(v, _)| *v) {
+                    f(a_neighbors, &data[pos].1, *a, b);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,255 @@
+// Counting triangles on several threads. Each thread starts out owning an
+// equal range of the first-level vertices, and works through it from the
+// bottom. A thread that runs out steals the top half of whatever is left of
+// the largest range, so ranges are only split when someone needs the work,
+// and a thread that lands on a few heavy vertices doesn't hold everyone else
+// up.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+    thread,
+};
+
+use crate::{
+    index::{self, Adjacency},
+    intersect,
+};
+
+// Above this many bytes of per-thread count arrays, per-vertex counting
+// shares one array of atomics between the threads instead.
+pub const ATOMIC_THRESHOLD: usize = 1 << 30;
+
+// The number of threads to use when none is given.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// Q(a, b, c) <- R(a, b), S(b, c), T(a, c), with all three atoms over `data`.
+pub fn count_triangles(data: &Adjacency, threads: usize) -> u64 {
+    let threads = threads.max(1);
+    let work = Ranges::new(data.len(), threads);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|me| {
+                let work = &work;
+                scope.spawn(move || count_share(data, work, me))
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).sum()
+    })
+}
+
+// Each vertex in any triangle of the query above, in ascending order, with
+// the number of triangles it's one of a, b, or c in.
+//
+// Counts are kept in arrays indexed by vertex id, with the ids renumbered
+// first if they're too sparse for that. Each thread counts into an array of
+// its own, and the arrays are summed at the end; a single shared array would
+// have every thread fighting over the cache lines of the popular vertices. If
+// that would take more than `ATOMIC_THRESHOLD` bytes, the threads share one
+// array of atomics instead.
+pub fn triangles_per_vertex(data: &Adjacency, threads: usize) -> Vec<(u64, u64)> {
+    match index::compact(data) {
+        Some((compacted, original)) => {
+            in_triangles(count_per_vertex(&compacted, threads), &original)
+        }
+        None => in_triangles(count_per_vertex(data, threads), &[]),
+    }
+}
+
+// The bytes of per-thread count arrays `threads` threads would need.
+pub(crate) fn array_bytes(universe: usize, threads: usize) -> usize {
+    universe
+        .saturating_mul(threads)
+        .saturating_mul(std::mem::size_of::<u64>())
+}
+
+// The vertices with any triangles among `counts`, which are indexed by id,
+// or by position in `original` if it isn't empty.
+pub(crate) fn in_triangles(counts: Vec<u64>, original: &[u64]) -> Vec<(u64, u64)> {
+    counts
+        .into_iter()
+        .enumerate()
+        .filter(|(_, c)| *c > 0)
+        .map(|(u, c)| (original.get(u).copied().unwrap_or(u as u64), c))
+        .collect()
+}
+
+// The per-vertex counts, indexed by id.
+pub(crate) fn count_per_vertex(data: &Adjacency, threads: usize) -> Vec<u64> {
+    let threads = threads.max(1);
+    let universe = universe(data);
+    let work = Ranges::new(data.len(), threads);
+    let count_into = |me: usize, add: &mut dyn FnMut(u64)| vertex_share(data, &work, me, add);
+
+    if array_bytes(universe, threads) > ATOMIC_THRESHOLD {
+        let counts: Vec<AtomicU64> = (0..universe).map(|_| AtomicU64::new(0)).collect();
+        thread::scope(|scope| {
+            for me in 0..threads {
+                let (counts, count_into) = (&counts, &count_into);
+                scope.spawn(move || {
+                    count_into(me, &mut |u| {
+                        counts[u as usize].fetch_add(1, Ordering::Relaxed);
+                    })
+                });
+            }
+        });
+        return counts.into_iter().map(AtomicU64::into_inner).collect();
+    }
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|me| {
+                let count_into = &count_into;
+                scope.spawn(move || {
+                    let mut counts = vec![0_u64; universe];
+                    count_into(me, &mut |u| counts[u as usize] += 1);
+                    counts
+                })
+            })
+            .collect();
+        let mut total = vec![0_u64; universe];
+        for worker in workers {
+            for (t, c) in total.iter_mut().zip(worker.join().unwrap()) {
+                *t += c;
+            }
+        }
+        total
+    })
+}
+
+// One past the largest vertex id in `data`.
+pub(crate) fn universe(data: &Adjacency) -> usize {
+    data.iter()
+        .flat_map(|(u, vs)| std::iter::once(*u).chain(vs.last().copied()))
+        .max()
+        .map_or(0, |m| m as usize + 1)
+}
+
+// Thread `me`'s part of `count_triangles`.
+pub(crate) fn count_share(data: &Adjacency, work: &Ranges, me: usize) -> u64 {
+    let mut count = 0;
+    work.run(me, |i| {
+        for_each_b(data, i, |a_neighbors, b_neighbors, _| {
+            count += intersect::count(a_neighbors, b_neighbors);
+        });
+    });
+    count
+}
+
+// Thread `me`'s part of `triangles_per_vertex`, calling `add` with each
+// corner of each triangle it finds.
+pub(crate) fn vertex_share(data: &Adjacency, work: &Ranges, me: usize, add: &mut dyn FnMut(u64)) {
+    work.run(me, |i| {
+        let a = data[i].0;
+        for_each_b(data, i, |a_neighbors, b_neighbors, b| {
+            let (mut x, mut y) = (0, 0);
+            while x < a_neighbors.len() && y < b_neighbors.len() {
+                let (u, v) = (a_neighbors[x], b_neighbors[y]);
+                if u == v {
+                    add(a);
+                    add(b);
+                    add(u);
+                }
+                x += (u <= v) as usize;
+                y += (v <= u) as usize;
+            }
+        });
+    });
+}
+
+// The first-level positions still to be counted, as one range per thread.
+pub(crate) struct Ranges {
+    ranges: Vec<Mutex<(usize, usize)>>,
+}
+
+impl Ranges {
+    pub(crate) fn new(len: usize, threads: usize) -> Self {
+        let ranges = (0..threads)
+            .map(|t| Mutex::new((len * t / threads, len * (t + 1) / threads)))
+            .collect();
+        Self { ranges }
+    }
+
+    // Call `f` with positions from thread `me`'s range until it's empty, then
+    // steal more, until there's nothing left anywhere.
+    pub(crate) fn run(&self, me: usize, mut f: impl FnMut(usize)) {
+        loop {
+            while let Some(i) = self.take(me) {
+                f(i);
+            }
+            if !self.steal(me) {
+                return;
+            }
+        }
+    }
+
+    fn take(&self, me: usize) -> Option<usize> {
+        let mut range = lock(&self.ranges[me]);
+        let (lo, hi) = *range;
+        (lo < hi).then(|| {
+            range.0 += 1;
+            lo
+        })
+    }
+
+    // Move the top half of the largest other range into ours, returning
+    // whether there was anything to take. Sizes are read without holding
+    // every lock at once, so the victim is re-checked once it's locked.
+    fn steal(&self, me: usize) -> bool {
+        loop {
+            let victim = (0..self.ranges.len())
+                .filter(|&t| t != me)
+                .map(|t| {
+                    let (lo, hi) = *lock(&self.ranges[t]);
+                    (hi - lo, t)
+                })
+                .max();
+            let Some((left, victim)) = victim else {
+                return false;
+            };
+            if left == 0 {
+                return false;
+            }
+            let mut theirs = lock(&self.ranges[victim]);
+            let (lo, hi) = *theirs;
+            if lo == hi {
+                continue;
+            }
+            let mid = lo + (hi - lo) / 2;
+            theirs.1 = mid;
+            drop(theirs);
+            *lock(&self.ranges[me]) = (mid, hi);
+            return true;
+        }
+    }
+}
+
+fn lock(range: &Mutex<(usize, usize)>) -> std::sync::MutexGuard<'_, (usize, usize)> {
+    range.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Bind b for the a at position `i`, calling `f` with the neighbors of a and
+// of b (whose intersection binds c), and b itself.
+pub(crate) fn for_each_b(data: &Adjacency, i: usize, mut f: impl FnMut(&[u64], &[u64], u64)) {
+    for_each_b_at(data, i, |_, pos| f(&data[i].1, &data[pos].1, data[pos].0));
+}
+
+// Like `for_each_b`, but calling `f` with where b is: its position among a's
+// neighbors, and its own position in `data`. For callers that keep more
+// alongside the lists, like weights.
+pub(crate) fn for_each_b_at(data: &Adjacency, i: usize, mut f: impl FnMut(usize, usize)) {
+    let mut pos = 0;
+    for (at, &b) in data[i].1.iter().enumerate() {
+        pos += data[pos..].partition_point(|(v, _)| *v < b);
+        if pos == data.len() {
+            break;
+        }
+        if data[pos].0 == b {
+            f(at, pos);
+        }
+    }
+}
@@ -0,0 +1,586 @@
+// Conjunctive patterns written as text, Datalog style:
+//
+//     Q(a, b, c) :- E(a, b), E(b, c), E(a, c)
+//
+// The body is a conjunction of atoms over named relations, any of which can
+// be negated with `!` (or `not`) to rule out bindings whose tuple is present.
+// Variables are named rather than numbered; the planner picks the order to
+// bind them in, unless a `/*+ order(a, c, b) */` hint says otherwise. The
+// head lists the variables the caller is interested in: with all of them,
+// every result is counted, and with fewer, each distinct binding of just
+// those (so `Q(a) :- E(a, b), E(b, c), E(a, c)` is the number of vertices
+// that start a triangle).
+//
+// Several rules for the same head, separated by `;`, make a union:
+//
+//     Q(a, b, c) :- R1(a, b), E(b, c), E(a, c); Q(a, b, c) :- R2(a, b), E(b, c), E(a, c)
+//
+// Head variables line up by position, so each rule can name its own. Under
+// set semantics a binding of the head counts once however many rules produce
+// it, and under bag semantics once per result of each rule.
+//
+// A variable written `$x` is a parameter: it's given a value each time the
+// pattern is run, like `Q(b, c) :- E($x, b), E(b, c), E($x, c)` for the
+// triangles through a given vertex. A parameterized pattern is prepared once,
+// which plans it, and then counted for as many values as needed.
+
+use std::fmt;
+
+use crate::{
+    join::{Atom, InvalidQuery},
+    plan::{self, Hint, Plan, Query, Statistics},
+    relation::{Constant, Relation},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    pub name: String,
+    // The variables' names, indexed by their number in `atoms`. Variables
+    // are numbered in the order they first appear in the body.
+    pub vars: Vec<String>,
+    // The variables in the head, by number.
+    pub head: Vec<usize>,
+    // The parameters among the variables, in the order their values are
+    // given.
+    pub params: Vec<usize>,
+    pub atoms: Vec<PatternAtom>,
+    pub hint: Option<Hint>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatternAtom {
+    pub relation: String,
+    pub vars: Vec<usize>,
+    pub negated: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatternError {
+    // The text isn't a pattern, with what was expected and roughly where.
+    Syntax { at: usize, expected: &'static str },
+    // The hint comment couldn't be read.
+    Hint(String),
+    // A head variable that the body never mentions.
+    UnboundHead(String),
+    // A variable repeated within one atom, like `E(a, a)`, which the join
+    // can't bind from a single level of the atom's trie.
+    RepeatedVariable { atom: usize, var: String },
+    // A rule of a union whose head has a different name or number of
+    // variables than the first rule's.
+    HeadMismatch(usize),
+    // The wrong number of values for a pattern's parameters.
+    Parameters { expected: usize, given: usize },
+    // A value given for a parameter the pattern doesn't have.
+    UnknownParameter(String),
+    // A parameter of the pattern with no values given.
+    MissingParameter(String),
+    // Parameters given different numbers of values.
+    UnevenParameters,
+    // A variable that only appears in negated atoms, so nothing binds it.
+    OnlyNegated(String),
+    UnknownRelation(String),
+    Invalid(InvalidQuery),
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Syntax { at, expected } => {
+                write!(f, "expected {} at offset {}", expected, at)
+            }
+            PatternError::Hint(why) => write!(f, "{}", why),
+            PatternError::UnboundHead(var) => {
+                write!(f, "head variable {} does not appear in the body", var)
+            }
+            PatternError::RepeatedVariable { atom, var } => {
+                write!(f, "atom {} uses variable {} more than once", atom, var)
+            }
+            PatternError::HeadMismatch(rule) => {
+                write!(f, "rule {} has a different head from the first rule", rule)
+            }
+            PatternError::Parameters { expected, given } => {
+                write!(f, "expected {} parameter values, got {}", expected, given)
+            }
+            PatternError::UnknownParameter(name) => {
+                write!(f, "{} isn't a parameter of the query", name)
+            }
+            PatternError::MissingParameter(name) => write!(f, "no values for {}", name),
+            PatternError::UnevenParameters => {
+                write!(f, "every parameter must have as many values")
+            }
+            PatternError::OnlyNegated(var) => {
+                write!(f, "variable {} only appears in negated atoms", var)
+            }
+            PatternError::UnknownRelation(name) => write!(f, "unknown relation {}", name),
+            PatternError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// How a union combines its rules' results.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Semantics {
+    // Each distinct binding of the head once.
+    Set,
+    // Every result of every rule, duplicates and all.
+    Bag,
+}
+
+impl Semantics {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "set" => Some(Semantics::Set),
+            "bag" => Some(Semantics::Bag),
+            _ => None,
+        }
+    }
+}
+
+impl From<InvalidQuery> for PatternError {
+    fn from(e: InvalidQuery) -> Self {
+        PatternError::Invalid(e)
+    }
+}
+
+impl Pattern {
+    pub fn parse(text: &str) -> Result<Self, PatternError> {
+        // Comments (hints included) are blanked out rather than removed, so
+        // that offsets in errors still point into the original text.
+        let mut source = text.to_string();
+        let mut from = 0;
+        while let Some(start) = source[from..].find("/*").map(|i| from + i) {
+            let end =
+                source[start..]
+                    .find("*/")
+                    .map(|i| start + i + 2)
+                    .ok_or(PatternError::Syntax {
+                        at: source.len(),
+                        expected: "*/",
+                    })?;
+            source.replace_range(start..end, &" ".repeat(end - start));
+            from = end;
+        }
+        let mut parser = Parser {
+            text: &source,
+            at: 0,
+        };
+
+        let name = parser.ident("a rule name")?;
+        let head_names = parser.vars()?;
+        parser.expect(":-")?;
+        let mut vars: Vec<String> = Vec::new();
+        let mut atoms = Vec::new();
+        loop {
+            let negated = parser.eat("!") || parser.keyword("not");
+            let relation = parser.ident("a relation name")?;
+            let names = parser.vars()?;
+            let mut atom_vars = Vec::with_capacity(names.len());
+            for var in names {
+                let number = match vars.iter().position(|v| *v == var) {
+                    Some(number) => number,
+                    None => {
+                        vars.push(var.clone());
+                        vars.len() - 1
+                    }
+                };
+                if atom_vars.contains(&number) {
+                    return Err(PatternError::RepeatedVariable {
+                        atom: atoms.len(),
+                        var,
+                    });
+                }
+                atom_vars.push(number);
+            }
+            atoms.push(PatternAtom {
+                relation,
+                vars: atom_vars,
+                negated,
+            });
+            if !parser.eat(",") {
+                break;
+            }
+        }
+        parser.eat(".");
+        parser.end()?;
+
+        let mut head = Vec::with_capacity(head_names.len());
+        for var in head_names {
+            let number = vars
+                .iter()
+                .position(|v| *v == var)
+                .ok_or(PatternError::UnboundHead(var))?;
+            if !head.contains(&number) {
+                head.push(number);
+            }
+        }
+        let names: Vec<&str> = vars.iter().map(String::as_str).collect();
+        let hint = Hint::parse(text, &names)
+            .transpose()
+            .map_err(PatternError::Hint)?;
+        let params = (0..vars.len())
+            .filter(|&v| vars[v].starts_with('$'))
+            .collect();
+        Ok(Self {
+            name,
+            vars,
+            head,
+            params,
+            atoms,
+            hint,
+        })
+    }
+
+    // The pattern as a query over the named relations. A pattern with
+    // parameters has to be prepared instead.
+    pub fn query<'a>(
+        &self,
+        relations: &[(&str, &'a dyn Relation)],
+    ) -> Result<Query<'a>, PatternError> {
+        self.query_with(relations, &[])
+    }
+
+    // The pattern as a query, with each parameter fixed by an atom over the
+    // matching one of `values`.
+    fn query_with<'a>(
+        &self,
+        relations: &[(&str, &'a dyn Relation)],
+        values: &'a [Constant],
+    ) -> Result<Query<'a>, PatternError> {
+        if values.len() != self.params.len() {
+            return Err(PatternError::Parameters {
+                expected: self.params.len(),
+                given: values.len(),
+            });
+        }
+        let mut atoms: Vec<Atom<'a>> = self
+            .atoms
+            .iter()
+            .map(|atom| {
+                let relation = relations
+                    .iter()
+                    .find(|(name, _)| *name == atom.relation)
+                    .map(|(_, relation)| *relation)
+                    .ok_or_else(|| PatternError::UnknownRelation(atom.relation.clone()))?;
+                Ok(Atom {
+                    relation,
+                    vars: atom.vars.clone(),
+                    negated: atom.negated,
+                })
+            })
+            .collect::<Result<_, PatternError>>()?;
+        for (&var, value) in self.params.iter().zip(values) {
+            atoms.push(Atom::new(value, &[var]));
+        }
+        let query = Query::new(atoms, self.vars.len());
+        query.validate().map_err(|e| match e {
+            InvalidQuery::Unbound(var) => PatternError::OnlyNegated(self.vars[var].clone()),
+            e => PatternError::Invalid(e),
+        })?;
+        Ok(match &self.hint {
+            Some(hint) => query.with_hint(hint.clone()),
+            None => query,
+        })
+    }
+
+    // Count the pattern's results over the named relations: every binding
+    // if the head has all of the variables, and otherwise the distinct
+    // bindings of the head's. The plan comes back for a full count; a
+    // projection is always planned with the head's variables first.
+    pub fn count(
+        &self,
+        relations: &[(&str, &dyn Relation)],
+        stats: &mut Statistics,
+    ) -> Result<(u64, Option<Plan>), PatternError> {
+        let query = self.query(relations)?;
+        if self.counts_every_result() {
+            let (count, plan) = plan::count_adaptive(&query, stats)?;
+            Ok((count, Some(plan)))
+        } else {
+            Ok((plan::count_distinct(&query, &self.head, stats)?, None))
+        }
+    }
+
+    // Whether the head has every variable, not counting parameters, which
+    // only ever have the one value.
+    fn counts_every_result(&self) -> bool {
+        (0..self.vars.len()).all(|v| self.head.contains(&v) || self.params.contains(&v))
+    }
+
+    // The values to count the pattern with, one run at a time, from lists of
+    // values given by parameter name: each run has a value for every
+    // parameter, in the order of `params`.
+    pub fn runs(&self, given: &[(String, Vec<u64>)]) -> Result<Vec<Vec<u64>>, PatternError> {
+        if let Some((name, _)) = given
+            .iter()
+            .find(|(name, _)| !self.params.iter().any(|&p| &self.vars[p] == name))
+        {
+            return Err(PatternError::UnknownParameter(name.clone()));
+        }
+        let columns = self
+            .params
+            .iter()
+            .map(|&p| {
+                let name = &self.vars[p];
+                match given.iter().find(|(given, _)| given == name) {
+                    Some((_, values)) => Ok(&values[..]),
+                    None => Err(PatternError::MissingParameter(name.clone())),
+                }
+            })
+            .collect::<Result<Vec<&[u64]>, _>>()?;
+        let runs = columns.first().map_or(0, |c| c.len());
+        if columns.iter().any(|c| c.len() != runs) {
+            return Err(PatternError::UnevenParameters);
+        }
+        Ok((0..runs)
+            .map(|run| columns.iter().map(|c| c[run]).collect())
+            .collect())
+    }
+
+    // Plan the pattern once for counting with any values of its parameters.
+    // Each parameter is estimated to have a single value, whatever it turns
+    // out to be, so the parameters are bound first.
+    pub fn prepare(
+        &self,
+        relations: &[(&str, &dyn Relation)],
+        stats: &mut Statistics,
+    ) -> Result<Prepared, PatternError> {
+        let placeholders: Vec<Constant> = self.params.iter().map(|_| Constant::new(0)).collect();
+        let query = self.query_with(relations, &placeholders)?;
+        let (order, kept) = if self.counts_every_result() {
+            (plan::plan(&query, stats)?.order, None)
+        } else {
+            // Fixing the parameters doesn't change which bindings of the head
+            // are distinct, so they can be kept along with it.
+            let mut keep = self.params.clone();
+            keep.extend(self.head.iter().filter(|v| !self.params.contains(v)));
+            (
+                plan::distinct_order(&query, &keep, stats)?,
+                Some(keep.len()),
+            )
+        };
+        Ok(Prepared {
+            pattern: self.clone(),
+            order,
+            kept,
+        })
+    }
+}
+
+// A pattern planned once, to be counted for any values of its parameters.
+#[derive(Clone, Debug)]
+pub struct Prepared {
+    pattern: Pattern,
+    // The order to bind the variables in.
+    order: Vec<usize>,
+    // For a pattern counting distinct bindings of its head, how many of the
+    // variables at the front of `order` make up a binding.
+    kept: Option<usize>,
+}
+
+impl Prepared {
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    // Count the pattern's results with its parameters set to `values`, in
+    // the order of `Pattern::params`.
+    pub fn count(
+        &self,
+        relations: &[(&str, &dyn Relation)],
+        values: &[u64],
+    ) -> Result<u64, PatternError> {
+        let values: Vec<Constant> = values.iter().map(|&v| Constant::new(v)).collect();
+        let query = self.pattern.query_with(relations, &values)?;
+        Ok(match self.kept {
+            None => plan::count_in_order(&query, &self.order)?,
+            Some(kept) => plan::count_distinct_in_order(&query, &self.order, kept)?,
+        })
+    }
+}
+
+// One or more rules for the same head.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Union {
+    pub rules: Vec<Pattern>,
+}
+
+impl Union {
+    pub fn parse(text: &str) -> Result<Self, PatternError> {
+        let mut rules: Vec<Pattern> = Vec::new();
+        let mut start = 0;
+        for end in rule_ends(text) {
+            let rule = Pattern::parse(&text[start..end]).map_err(|e| match e {
+                PatternError::Syntax { at, expected } => PatternError::Syntax {
+                    at: start + at,
+                    expected,
+                },
+                e => e,
+            })?;
+            if let Some(first) = rules.first() {
+                if rule.name != first.name || rule.head.len() != first.head.len() {
+                    return Err(PatternError::HeadMismatch(rules.len()));
+                }
+            }
+            rules.push(rule);
+            start = end + 1;
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.rules[0].name
+    }
+
+    // Count the union's results over the named relations. A single rule is
+    // counted as `Pattern::count` does. Under set semantics, each rule's
+    // distinct head bindings are gathered up and merged, so they have to fit
+    // in memory.
+    pub fn count(
+        &self,
+        relations: &[(&str, &dyn Relation)],
+        semantics: Semantics,
+        stats: &mut Statistics,
+    ) -> Result<(u64, Option<Plan>), PatternError> {
+        match (&self.rules[..], semantics) {
+            ([rule], Semantics::Set) => rule.count(relations, stats),
+            (rules, Semantics::Bag) => {
+                let mut total = 0;
+                let mut plans = Vec::with_capacity(rules.len());
+                for rule in rules {
+                    let (count, plan) = plan::count_adaptive(&rule.query(relations)?, stats)?;
+                    total += count;
+                    plans.push(plan);
+                }
+                let plan = (plans.len() == 1).then(|| plans.pop().unwrap());
+                Ok((total, plan))
+            }
+            (rules, Semantics::Set) => {
+                let mut found = Vec::new();
+                for rule in rules {
+                    found.extend(plan::project(&rule.query(relations)?, &rule.head, stats)?);
+                }
+                found.sort_unstable();
+                found.dedup();
+                Ok((found.len() as u64, None))
+            }
+        }
+    }
+}
+
+// Where each rule of a union ends: at each `;` outside a comment, and at the
+// end of the text.
+fn rule_ends(text: &str) -> Vec<usize> {
+    let mut ends = Vec::new();
+    let mut in_comment = false;
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if in_comment {
+            if bytes[i..].starts_with(b"*/") {
+                in_comment = false;
+                i += 1;
+            }
+        } else if bytes[i..].starts_with(b"/*") {
+            in_comment = true;
+            i += 1;
+        } else if bytes[i] == b';' {
+            ends.push(i);
+        }
+        i += 1;
+    }
+    ends.push(text.len());
+    ends
+}
+
+struct Parser<'t> {
+    text: &'t str,
+    at: usize,
+}
+
+impl<'t> Parser<'t> {
+    fn skip_space(&mut self) {
+        let rest = &self.text[self.at..];
+        self.at += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, expected: &'static str) -> PatternError {
+        PatternError::Syntax {
+            at: self.at,
+            expected,
+        }
+    }
+
+    // Consume `token` if it's next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+        let found = self.text[self.at..].starts_with(token);
+        if found {
+            self.at += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &'static str) -> Result<(), PatternError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(token))
+        }
+    }
+
+    // Consume `word` if it's the next identifier.
+    fn keyword(&mut self, word: &str) -> bool {
+        let start = self.at;
+        match self.ident("") {
+            Ok(ident) if ident == word => true,
+            _ => {
+                self.at = start;
+                false
+            }
+        }
+    }
+
+    fn ident(&mut self, what: &'static str) -> Result<String, PatternError> {
+        self.skip_space();
+        let rest = &self.text[self.at..];
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(self.error(what));
+        }
+        self.at += len;
+        Ok(rest[..len].to_string())
+    }
+
+    // A parenthesized list of variable names, possibly empty.
+    fn vars(&mut self) -> Result<Vec<String>, PatternError> {
+        self.expect("(")?;
+        let mut vars = Vec::new();
+        if self.eat(")") {
+            return Ok(vars);
+        }
+        loop {
+            let param = self.eat("$");
+            let name = self.ident("a variable")?;
+            vars.push(if param { format!("${}", name) } else { name });
+            if self.eat(")") {
+                return Ok(vars);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn end(&mut self) -> Result<(), PatternError> {
+        self.skip_space();
+        if self.at == self.text.len() {
+            Ok(())
+        } else {
+            Err(self.error("the end of the pattern"))
+        }
+    }
+}
@@ -0,0 +1,371 @@
+// A binary file format for a built index, so a large graph only has to be
+// parsed and sorted once. The layout is the index in compressed sparse row
+// form, little-endian throughout, with every section starting on a 64-byte
+// boundary so that the file can be mapped into memory and read in place:
+//
+//     header   64 bytes, see below
+//     sources  u64 per first-level entry: the vertex it's for
+//     offsets  u64 per first-level entry, plus one: where each entry's
+//              neighbors start in `targets`, and where the last one ends
+//     targets  u64 per edge: the neighbors of every entry, one after another
+//
+// The header is the magic bytes, the format version, a set of feature flags,
+// the number of sources and edges, and the offset of each section.
+//
+// The header is always little-endian. The sections are too, unless the
+// BIG_ENDIAN flag is set, which a big-endian machine can ask for so that it
+// can read its own files in place. Readers convert whichever order a file is
+// in, so an index built on one architecture loads on any other.
+//
+// Versions are backward compatible: a reader understands every version up to
+// its own. A newer writer may set feature flags; those in the low half of the
+// word are optional (a reader may ignore them and still read the file
+// correctly), and those in the high half are required (a reader that doesn't
+// know one must refuse the file).
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::index::Adjacency;
+
+pub const MAGIC: [u8; 8] = *b"TRICSR\0\0";
+pub const VERSION: u32 = 1;
+pub const ALIGN: usize = 64;
+const HEADER_LEN: usize = 64;
+// The sections are big-endian. Required, since a reader that didn't know
+// about it would read garbage.
+pub const BIG_ENDIAN: u32 = 1 << 16;
+// The required flags this version knows about.
+const KNOWN_REQUIRED_FLAGS: u32 = BIG_ENDIAN;
+const REQUIRED_FLAGS: u32 = 0xffff_0000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    pub fn native() -> Self {
+        if cfg!(target_endian = "big") {
+            ByteOrder::Big
+        } else {
+            ByteOrder::Little
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "little" => Some(ByteOrder::Little),
+            "big" => Some(ByteOrder::Big),
+            "native" => Some(Self::native()),
+            _ => None,
+        }
+    }
+
+    fn encode(self, v: u64) -> [u8; 8] {
+        match self {
+            ByteOrder::Little => v.to_le_bytes(),
+            ByteOrder::Big => v.to_be_bytes(),
+        }
+    }
+
+    fn decode(self, bytes: [u8; 8]) -> u64 {
+        match self {
+            ByteOrder::Little => u64::from_le_bytes(bytes),
+            ByteOrder::Big => u64::from_be_bytes(bytes),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    NotAnIndex,
+    // Written by a newer version, or needing features this one lacks.
+    Unsupported(String),
+    Corrupt(String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(e) => write!(f, "{}", e),
+            FormatError::NotAnIndex => write!(f, "not an index file"),
+            FormatError::Unsupported(message) => write!(f, "unsupported index: {}", message),
+            FormatError::Corrupt(message) => write!(f, "corrupt index: {}", message),
+        }
+    }
+}
+
+impl From<io::Error> for FormatError {
+    fn from(e: io::Error) -> Self {
+        FormatError::Io(e)
+    }
+}
+
+struct Header {
+    version: u32,
+    flags: u32,
+    sources: u64,
+    edges: u64,
+    sources_at: u64,
+    offsets_at: u64,
+    targets_at: u64,
+}
+
+impl Header {
+    fn for_index(data: &Adjacency, order: ByteOrder) -> Self {
+        let sources = data.len() as u64;
+        let edges = data.iter().map(|(_, vs)| vs.len() as u64).sum();
+        let sources_at = HEADER_LEN as u64;
+        let offsets_at = align(sources_at + 8 * sources);
+        let targets_at = align(offsets_at + 8 * (sources + 1));
+        Self {
+            version: VERSION,
+            flags: match order {
+                ByteOrder::Little => 0,
+                ByteOrder::Big => BIG_ENDIAN,
+            },
+            sources,
+            edges,
+            sources_at,
+            offsets_at,
+            targets_at,
+        }
+    }
+
+    fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut out = [0; HEADER_LEN];
+        out[0..8].copy_from_slice(&MAGIC);
+        out[8..12].copy_from_slice(&self.version.to_le_bytes());
+        out[12..16].copy_from_slice(&self.flags.to_le_bytes());
+        for (i, field) in [
+            self.sources,
+            self.edges,
+            self.sources_at,
+            self.offsets_at,
+            self.targets_at,
+        ]
+        .iter()
+        .enumerate()
+        {
+            out[16 + 8 * i..24 + 8 * i].copy_from_slice(&field.to_le_bytes());
+        }
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, FormatError> {
+        if bytes.len() < HEADER_LEN || bytes[0..8] != MAGIC {
+            return Err(FormatError::NotAnIndex);
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        let header = Self {
+            version: u32_at(8),
+            flags: u32_at(12),
+            sources: u64_at(16),
+            edges: u64_at(24),
+            sources_at: u64_at(32),
+            offsets_at: u64_at(40),
+            targets_at: u64_at(48),
+        };
+        if header.version > VERSION {
+            return Err(FormatError::Unsupported(format!(
+                "format version {} is newer than {}",
+                header.version, VERSION
+            )));
+        }
+        let unknown = header.flags & REQUIRED_FLAGS & !KNOWN_REQUIRED_FLAGS;
+        if unknown != 0 {
+            return Err(FormatError::Unsupported(format!(
+                "requires features {:#x}",
+                unknown
+            )));
+        }
+        Ok(header)
+    }
+
+    fn byte_order(&self) -> ByteOrder {
+        if self.flags & BIG_ENDIAN != 0 {
+            ByteOrder::Big
+        } else {
+            ByteOrder::Little
+        }
+    }
+}
+
+fn align(offset: u64) -> u64 {
+    offset.div_ceil(ALIGN as u64) * ALIGN as u64
+}
+
+// Write `data` in the index format.
+pub fn write_index<W: Write>(data: &Adjacency, out: W) -> io::Result<()> {
+    write_index_in(data, out, ByteOrder::Little)
+}
+
+// Write `data` in the index format, with the sections in the given order.
+pub fn write_index_in<W: Write>(data: &Adjacency, out: W, order: ByteOrder) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    let header = Header::for_index(data, order);
+    let padding = |from: u64, to: u64| vec![0; (to - from) as usize];
+    out.write_all(&header.encode())?;
+    out.write_all(&padding(HEADER_LEN as u64, header.sources_at))?;
+    for (u, _) in data {
+        out.write_all(&order.encode(*u))?;
+    }
+    out.write_all(&padding(
+        header.sources_at + 8 * header.sources,
+        header.offsets_at,
+    ))?;
+    let mut offset = 0_u64;
+    out.write_all(&order.encode(offset))?;
+    for (_, vs) in data {
+        offset += vs.len() as u64;
+        out.write_all(&order.encode(offset))?;
+    }
+    out.write_all(&padding(
+        header.offsets_at + 8 * (header.sources + 1),
+        header.targets_at,
+    ))?;
+    for (_, vs) in data {
+        for v in vs {
+            out.write_all(&order.encode(*v))?;
+        }
+    }
+    out.flush()
+}
+
+pub fn save(data: &Adjacency, path: &Path) -> io::Result<()> {
+    save_in(data, path, ByteOrder::Little)
+}
+
+pub fn save_in(data: &Adjacency, path: &Path, order: ByteOrder) -> io::Result<()> {
+    write_index_in(data, File::create(path)?, order)
+}
+
+// A view of an index file's bytes (say, from a memory map), read in place.
+pub struct Csr<'a> {
+    order: ByteOrder,
+    sources: &'a [u8],
+    offsets: &'a [u8],
+    targets: &'a [u8],
+}
+
+impl<'a> Csr<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        let header = Header::decode(bytes)?;
+        let section = |at: u64, len: u64| -> Result<&'a [u8], FormatError> {
+            let start = at as usize;
+            let end = len
+                .checked_mul(8)
+                .and_then(|n| n.checked_add(at))
+                .map(|n| n as usize)
+                .filter(|&end| end <= bytes.len())
+                .ok_or_else(|| FormatError::Corrupt("file is truncated".to_string()))?;
+            Ok(&bytes[start..end])
+        };
+        let csr = Self {
+            order: header.byte_order(),
+            sources: section(header.sources_at, header.sources)?,
+            offsets: section(header.offsets_at, header.sources + 1)?,
+            targets: section(header.targets_at, header.edges)?,
+        };
+        let mut last = 0;
+        for i in 0..=csr.len() {
+            let o = csr.offset(i);
+            if o < last || o > header.edges {
+                return Err(FormatError::Corrupt(format!("bad offset {} at {}", o, i)));
+            }
+            last = o;
+        }
+        // Readers binary search and merge these, so out-of-order or repeated
+        // values would give wrong answers rather than an error.
+        if !increasing((0..csr.len()).map(|i| csr.source(i))) {
+            return Err(FormatError::Corrupt("sources out of order".to_string()));
+        }
+        for i in 0..csr.len() {
+            if !increasing(csr.neighbors(i)) {
+                return Err(FormatError::Corrupt(format!(
+                    "neighbors of {} out of order",
+                    csr.source(i)
+                )));
+            }
+        }
+        Ok(csr)
+    }
+
+    // The number of first-level entries.
+    pub fn len(&self) -> usize {
+        self.sources.len() / 8
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The vertex of the `i`th first-level entry.
+    pub fn source(&self, i: usize) -> u64 {
+        read_u64(self.order, self.sources, i)
+    }
+
+    // The neighbors of the `i`th first-level entry.
+    pub fn neighbors(&self, i: usize) -> impl Iterator<Item = u64> + 'a {
+        let (order, targets) = (self.order, self.targets);
+        (self.offset(i) as usize..self.offset(i + 1) as usize)
+            .map(move |j| read_u64(order, targets, j))
+    }
+
+    fn offset(&self, i: usize) -> u64 {
+        read_u64(self.order, self.offsets, i)
+    }
+
+    // The order the sections are in. If it isn't `ByteOrder::native()`,
+    // every value read is converted.
+    pub fn byte_order(&self) -> ByteOrder {
+        self.order
+    }
+
+    pub fn to_adjacency(&self) -> Adjacency {
+        (0..self.len())
+            .map(|i| (self.source(i), self.neighbors(i).collect()))
+            .collect()
+    }
+}
+
+// Whether each value is larger than the one before it.
+fn increasing(mut values: impl Iterator<Item = u64>) -> bool {
+    let mut last = None;
+    values.all(|v| {
+        let larger = last.is_none_or(|l| l < v);
+        last = Some(v);
+        larger
+    })
+}
+
+fn read_u64(order: ByteOrder, bytes: &[u8], i: usize) -> u64 {
+    order.decode(bytes[8 * i..8 * i + 8].try_into().unwrap())
+}
+
+// Read a whole index.
+pub fn read_index<R: Read>(mut reader: R) -> Result<Adjacency, FormatError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    Ok(Csr::parse(&bytes)?.to_adjacency())
+}
+
+pub fn load(path: &Path) -> Result<Adjacency, FormatError> {
+    read_index(BufReader::new(File::open(path)?))
+}
+
+// Whether the file at `path` starts like an index file.
+pub fn is_index_file(path: &Path) -> bool {
+    let mut magic = [0; 8];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok_and(|_| magic == MAGIC)
+}
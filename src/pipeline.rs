@@ -0,0 +1,144 @@
+// Loading an edge list straight into an index as a pipeline of stages on
+// their own threads, joined by bounded channels: reading lines, parsing them
+// into edges, sorting each batch of edges into a run, and merging the runs
+// into the index. Reading overlaps with the CPU work after it, and there's
+// never an unsorted copy of the whole edge list, or a second copy to sort.
+//
+//     read lines -> parse -> sort runs -> merge runs -> index
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::{
+    index::Adjacency,
+    loader::{Dialect, IndexBase, LineParser, LoadError},
+};
+
+// How many lines make up one batch passed between stages.
+pub const BATCH_LINES: usize = 1 << 16;
+// How many batches may wait between any two stages.
+pub const DEPTH: usize = 4;
+
+// Load the edge list at `path` into an index.
+pub fn load(path: &Path, dialect: &Dialect) -> Result<Adjacency, LoadError> {
+    build(BufReader::new(File::open(path)?), dialect)
+}
+
+// The same index `loader::to_adjacency(loader::read_edges(..))` would build.
+pub fn build<R: BufRead + Send>(reader: R, dialect: &Dialect) -> Result<Adjacency, LoadError> {
+    thread::scope(|scope| {
+        let (lines_tx, lines_rx) = mpsc::sync_channel::<Vec<String>>(DEPTH);
+        let (edges_tx, edges_rx) = mpsc::sync_channel::<Vec<(u64, u64)>>(DEPTH);
+        let (runs_tx, runs_rx) = mpsc::sync_channel::<Vec<(u64, u64)>>(DEPTH);
+
+        let read = scope.spawn(move || -> Result<(), LoadError> {
+            let mut batch = Vec::with_capacity(BATCH_LINES);
+            for line in reader.lines() {
+                batch.push(line?);
+                if batch.len() == BATCH_LINES {
+                    let full = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_LINES));
+                    if lines_tx.send(full).is_err() {
+                        // A later stage failed, and will report why.
+                        return Ok(());
+                    }
+                }
+            }
+            lines_tx.send(batch).ok();
+            Ok(())
+        });
+
+        let parse = scope.spawn(move || -> Result<IndexBase, LoadError> {
+            let mut parser = LineParser::new(dialect);
+            for lines in lines_rx {
+                let mut edges = Vec::with_capacity(lines.len());
+                for line in &lines {
+                    if let Some((u, v, ())) = parser.parse(line, |_| Ok(()))? {
+                        edges.push((u, v));
+                    }
+                }
+                if edges_tx.send(edges).is_err() {
+                    break;
+                }
+            }
+            Ok(parser.base())
+        });
+
+        let sort = scope.spawn(move || {
+            for mut edges in edges_rx {
+                edges.sort_unstable();
+                if runs_tx.send(edges).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let merged = merge_runs(runs_rx);
+        sort.join().unwrap();
+        let base = parse.join().unwrap()?;
+        read.join().unwrap()?;
+        Ok(to_index(merged, base))
+    })
+}
+
+// Merge sorted runs as they arrive, like a binary counter: a run is merged
+// with the one before it whenever that one is no more than twice as long,
+// which keeps the runs in decreasing order of length and the total merging
+// work at O(n log n).
+fn merge_runs(runs: Receiver<Vec<(u64, u64)>>) -> Vec<(u64, u64)> {
+    let mut stack: Vec<Vec<(u64, u64)>> = Vec::new();
+    for run in runs {
+        stack.push(run);
+        while stack.len() >= 2 && stack[stack.len() - 2].len() <= 2 * stack[stack.len() - 1].len() {
+            let b = stack.pop().unwrap();
+            let a = stack.pop().unwrap();
+            stack.push(merge(a, b));
+        }
+    }
+    stack
+        .into_iter()
+        .rev()
+        .fold(Vec::new(), |acc, run| merge(run, acc))
+}
+
+fn merge(a: Vec<(u64, u64)>, b: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    if a.is_empty() {
+        return b;
+    }
+    if b.is_empty() {
+        return a;
+    }
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut a, mut b) = (a.into_iter().peekable(), b.into_iter().peekable());
+    while let (Some(x), Some(y)) = (a.peek(), b.peek()) {
+        out.push(if x <= y { a.next() } else { b.next() }.unwrap());
+    }
+    out.extend(a);
+    out.extend(b);
+    out
+}
+
+// Group the sorted edges by source, shifting one-based ids down on the way.
+// Shifting every id by the same amount leaves them sorted, and so leaves any
+// repeats of an edge next to each other, where they're dropped as in
+// `loader::to_adjacency`.
+fn to_index(edges: Vec<(u64, u64)>, base: IndexBase) -> Adjacency {
+    let shift = (base == IndexBase::One) as u64;
+    let mut data: Adjacency = Vec::new();
+    for (u, v) in edges {
+        let (u, v) = (u - shift, v - shift);
+        match data.last_mut() {
+            Some((last, neighbors)) if *last == u => {
+                if neighbors.last() != Some(&v) {
+                    neighbors.push(v);
+                }
+            }
+            _ => data.push((u, vec![v])),
+        }
+    }
+    data
+}
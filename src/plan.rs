@@ -0,0 +1,459 @@
+// Planning and running conjunctive queries given as a whole rather than as a
+// ready-made join. The planner picks the order the join binds variables in,
+// from the cardinalities of the relations involved, and a batch of queries is
+// validated up front and planned together, so statistics are gathered once per
+// relation and a query repeated within the batch is only run once.
+//
+// Estimates can be badly off on skewed data, so the executor checks the first
+// step of a plan against them before committing to it, and plans again from
+// what it saw if the plan turns out to be much worse than expected.
+//
+// Users who know better can override the planner with a hint, either built
+// directly or parsed from a `/*+ order(a, c, b) */` comment in a query's text.
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use crate::{
+    cache::QueryKey,
+    index::Adjacency,
+    join::{self, Atom, InvalidQuery},
+    relation::{EdgeRelation, Relation},
+};
+
+// A query: a conjunction of atoms over variables `0..num_vars`. Unlike in
+// `join`, the numbering carries no meaning; the planner decides the order.
+pub struct Query<'a> {
+    pub atoms: Vec<Atom<'a>>,
+    pub num_vars: usize,
+    pub hint: Option<Hint>,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(atoms: Vec<Atom<'a>>, num_vars: usize) -> Self {
+        Self {
+            atoms,
+            num_vars,
+            hint: None,
+        }
+    }
+
+    pub fn with_hint(mut self, hint: Hint) -> Self {
+        self.hint = Some(hint);
+        self
+    }
+
+    // Check everything the join would otherwise reject.
+    pub fn validate(&self) -> Result<(), InvalidQuery> {
+        join::validate(&self.atoms, self.num_vars)
+    }
+}
+
+// Instructions to the planner. Each atom's trie always visits its columns in
+// the order their variables are bound, so choosing the variable order also
+// chooses which index (e.g. forward or reversed edges) each atom reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hint {
+    // The variables in the order to bind them.
+    pub order: Vec<usize>,
+}
+
+impl Hint {
+    // Find a `/*+ order(...) */` hint in a query's text, naming variables as
+    // listed in `vars`. Returns None if there's no hint, and an error if
+    // there's one we can't read.
+    pub fn parse(text: &str, vars: &[&str]) -> Option<Result<Self, String>> {
+        let start = text.find("/*+")? + 3;
+        let Some(len) = text[start..].find("*/") else {
+            return Some(Err("unterminated hint".to_string()));
+        };
+        let body = text[start..start + len].trim();
+        let Some(list) = body
+            .strip_prefix("order")
+            .map(str::trim_start)
+            .and_then(|s| s.strip_prefix('('))
+            .and_then(|s| s.strip_suffix(')'))
+        else {
+            return Some(Err(format!("unknown hint {:?}", body)));
+        };
+        let order: Result<Vec<usize>, String> = list
+            .split(',')
+            .map(|name| {
+                let name = name.trim();
+                vars.iter()
+                    .position(|v| *v == name)
+                    .ok_or_else(|| format!("hint names unknown variable {:?}", name))
+            })
+            .collect();
+        Some(order.map(|order| Hint { order }))
+    }
+}
+
+// What the planner decided for a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    // The variables in the order to bind them.
+    pub order: Vec<usize>,
+    // If the query had a hint, whether it was followed, or why not.
+    pub hint: Option<Result<(), String>>,
+    // Whether the first plan was abandoned after its estimates proved wrong.
+    pub replanned: bool,
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "order {:?}", self.order)?;
+        match &self.hint {
+            Some(Ok(())) => write!(f, " (hint honored)"),
+            Some(Err(why)) => write!(f, " (hint ignored: {})", why),
+            None => Ok(()),
+        }?;
+        if self.replanned {
+            write!(f, " (re-planned after a misestimate)")?;
+        }
+        Ok(())
+    }
+}
+
+// A query in a batch that can't be run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    // The query's position in the batch.
+    pub query: usize,
+    pub error: InvalidQuery,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "query {}: {}", self.query, self.error)
+    }
+}
+
+// Statistics about the relations a set of queries read, each gathered the
+// first time it's asked for. Relations are told apart by address, since
+// they're borrowed for as long as the queries are.
+#[derive(Default)]
+pub struct Statistics {
+    cardinalities: HashMap<usize, usize>,
+    distinct: HashMap<(usize, usize), usize>,
+}
+
+impl Statistics {
+    pub fn cardinality(&mut self, relation: &dyn Relation) -> usize {
+        *self
+            .cardinalities
+            .entry(address(relation))
+            .or_insert_with(|| relation.cardinality())
+    }
+
+    // The number of distinct values in one of the relation's columns, found
+    // by walking the first level of a trie with that column first.
+    pub fn distinct(&mut self, relation: &dyn Relation, column: usize) -> usize {
+        *self
+            .distinct
+            .entry((address(relation), column))
+            .or_insert_with(|| {
+                let mut order = vec![column];
+                order.extend((0..relation.arity()).filter(|&c| c != column));
+                let mut it = relation.trie_iter(&order);
+                let mut n = 0;
+                while it.value().is_some() {
+                    n += 1;
+                    it.next();
+                }
+                n
+            })
+    }
+}
+
+fn address(relation: &dyn Relation) -> usize {
+    relation as *const dyn Relation as *const () as usize
+}
+
+// The order to bind the query's variables in: `order[i]` is the variable to
+// bind `i`th. Each variable is estimated by its smallest positive atom, since
+// a binding has to be in all of them. We start from the smallest, and then
+// keep taking the smallest of those sharing an atom with one already bound,
+// so that every level is narrowed by the ones above it.
+pub fn order(query: &Query, stats: &mut Statistics) -> Result<Vec<usize>, InvalidQuery> {
+    query.validate()?;
+    Ok(order_avoiding(query, stats, &[]))
+}
+
+// `order`, but never starting with any of the variables in `avoid` (unless
+// that's all there is).
+fn order_avoiding(query: &Query, stats: &mut Statistics, avoid: &[usize]) -> Vec<usize> {
+    let mut estimate = vec![usize::MAX; query.num_vars];
+    for atom in query.atoms.iter().filter(|a| !a.negated) {
+        let size = stats.cardinality(atom.relation);
+        for &var in &atom.vars {
+            estimate[var] = estimate[var].min(size);
+        }
+    }
+    let mut order = Vec::with_capacity(query.num_vars);
+    let mut bound = vec![false; query.num_vars];
+    while order.len() < query.num_vars {
+        let connected = |var: usize| {
+            query
+                .atoms
+                .iter()
+                .any(|a| !a.negated && a.vars.contains(&var) && a.vars.iter().any(|&v| bound[v]))
+        };
+        let next = (0..query.num_vars)
+            .filter(|&v| !bound[v])
+            .min_by_key(|&v| {
+                let avoided = order.is_empty() && avoid.contains(&v);
+                (avoided, !connected(v), estimate[v], v)
+            })
+            .unwrap();
+        bound[next] = true;
+        order.push(next);
+    }
+    order
+}
+
+// Plan the query: follow its hint if it has a usable one, and otherwise pick
+// the order ourselves.
+pub fn plan(query: &Query, stats: &mut Statistics) -> Result<Plan, InvalidQuery> {
+    query.validate()?;
+    let hint = query.hint.as_ref().map(|hint| {
+        let mut seen = vec![false; query.num_vars];
+        for &var in &hint.order {
+            if var >= query.num_vars || std::mem::replace(&mut seen[var], true) {
+                return Err(format!(
+                    "{:?} is not an ordering of the variables",
+                    hint.order
+                ));
+            }
+        }
+        if hint.order.len() != query.num_vars {
+            return Err(format!("{:?} leaves out some variables", hint.order));
+        }
+        Ok(())
+    });
+    let order = match (&query.hint, &hint) {
+        (Some(h), Some(Ok(()))) => h.order.clone(),
+        _ => order_avoiding(query, stats, &[]),
+    };
+    Ok(Plan {
+        order,
+        hint,
+        replanned: false,
+    })
+}
+
+// How many times more intermediate results than estimated the first step of a
+// plan may produce before we give up on it.
+pub const REPLAN_FACTOR: f64 = 100.0;
+
+// Count the query's results like `count_in_order`, but first check the plan's
+// estimate of the intermediate result once its first two variables are bound
+// (the most any level below can be narrowed from) against the join itself.
+// If the actual size passes `REPLAN_FACTOR` times the estimate, we stop
+// looking and plan again without starting from the same variable. This
+// happens at most once, and the probe is cut off at the limit, so a bad
+// estimate costs at most that much extra work. Hinted plans are run as given.
+pub fn count_adaptive(query: &Query, stats: &mut Statistics) -> Result<(u64, Plan), InvalidQuery> {
+    let mut plan = plan(query, stats)?;
+    if query.num_vars >= 2 && !matches!(plan.hint, Some(Ok(()))) {
+        let estimate = estimate_pair(query, plan.order[0], plan.order[1], stats);
+        let limit = (estimate * REPLAN_FACTOR).max(1.0).min(u64::MAX as f64) as u64;
+        let atoms = renumber(query, &plan.order);
+        if join::count_prefix(&atoms, query.num_vars, 2, limit)? >= limit {
+            let first = plan.order[0];
+            plan.order = order_avoiding(query, stats, &[first]);
+            plan.replanned = plan.order[0] != first;
+        }
+    }
+    Ok((count_in_order(query, &plan.order)?, plan))
+}
+
+// The estimated number of bindings of `a` followed by `b`: the distinct values
+// of `a`, times how many values of `b` each has on average in the tightest
+// atom relating them (or all of `b`'s values, if none does), assuming the
+// atoms are independent.
+fn estimate_pair(query: &Query, a: usize, b: usize, stats: &mut Statistics) -> f64 {
+    let mut first = f64::INFINITY;
+    let mut second = f64::INFINITY;
+    let mut fanout = f64::INFINITY;
+    for atom in query.atoms.iter().filter(|atom| !atom.negated) {
+        let col_a = atom.vars.iter().position(|&v| v == a);
+        let col_b = atom.vars.iter().position(|&v| v == b);
+        if let Some(col) = col_a {
+            first = first.min(stats.distinct(atom.relation, col) as f64);
+        }
+        if let Some(col) = col_b {
+            second = second.min(stats.distinct(atom.relation, col) as f64);
+        }
+        if let (Some(col), Some(_)) = (col_a, col_b) {
+            let values = stats.distinct(atom.relation, col).max(1) as f64;
+            fanout = fanout.min(stats.cardinality(atom.relation) as f64 / values);
+        }
+    }
+    first * fanout.min(second)
+}
+
+// The number of distinct bindings of the `keep` variables among the query's
+// results, e.g. how many vertices are in any triangle. The kept variables are
+// bound first (in the planner's order among themselves) so that the join can
+// move on from each as soon as it finds one result for it.
+pub fn count_distinct(
+    query: &Query,
+    keep: &[usize],
+    stats: &mut Statistics,
+) -> Result<u64, InvalidQuery> {
+    let order = distinct_order(query, keep, stats)?;
+    let kept = order.iter().filter(|v| keep.contains(v)).count();
+    count_distinct_in_order(query, &order, kept)
+}
+
+// The planner's order for the query, with the `keep` variables moved to the
+// front, as `count_distinct` and `project` bind them.
+pub fn distinct_order(
+    query: &Query,
+    keep: &[usize],
+    stats: &mut Statistics,
+) -> Result<Vec<usize>, InvalidQuery> {
+    join::known(keep, query.num_vars)?;
+    let planned = order(query, stats)?;
+    let (mut order, rest): (Vec<usize>, Vec<usize>) =
+        planned.into_iter().partition(|v| keep.contains(v));
+    order.extend(rest);
+    Ok(order)
+}
+
+// The number of distinct bindings of the first `kept` variables of `order`,
+// binding the query's variables in that order.
+pub fn count_distinct_in_order(
+    query: &Query,
+    order: &[usize],
+    kept: usize,
+) -> Result<u64, InvalidQuery> {
+    check_order(query, order)?;
+    join::count_distinct(
+        &renumber(query, order),
+        query.num_vars,
+        &(0..kept).collect::<Vec<_>>(),
+    )
+}
+
+// The distinct bindings of the `keep` variables among the query's results,
+// each listing the variables' values in the order of `keep`, in ascending
+// order. As in `count_distinct`, the kept variables are bound first.
+pub fn project(
+    query: &Query,
+    keep: &[usize],
+    stats: &mut Statistics,
+) -> Result<Vec<Vec<u64>>, InvalidQuery> {
+    let order = distinct_order(query, keep, stats)?;
+    let kept = order.iter().filter(|v| keep.contains(v)).count();
+    let found = join::project(
+        &renumber(query, &order),
+        query.num_vars,
+        &(0..kept).collect::<Vec<_>>(),
+    )?;
+    // Put the columns back in the order they were asked for.
+    let columns: Vec<usize> = keep
+        .iter()
+        .map(|v| order.iter().position(|o| o == v).unwrap())
+        .collect();
+    let mut found: Vec<Vec<u64>> = found
+        .into_iter()
+        .map(|binding| columns.iter().map(|&c| binding[c]).collect())
+        .collect();
+    found.sort_unstable();
+    Ok(found)
+}
+
+// The vertices of a graph in at least one triangle, in ascending order, and
+// how many are in one as each of a, b and c.
+pub struct Participation {
+    pub vertices: Vec<u64>,
+    pub roles: [usize; 3],
+}
+
+// Which vertices of the graph are in some triangle. A vertex is if it's in
+// one in any of the three roles, so the answer is the union of each role's
+// distinct values.
+pub fn participating(data: &Arc<Adjacency>) -> Participation {
+    let edges = EdgeRelation::new(data.clone());
+    let query = Query::new(
+        vec![
+            Atom::new(&edges, &[0, 1]),
+            Atom::new(&edges, &[1, 2]),
+            Atom::new(&edges, &[0, 2]),
+        ],
+        3,
+    );
+    let mut stats = Statistics::default();
+    let mut vertices: Vec<u64> = Vec::new();
+    let mut roles = [0; 3];
+    for (v, role) in roles.iter_mut().enumerate() {
+        let found = project(&query, &[v], &mut stats).expect("the triangle query is valid");
+        *role = found.len();
+        vertices.extend(found.into_iter().map(|binding| binding[0]));
+    }
+    vertices.sort_unstable();
+    vertices.dedup();
+    Participation { vertices, roles }
+}
+
+// Count the query's results, binding its variables in the given order.
+pub fn count_in_order(query: &Query, order: &[usize]) -> Result<u64, InvalidQuery> {
+    check_order(query, order)?;
+    join::count(&renumber(query, order), query.num_vars)
+}
+
+// Check that `order` is a permutation of the query's variables.
+fn check_order(query: &Query, order: &[usize]) -> Result<(), InvalidQuery> {
+    let mut sorted = order.to_vec();
+    sorted.sort_unstable();
+    if !sorted.iter().copied().eq(0..query.num_vars) {
+        return Err(InvalidQuery::Order(order.to_vec()));
+    }
+    Ok(())
+}
+
+// The query's atoms with the variables numbered by their place in `order`,
+// as the join wants them.
+fn renumber<'a>(query: &Query<'a>, order: &[usize]) -> Vec<Atom<'a>> {
+    let mut position = vec![0; query.num_vars];
+    for (i, &var) in order.iter().enumerate() {
+        position[var] = i;
+    }
+    query
+        .atoms
+        .iter()
+        .map(|a| Atom {
+            relation: a.relation,
+            vars: a.vars.iter().map(|&v| position[v]).collect(),
+            negated: a.negated,
+        })
+        .collect()
+}
+
+// Count the results of each of the queries, in the same order. Nothing is run
+// unless every query is valid.
+pub fn run_batch(queries: &[Query]) -> Result<Vec<u64>, QueryError> {
+    for (i, query) in queries.iter().enumerate() {
+        query
+            .validate()
+            .map_err(|error| QueryError { query: i, error })?;
+    }
+    let mut stats = Statistics::default();
+    let mut done: HashMap<QueryKey, u64> = HashMap::new();
+    let mut counts = Vec::with_capacity(queries.len());
+    for (i, query) in queries.iter().enumerate() {
+        let key = QueryKey::new(&query.atoms, query.num_vars);
+        if let Some(count) = key.as_ref().and_then(|k| done.get(k)) {
+            counts.push(*count);
+            continue;
+        }
+        let (count, _) =
+            count_adaptive(query, &mut stats).map_err(|error| QueryError { query: i, error })?;
+        if let Some(key) = key {
+            done.insert(key, count);
+        }
+        counts.push(count);
+    }
+    Ok(counts)
+}
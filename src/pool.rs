@@ -0,0 +1,237 @@
+// Worker threads and scratch space kept between counts, for callers that
+// count over and over in one process (a server, say). Spawning a thread per
+// worker and allocating per-thread count arrays costs little next to one big
+// count, but it adds up over many small ones. Here the workers live as long
+// as the pool, and count arrays go back to the pool when a count is done, to
+// be reused by the next one.
+//
+// Jobs own what they work on (the graph comes in an `Arc`), so a worker never
+// borrows from a caller that might have gone away. Several callers can share
+// a pool: each count splits its work with `parallel`'s work-stealing ranges,
+// so it finishes even if some of its jobs are queued behind another count's.
+
+use std::{
+    fmt, io,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex, MutexGuard, OnceLock, PoisonError},
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    index::{self, Adjacency},
+    parallel::{self, Ranges, ATOMIC_THRESHOLD},
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+pub struct Pool {
+    workers: Mutex<Vec<Worker>>,
+    // Per-vertex count arrays not in use by any count. They keep the size of
+    // the largest graph they've been used for until `trim` is called.
+    scratch: Mutex<Vec<Vec<u64>>>,
+}
+
+struct Worker {
+    jobs: mpsc::Sender<Job>,
+    handle: JoinHandle<()>,
+}
+
+impl Worker {
+    fn spawn() -> io::Result<Self> {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let handle = thread::Builder::new().spawn(move || {
+            for job in queue {
+                job();
+            }
+        })?;
+        Ok(Self { jobs, handle })
+    }
+}
+
+// Why a pool couldn't run a batch of jobs.
+#[derive(Debug)]
+pub enum PoolError {
+    // The system wouldn't start another worker thread.
+    Spawn(io::Error),
+    // A worker went away without finishing its job. Jobs catch their own
+    // panics, so this means its thread died some other way.
+    Stopped,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::Spawn(e) => write!(f, "couldn't start a worker thread: {}", e),
+            PoolError::Stopped => write!(f, "a pool worker stopped before finishing its job"),
+        }
+    }
+}
+
+// A pool shared by the whole process, started on first use.
+pub fn global() -> &'static Pool {
+    static GLOBAL: OnceLock<Pool> = OnceLock::new();
+    GLOBAL.get_or_init(Pool::new)
+}
+
+impl Default for Pool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pool {
+    // A pool with no workers yet; they're started as counts ask for them.
+    pub fn new() -> Self {
+        Self {
+            workers: Mutex::new(Vec::new()),
+            scratch: Mutex::new(Vec::new()),
+        }
+    }
+
+    // How many workers have been started.
+    pub fn threads(&self) -> usize {
+        lock(&self.workers).len()
+    }
+
+    // Free the count arrays kept for reuse.
+    pub fn trim(&self) {
+        lock(&self.scratch).clear();
+    }
+
+    // Run each of `jobs` on a worker of its own, starting workers if there
+    // aren't enough, and return their results in order. A panic in a job is
+    // passed on to the caller once every job has finished.
+    pub fn run<T, F>(&self, jobs: Vec<F>) -> Result<Vec<T>, PoolError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let receivers = {
+            let mut workers = lock(&self.workers);
+            while workers.len() < jobs.len() {
+                workers.push(Worker::spawn().map_err(PoolError::Spawn)?);
+            }
+            jobs.into_iter()
+                .zip(workers.iter())
+                .map(|(job, worker)| {
+                    let (send, receive) = mpsc::sync_channel(1);
+                    let job: Job = Box::new(move || {
+                        // The caller waits for every job, so this only fails if
+                        // the caller has itself panicked.
+                        let _ = send.send(panic::catch_unwind(AssertUnwindSafe(job)));
+                    });
+                    worker.jobs.send(job).map_err(|_| PoolError::Stopped)?;
+                    Ok(receive)
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let results: Vec<_> = receivers.into_iter().map(|r| r.recv()).collect();
+        results
+            .into_iter()
+            .map(|r| match r {
+                Ok(Ok(result)) => Ok(result),
+                Ok(Err(payload)) => panic::resume_unwind(payload),
+                Err(_) => Err(PoolError::Stopped),
+            })
+            .collect()
+    }
+
+    // `parallel::count_triangles` on this pool's workers.
+    pub fn count_triangles(&self, data: &Arc<Adjacency>, threads: usize) -> Result<u64, PoolError> {
+        let threads = threads.max(1);
+        let work = Arc::new(Ranges::new(data.len(), threads));
+        let jobs = (0..threads)
+            .map(|me| {
+                let (data, work) = (data.clone(), work.clone());
+                move || parallel::count_share(&data, &work, me)
+            })
+            .collect();
+        Ok(self.run(jobs)?.into_iter().sum())
+    }
+
+    // `parallel::triangles_per_vertex` on this pool's workers, counting into
+    // arrays kept from earlier calls. Past `ATOMIC_THRESHOLD` there'd be no
+    // per-thread arrays to keep, so that case is left to `parallel`.
+    pub fn triangles_per_vertex(
+        &self,
+        data: &Arc<Adjacency>,
+        threads: usize,
+    ) -> Result<Vec<(u64, u64)>, PoolError> {
+        Ok(match index::compact(data) {
+            Some((compacted, original)) => {
+                let counts = self.count_per_vertex(&Arc::new(compacted), threads)?;
+                parallel::in_triangles(counts, &original)
+            }
+            None => parallel::in_triangles(self.count_per_vertex(data, threads)?, &[]),
+        })
+    }
+
+    // The per-vertex counts, indexed by id.
+    fn count_per_vertex(
+        &self,
+        data: &Arc<Adjacency>,
+        threads: usize,
+    ) -> Result<Vec<u64>, PoolError> {
+        let threads = threads.max(1);
+        let universe = parallel::universe(data);
+        if parallel::array_bytes(universe, threads) > ATOMIC_THRESHOLD {
+            return Ok(parallel::count_per_vertex(data, threads));
+        }
+        let arrays: Vec<Vec<u64>> = {
+            let mut scratch = lock(&self.scratch);
+            let kept = scratch.len().saturating_sub(threads);
+            let mut arrays = scratch.split_off(kept);
+            arrays.resize_with(threads, Vec::new);
+            arrays
+        };
+        let work = Arc::new(Ranges::new(data.len(), threads));
+        let jobs = arrays
+            .into_iter()
+            .enumerate()
+            .map(|(me, mut counts)| {
+                let (data, work) = (data.clone(), work.clone());
+                move || {
+                    counts.clear();
+                    counts.resize(universe, 0);
+                    parallel::vertex_share(&data, &work, me, &mut |u| counts[u as usize] += 1);
+                    counts
+                }
+            })
+            .collect();
+        let arrays = self.run(jobs)?;
+        let mut total = vec![0_u64; universe];
+        for counts in &arrays {
+            for (t, c) in total.iter_mut().zip(counts) {
+                *t += c;
+            }
+        }
+        lock(&self.scratch).extend(arrays);
+        Ok(total)
+    }
+}
+
+impl Drop for Pool {
+    // Closing each worker's queue lets it finish what it has and stop.
+    fn drop(&mut self) {
+        let workers = std::mem::take(
+            self.workers
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        let handles: Vec<_> = workers
+            .into_iter()
+            .map(|Worker { jobs, handle }| {
+                drop(jobs);
+                handle
+            })
+            .collect();
+        for handle in handles {
+            // Jobs catch their own panics, so workers don't.
+            let _ = handle.join();
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
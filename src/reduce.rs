@@ -0,0 +1,48 @@
+// Shrinking a failing input to a minimal one by delta debugging (Zeller and
+// Hildebrandt's ddmin): repeatedly try smaller subsets, and their
+// complements, keeping any that still fail. The result fails, and removing
+// any single item from it makes it pass.
+
+// Reduce `items`, which must fail the `fails` test, to a minimal subset that
+// still does.
+pub fn reduce<T: Clone>(mut items: Vec<T>, mut fails: impl FnMut(&[T]) -> bool) -> Vec<T> {
+    let mut n = 2;
+    while items.len() >= 2 {
+        let chunk = items.len().div_ceil(n);
+        let chunks: Vec<&[T]> = items.chunks(chunk).collect();
+        let mut reduced = None;
+        // First look for a single chunk that fails by itself.
+        for c in &chunks {
+            if fails(c) {
+                reduced = Some((c.to_vec(), 2));
+                break;
+            }
+        }
+        // Then for a chunk we can leave out.
+        if reduced.is_none() && chunks.len() > 2 {
+            for skip in 0..chunks.len() {
+                let rest: Vec<T> = chunks
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i != skip)
+                    .flat_map(|(_, c)| c.iter().cloned())
+                    .collect();
+                if fails(&rest) {
+                    reduced = Some((rest, (n - 1).max(2)));
+                    break;
+                }
+            }
+        }
+        match reduced {
+            Some((smaller, next)) => {
+                items = smaller;
+                n = next;
+            }
+            // Neither worked at this granularity, so try a finer one, until
+            // we're down to single items.
+            None if n < items.len() => n = (2 * n).min(items.len()),
+            None => break,
+        }
+    }
+    items
+}
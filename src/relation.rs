@@ -0,0 +1,268 @@
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::HashMap,
+    sync::Arc,
+};
+
+use crate::{
+    index::{mix, Adjacency, Csr, Index, TrieIterator},
+    loader,
+    trie::Trie,
+};
+
+// Anything the join engine can read tuples from. A relation only has to be
+// able to hand out trie iterators over its tuples with the columns visited in
+// a requested order; how it stores them (in memory, in a database, computed on
+// the fly) is up to the implementation.
+pub trait Relation {
+    // The names of the columns, in their natural order. The arity of the
+    // relation is the length of the schema.
+    fn schema(&self) -> &[String];
+
+    // The number of tuples in the relation.
+    fn cardinality(&self) -> usize;
+
+    // A trie iterator over the tuples whose levels are the columns listed in
+    // `order`, which must be a permutation of `0..arity`. The iterator starts
+    // at the beginning of the first level.
+    fn trie_iter(&self, order: &[usize]) -> Box<dyn TrieIterator + '_>;
+
+    fn arity(&self) -> usize {
+        self.schema().len()
+    }
+
+    // A hash of the relation's contents, if it can provide one, such that two
+    // relations with the same tuples have the same content hash. Results of
+    // queries over relations without one are never cached.
+    fn content_hash(&self) -> Option<u64> {
+        None
+    }
+}
+
+// A binary relation stored as the sorted two-level index. The reversed copy
+// needed to iterate destination-first is only built if someone asks for it.
+pub struct EdgeRelation {
+    schema: Vec<String>,
+    forward: Arc<Csr>,
+    reverse: OnceCell<Arc<Csr>>,
+    hash: OnceCell<u64>,
+    cardinality: usize,
+}
+
+impl EdgeRelation {
+    pub fn new(data: Arc<Adjacency>) -> Self {
+        let cardinality = data.iter().map(|(_, vs)| vs.len()).sum();
+        Self {
+            schema: vec!["src".to_string(), "dst".to_string()],
+            forward: Arc::new(Csr::from(&*data)),
+            reverse: OnceCell::new(),
+            hash: OnceCell::new(),
+            cardinality,
+        }
+    }
+
+    // Rename the two columns, e.g. to match the attributes of a query.
+    pub fn with_schema(mut self, src: &str, dst: &str) -> Self {
+        self.schema = vec![src.to_string(), dst.to_string()];
+        self
+    }
+
+    fn reverse(&self) -> Arc<Csr> {
+        self.reverse
+            .get_or_init(|| {
+                let edges = self
+                    .forward
+                    .iter()
+                    .flat_map(|(u, vs)| vs.iter().map(move |v| (*v, u)))
+                    .collect();
+                Arc::new(Csr::from(&loader::to_adjacency(edges)))
+            })
+            .clone()
+    }
+}
+
+impl Relation for EdgeRelation {
+    fn schema(&self) -> &[String] {
+        &self.schema
+    }
+
+    fn cardinality(&self) -> usize {
+        self.cardinality
+    }
+
+    fn content_hash(&self) -> Option<u64> {
+        Some(
+            *self
+                .hash
+                .get_or_init(|| Index::new(self.forward.clone()).fingerprint()),
+        )
+    }
+
+    fn trie_iter(&self, order: &[usize]) -> Box<dyn TrieIterator + '_> {
+        match order {
+            [0, 1] => Box::new(Index::new(self.forward.clone())),
+            [1, 0] => Box::new(Index::new(self.reverse())),
+            _ => panic!("invalid column order {:?} for a binary relation", order),
+        }
+    }
+}
+
+// A relation of any arity stored as a `Trie`. A trie with its columns in
+// some other order is built the first time the join asks for that order, and
+// kept for the next.
+pub struct TupleRelation {
+    schema: Vec<String>,
+    tries: RefCell<HashMap<Vec<usize>, Arc<Trie>>>,
+    hash: OnceCell<u64>,
+    cardinality: usize,
+}
+
+impl TupleRelation {
+    // A relation over `tuples`, each with `arity` values, with duplicates
+    // dropped. Its columns are named c0, c1 and so on.
+    pub fn new(arity: usize, tuples: Vec<Vec<u64>>) -> Self {
+        let trie = Trie::new(arity, tuples);
+        let cardinality = trie.len();
+        let natural: Vec<usize> = (0..arity).collect();
+        Self {
+            schema: (0..arity).map(|c| format!("c{}", c)).collect(),
+            tries: RefCell::new(HashMap::from([(natural, Arc::new(trie))])),
+            hash: OnceCell::new(),
+            cardinality,
+        }
+    }
+
+    // Rename the columns, e.g. to match the attributes of a query.
+    pub fn with_schema(mut self, schema: &[&str]) -> Self {
+        assert_eq!(
+            schema.len(),
+            self.arity(),
+            "a schema must name every column"
+        );
+        self.schema = schema.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    // The trie with its levels in `order`.
+    fn trie(&self, order: &[usize]) -> Arc<Trie> {
+        if let Some(trie) = self.tries.borrow().get(order) {
+            return trie.clone();
+        }
+        let natural: Vec<usize> = (0..self.arity()).collect();
+        let tuples = self.tries.borrow()[&natural]
+            .tuples()
+            .into_iter()
+            .map(|t| order.iter().map(|&c| t[c]).collect())
+            .collect();
+        let trie = Arc::new(Trie::new(order.len(), tuples));
+        self.tries.borrow_mut().insert(order.to_vec(), trie.clone());
+        trie
+    }
+}
+
+impl Relation for TupleRelation {
+    fn schema(&self) -> &[String] {
+        &self.schema
+    }
+
+    fn cardinality(&self) -> usize {
+        self.cardinality
+    }
+
+    // The wrapping sum of a hash of each tuple, chained through its values
+    // the way `index::fingerprint` hashes an edge, so that a binary relation
+    // has the same hash as an `EdgeRelation` with the same edges.
+    fn content_hash(&self) -> Option<u64> {
+        Some(*self.hash.get_or_init(|| {
+            let natural: Vec<usize> = (0..self.arity()).collect();
+            self.trie(&natural)
+                .tuples()
+                .iter()
+                .map(|t| t.iter().fold(0, |h, &v| mix(h ^ v)))
+                .fold(0, u64::wrapping_add)
+        }))
+    }
+
+    fn trie_iter(&self, order: &[usize]) -> Box<dyn TrieIterator + '_> {
+        let mut columns = order.to_vec();
+        columns.sort_unstable();
+        if !columns.iter().copied().eq(0..self.arity()) {
+            panic!(
+                "invalid column order {:?} for a relation of arity {}",
+                order,
+                self.arity()
+            );
+        }
+        Box::new(self.trie(order).iter())
+    }
+}
+
+// A unary relation holding a single value, for fixing a variable of a query
+// to a constant: an atom over it lets only that value through, and its
+// cardinality of one has the planner bind the variable first.
+pub struct Constant {
+    schema: Vec<String>,
+    value: u64,
+}
+
+impl Constant {
+    pub fn new(value: u64) -> Self {
+        Self {
+            schema: vec!["value".to_string()],
+            value,
+        }
+    }
+}
+
+impl Relation for Constant {
+    fn schema(&self) -> &[String] {
+        &self.schema
+    }
+
+    fn cardinality(&self) -> usize {
+        1
+    }
+
+    fn trie_iter(&self, order: &[usize]) -> Box<dyn TrieIterator + '_> {
+        match order {
+            [0] => Box::new(ConstantIterator {
+                value: [self.value],
+                done: false,
+            }),
+            _ => panic!("invalid column order {:?} for a unary relation", order),
+        }
+    }
+}
+
+// The one level of a `Constant`: its value, then the end.
+struct ConstantIterator {
+    value: [u64; 1],
+    done: bool,
+}
+
+impl TrieIterator for ConstantIterator {
+    fn seek(&mut self, v: u64) {
+        self.done |= v > self.value[0];
+    }
+
+    // There's only the one level.
+    fn up(&mut self) {}
+
+    fn down(&mut self) {}
+
+    fn reset(&mut self) {
+        self.done = false;
+    }
+
+    fn value(&self) -> Option<u64> {
+        (!self.done).then_some(self.value[0])
+    }
+
+    fn next(&mut self) {
+        self.done = true;
+    }
+
+    fn rest(&self) -> Option<&[u64]> {
+        Some(if self.done { &[] } else { &self.value })
+    }
+}
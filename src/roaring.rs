@@ -0,0 +1,417 @@
+// A backend for dense graphs, with each neighbor set stored as a Roaring
+// bitmap: the ids are split into chunks of 2^16 by their high bits, and each
+// chunk that has any ids in it gets a container of its own, either a sorted
+// array of the low 16 bits when there are only a few of them, or a bitset
+// over the whole chunk when there are more than `ARRAY_MAX`, at which point
+// the bitset is the smaller of the two.
+//
+// The point is the innermost intersection. Two bitset containers intersect
+// with a word-by-word AND and a popcount, 64 ids at a time, and a bitset
+// against an array with a probe per array value, where a merge would walk
+// both. `RoaringGraph::count_triangles` counts that way, only ever taking
+// the size of each intersection; the trie iterator is there so that the
+// other loop nests can run on the backend too.
+
+use std::sync::Arc;
+
+use crate::index::{gallop, Adjacency, TrieIterator};
+
+// Above this many values, a container is stored as a bitset: 4096 u16s take
+// the same 8KB as a bitset of the 2^16 ids in a chunk.
+const ARRAY_MAX: usize = 4096;
+const BITSET_WORDS: usize = (1 << 16) / 64;
+
+// The average degree above which `--backend auto` picks this backend rather
+// than the flat one. Below it, few chunks have enough neighbors in them to
+// be stored as bitsets, and on sparse graphs building the sets costs more
+// than the smaller arrays save.
+pub const MIN_AVG_DEGREE: f64 = 1024.0;
+
+#[derive(Clone, Debug)]
+enum Container {
+    Array(Vec<u16>),
+    Bitset(Box<[u64; BITSET_WORDS]>),
+}
+
+impl Container {
+    fn from_sorted(lows: &[u16]) -> Self {
+        if lows.len() <= ARRAY_MAX {
+            return Container::Array(lows.to_vec());
+        }
+        let mut words = Box::new([0_u64; BITSET_WORDS]);
+        for &low in lows {
+            words[low as usize / 64] |= 1 << (low % 64);
+        }
+        Container::Bitset(words)
+    }
+
+    // The position of the first value at or after position `from` which is
+    // at least `low`. Positions are indexes into an array, and the values
+    // themselves in a bitset.
+    fn find(&self, from: usize, low: u32) -> Option<usize> {
+        match self {
+            Container::Array(values) => {
+                let at =
+                    from + values[from.min(values.len())..].partition_point(|&x| (x as u32) < low);
+                (at < values.len()).then_some(at)
+            }
+            Container::Bitset(words) => {
+                let at = from.max(low as usize);
+                if at >= 1 << 16 {
+                    return None;
+                }
+                let mut word = at / 64;
+                let mut bits = words[word] >> (at % 64) << (at % 64);
+                while bits == 0 {
+                    word += 1;
+                    if word == BITSET_WORDS {
+                        return None;
+                    }
+                    bits = words[word];
+                }
+                Some(word * 64 + bits.trailing_zeros() as usize)
+            }
+        }
+    }
+
+    fn low(&self, at: usize) -> u16 {
+        match self {
+            Container::Array(values) => values[at],
+            Container::Bitset(_) => at as u16,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Container::Array(values) => values.len(),
+            Container::Bitset(words) => words.iter().map(|w| w.count_ones() as usize).sum(),
+        }
+    }
+
+    fn contains(&self, low: u16) -> bool {
+        match self {
+            Container::Array(values) => values.binary_search(&low).is_ok(),
+            Container::Bitset(words) => words[low as usize / 64] >> (low % 64) & 1 == 1,
+        }
+    }
+
+    fn and_len(&self, other: &Container) -> u64 {
+        match (self, other) {
+            (Container::Bitset(a), Container::Bitset(b)) => a
+                .iter()
+                .zip(b.iter())
+                .map(|(x, y)| (x & y).count_ones() as u64)
+                .sum(),
+            (Container::Array(values), bitset @ Container::Bitset(_))
+            | (bitset @ Container::Bitset(_), Container::Array(values)) => {
+                values.iter().filter(|&&low| bitset.contains(low)).count() as u64
+            }
+            (Container::Array(a), Container::Array(b)) => array_and_len(a, b),
+        }
+    }
+
+    fn heap_size(&self) -> usize {
+        match self {
+            Container::Array(values) => values.capacity() * std::mem::size_of::<u16>(),
+            Container::Bitset(_) => BITSET_WORDS * std::mem::size_of::<u64>(),
+        }
+    }
+}
+
+// Merge two sorted arrays, or when one is much shorter, look each of its
+// values up in the other.
+fn array_and_len(a: &[u16], b: &[u16]) -> u64 {
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if small.len() * 16 < large.len() {
+        return small
+            .iter()
+            .filter(|x| large.binary_search(x).is_ok())
+            .count() as u64;
+    }
+    let (mut i, mut j, mut count) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                count += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    count
+}
+
+// A set of vertex ids.
+#[derive(Clone, Debug, Default)]
+pub struct Roaring {
+    // The high bits (the id shifted right by 16) of each chunk with a
+    // container, in ascending order.
+    keys: Vec<u64>,
+    containers: Vec<Container>,
+}
+
+impl Roaring {
+    pub fn from_sorted(values: &[u64]) -> Self {
+        let mut set = Self::default();
+        let mut lows = Vec::new();
+        let mut rest = values;
+        while let Some(&first) = rest.first() {
+            let key = first >> 16;
+            let end = rest.partition_point(|&v| v >> 16 == key);
+            lows.clear();
+            lows.extend(rest[..end].iter().map(|&v| v as u16));
+            set.keys.push(key);
+            set.containers.push(Container::from_sorted(&lows));
+            rest = &rest[end..];
+        }
+        set
+    }
+
+    pub fn len(&self) -> usize {
+        self.containers.iter().map(Container::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn contains(&self, v: u64) -> bool {
+        self.keys
+            .binary_search(&(v >> 16))
+            .is_ok_and(|i| self.containers[i].contains(v as u16))
+    }
+
+    // The size of the intersection of the two sets, without building it.
+    pub fn intersect_len(&self, other: &Roaring) -> u64 {
+        let (mut i, mut j, mut count) = (0, 0, 0);
+        while i < self.keys.len() && j < other.keys.len() {
+            match self.keys[i].cmp(&other.keys[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    count += self.containers[i].and_len(&other.containers[j]);
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        count
+    }
+
+    // The values in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        let mut cursor = Cursor::new(self);
+        std::iter::from_fn(move || {
+            let value = cursor.value;
+            cursor.next(self);
+            value
+        })
+    }
+
+    pub fn heap_size(&self) -> usize {
+        self.keys.capacity() * std::mem::size_of::<u64>()
+            + self.containers.capacity() * std::mem::size_of::<Container>()
+            + self
+                .containers
+                .iter()
+                .map(Container::heap_size)
+                .sum::<usize>()
+    }
+}
+
+// A position in a set: the container at index `at`, and the position `pos`
+// within it (see `Container::find`), decoded into `value` as the cursor gets
+// there. Past the end, `at` is the number of containers.
+#[derive(Clone, Copy)]
+struct Cursor {
+    at: usize,
+    pos: usize,
+    value: Option<u64>,
+}
+
+impl Cursor {
+    fn new(set: &Roaring) -> Self {
+        let mut cursor = Self {
+            at: 0,
+            pos: 0,
+            value: None,
+        };
+        cursor.settle(set, 0, 0, 0);
+        cursor
+    }
+
+    // Move to the first value at or after position `from` of container `at`
+    // whose low bits are at least `low`, or failing that, to the first value
+    // of the containers after it.
+    fn settle(&mut self, set: &Roaring, mut at: usize, mut from: usize, mut low: u32) {
+        while at < set.containers.len() {
+            let container = &set.containers[at];
+            if let Some(pos) = container.find(from, low) {
+                self.at = at;
+                self.pos = pos;
+                self.value = Some(set.keys[at] << 16 | container.low(pos) as u64);
+                return;
+            }
+            at += 1;
+            from = 0;
+            low = 0;
+        }
+        self.at = set.containers.len();
+        self.value = None;
+    }
+
+    fn next(&mut self, set: &Roaring) {
+        if self.value.is_some() {
+            self.settle(set, self.at, self.pos + 1, 0);
+        }
+    }
+
+    fn seek(&mut self, set: &Roaring, v: u64) {
+        if self.value.is_none_or(|current| current >= v) {
+            return;
+        }
+        let key = v >> 16;
+        let low = (v & 0xffff) as u32;
+        if set.keys[self.at] == key {
+            self.settle(set, self.at, self.pos, low);
+        } else {
+            let at = gallop(&set.keys, self.at, |&k| k < key);
+            let low = if set.keys.get(at) == Some(&key) {
+                low
+            } else {
+                0
+            };
+            self.settle(set, at, 0, low);
+        }
+    }
+}
+
+pub struct RoaringGraph {
+    sources: Vec<u64>,
+    sets: Vec<Roaring>,
+}
+
+impl RoaringGraph {
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    // A trie iterator over the edges, at the start of the first level.
+    pub fn iter(self: &Arc<Self>) -> RoaringIterator {
+        RoaringIterator {
+            graph: self.clone(),
+            level: Level::Upper(0),
+        }
+    }
+
+    // Q(a, b, c) <- R(a, b), S(b, c), T(a, c): for each edge (a, b), the
+    // number of c's is the size of the intersection of a's and b's sets.
+    pub fn count_triangles(&self) -> u64 {
+        let mut count = 0;
+        for a in &self.sets {
+            // The b's come in ascending order, so each one's set is found by
+            // seeking forward from the last.
+            let mut j = 0;
+            for b in a.iter() {
+                j = gallop(&self.sources, j, |&u| u < b);
+                if j == self.sources.len() {
+                    break;
+                }
+                if self.sources[j] == b {
+                    count += a.intersect_len(&self.sets[j]);
+                }
+            }
+        }
+        count
+    }
+
+    // Roughly how many bytes of heap the graph takes up.
+    pub fn heap_size(&self) -> usize {
+        self.sources.capacity() * std::mem::size_of::<u64>()
+            + self.sets.capacity() * std::mem::size_of::<Roaring>()
+            + self.sets.iter().map(Roaring::heap_size).sum::<usize>()
+    }
+}
+
+impl From<&Adjacency> for RoaringGraph {
+    fn from(data: &Adjacency) -> Self {
+        Self {
+            sources: data.iter().map(|(u, _)| *u).collect(),
+            sets: data
+                .iter()
+                .map(|(_, vs)| Roaring::from_sorted(vs))
+                .collect(),
+        }
+    }
+}
+
+enum Level {
+    Upper(usize),
+    Lower(usize, Cursor),
+}
+
+pub struct RoaringIterator {
+    graph: Arc<RoaringGraph>,
+    level: Level,
+}
+
+// The set under the first-level entry at `i`. Past the end of the first
+// level there's nothing to bind, so the level below is the empty set, as it
+// is for `down`.
+fn set_at(graph: &RoaringGraph, i: usize) -> &Roaring {
+    static EMPTY: Roaring = Roaring {
+        keys: Vec::new(),
+        containers: Vec::new(),
+    };
+    graph.sets.get(i).unwrap_or(&EMPTY)
+}
+
+impl TrieIterator for RoaringIterator {
+    fn seek(&mut self, v: u64) {
+        match &mut self.level {
+            Level::Upper(i) => *i = gallop(&self.graph.sources, *i, |&x| x < v),
+            Level::Lower(i, cursor) => cursor.seek(set_at(&self.graph, *i), v),
+        }
+    }
+
+    fn up(&mut self) {
+        // At the top level, there's nothing bound to undo.
+        if let Level::Lower(i, _) = self.level {
+            self.level = Level::Upper(i);
+        }
+    }
+
+    fn down(&mut self) {
+        // At the bottom level, there's no level below.
+        if let Level::Upper(i) = self.level {
+            self.level = Level::Lower(i, Cursor::new(set_at(&self.graph, i)));
+        }
+    }
+
+    fn reset(&mut self) {
+        match self.level {
+            Level::Upper(_) => self.level = Level::Upper(0),
+            Level::Lower(i, _) => self.level = Level::Lower(i, Cursor::new(set_at(&self.graph, i))),
+        }
+    }
+
+    fn value(&self) -> Option<u64> {
+        match &self.level {
+            Level::Upper(i) => self.graph.sources.get(*i).copied(),
+            Level::Lower(_, cursor) => cursor.value,
+        }
+    }
+
+    fn next(&mut self) {
+        match &mut self.level {
+            Level::Upper(i) => *i += 1,
+            Level::Lower(i, cursor) => cursor.next(set_at(&self.graph, *i)),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+// Approximate triangle counts from a sample of the first-level vertices. Each
+// sampled vertex's triangles (those it's the `a` of) are counted exactly, and
+// the total is scaled up from there.
+//
+// On skewed graphs almost all of the triangles belong to a few high-degree
+// vertices, so a uniform sample's estimate swings wildly depending on whether
+// it happens to catch them. Instead the vertices are split into strata by
+// degree, and each stratum is sampled at its own rate: the heavy one much more
+// densely (often exhaustively), the light one sparsely. Summing each stratum's
+// scaled-up estimate gives an unbiased estimate of the total, whose variance
+// is the sum of the strata's.
+//
+// Rather than fixing the sample size up front, `until` keeps drawing samples
+// until the estimate's confidence interval is as tight as asked for.
+
+use std::fmt;
+
+use rand::{rngs::StdRng, seq::index, SeedableRng};
+
+use crate::{index::Adjacency, intersect};
+
+// One class of vertices and what its sample says.
+#[derive(Debug, Clone)]
+pub struct Stratum {
+    pub name: &'static str,
+    // How many vertices are in the class, and how many of them we counted.
+    pub population: usize,
+    pub sampled: usize,
+    // The estimated number of triangles rooted in the class, and the variance
+    // of that estimate.
+    pub estimate: f64,
+    pub variance: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Estimate {
+    pub triangles: f64,
+    pub strata: Vec<Stratum>,
+}
+
+impl Estimate {
+    pub fn variance(&self) -> f64 {
+        self.strata.iter().map(|s| s.variance).sum()
+    }
+
+    pub fn std_error(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    // The confidence interval `z` standard errors either side of the
+    // estimate (z = 1.96 for 95%).
+    pub fn interval(&self, z: f64) -> (f64, f64) {
+        let half = z * self.std_error();
+        ((self.triangles - half).max(0.0), self.triangles + half)
+    }
+
+    pub fn sampled(&self) -> usize {
+        self.strata.iter().map(|s| s.sampled).sum()
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "estimated triangles: {:.0} ± {:.0}",
+            self.triangles,
+            self.std_error()
+        )?;
+        for s in &self.strata {
+            writeln!(
+                f,
+                "  {:<6} {:>8} of {:>8} vertices  estimate {:>14.0}  std error {:>12.0}",
+                s.name,
+                s.sampled,
+                s.population,
+                s.estimate,
+                s.variance.sqrt()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// The z-score of a two-sided 95% confidence interval.
+pub const Z_95: f64 = 1.96;
+
+// How many vertices `until` samples before its first check of the interval.
+// Each round after that doubles the sample.
+const ROUND: usize = 64;
+
+// How many of a stratum's vertices `until` counts before trusting its
+// variance: on skewed data, a handful of samples that all happen to miss the
+// big contributors makes the stratum look far more certain than it is.
+const MIN_SAMPLES: usize = 100;
+
+// Estimate the triangle count from `samples` vertices drawn uniformly.
+pub fn uniform(data: &Adjacency, samples: usize, seed: u64) -> Estimate {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut all = Sampler::new("all", (0..data.len()).collect(), &mut rng);
+    all.draw(data, samples);
+    combine(vec![all.stratum()])
+}
+
+// Estimate the triangle count from about `samples` vertices, split between
+// those with more than `threshold` neighbors and the rest. Each stratum gets
+// a share of the samples in proportion to its number of wedges (a vertex of
+// degree d heads about d² of them), which is roughly where its triangles and
+// its variance are.
+pub fn stratified(data: &Adjacency, samples: usize, threshold: usize, seed: u64) -> Estimate {
+    let mut strata = strata(data, threshold, seed);
+    allocate(data, &mut strata, samples);
+    combine(strata.iter().map(Sampler::stratum).collect())
+}
+
+// Keep sampling, stratified as in `stratified`, until the confidence interval
+// `z` standard errors wide is within `relative_error` of the estimate either
+// side, or until every vertex has been counted (which makes it exact).
+pub fn until(
+    data: &Adjacency,
+    relative_error: f64,
+    z: f64,
+    threshold: usize,
+    seed: u64,
+) -> Estimate {
+    let mut strata = strata(data, threshold, seed);
+    let mut round = ROUND;
+    loop {
+        allocate(data, &mut strata, round);
+        round = strata.iter().map(|s| s.n).sum::<usize>().max(ROUND);
+        let estimate = combine(strata.iter().map(Sampler::stratum).collect());
+        let settled = strata.iter().all(|s| s.n >= MIN_SAMPLES.min(s.order.len()));
+        let exhausted = strata.iter().all(|s| s.n == s.order.len());
+        if exhausted || (settled && z * estimate.std_error() <= relative_error * estimate.triangles)
+        {
+            return estimate;
+        }
+    }
+}
+
+fn strata(data: &Adjacency, threshold: usize, seed: u64) -> Vec<Sampler> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (heavy, light): (Vec<usize>, Vec<usize>) =
+        (0..data.len()).partition(|&i| data[i].1.len() > threshold);
+    vec![
+        Sampler::new("heavy", heavy, &mut rng),
+        Sampler::new("light", light, &mut rng),
+    ]
+}
+
+// Draw `samples` more vertices across the strata, in proportion to how many
+// wedges each has left unsampled.
+fn allocate(data: &Adjacency, strata: &mut [Sampler], samples: usize) {
+    let left: Vec<f64> = strata
+        .iter()
+        .map(|s| {
+            s.order[s.n..]
+                .iter()
+                .map(|&i| (data[i].1.len() as f64).powi(2))
+                .sum()
+        })
+        .collect();
+    let total = left.iter().sum::<f64>().max(1.0);
+    let mut remaining = samples;
+    for (s, w) in strata.iter_mut().zip(&left) {
+        let share = ((samples as f64 * w / total).ceil() as usize).min(remaining);
+        remaining -= s.draw(data, share);
+    }
+    // Whatever couldn't be placed by wedges (say, in strata of isolated
+    // vertices) goes to whichever strata still have vertices left.
+    for s in strata.iter_mut() {
+        remaining -= s.draw(data, remaining);
+    }
+}
+
+fn combine(strata: Vec<Stratum>) -> Estimate {
+    Estimate {
+        triangles: strata.iter().map(|s| s.estimate).sum(),
+        strata,
+    }
+}
+
+// One stratum's sample as it grows: the class's vertices in a random order,
+// the first `n` of which have been counted, and the running sums of their
+// counts.
+struct Sampler {
+    name: &'static str,
+    order: Vec<usize>,
+    n: usize,
+    sum: f64,
+    sum_sq: f64,
+}
+
+impl Sampler {
+    fn new(name: &'static str, class: Vec<usize>, rng: &mut StdRng) -> Self {
+        let order = index::sample(rng, class.len(), class.len())
+            .into_iter()
+            .map(|j| class[j])
+            .collect();
+        Self {
+            name,
+            order,
+            n: 0,
+            sum: 0.0,
+            sum_sq: 0.0,
+        }
+    }
+
+    // Count up to `k` more vertices without replacement, returning how many.
+    fn draw(&mut self, data: &Adjacency, k: usize) -> usize {
+        let k = k.min(self.order.len() - self.n);
+        for &i in &self.order[self.n..self.n + k] {
+            let c = rooted(data, i) as f64;
+            self.sum += c;
+            self.sum_sq += c * c;
+        }
+        self.n += k;
+        k
+    }
+
+    // Scale the sample up to the whole class.
+    fn stratum(&self) -> Stratum {
+        let population = self.order.len();
+        let (estimate, variance) = if self.n == 0 {
+            (0.0, 0.0)
+        } else {
+            let n = self.n as f64;
+            let mean = self.sum / n;
+            let var = if self.n > 1 {
+                ((self.sum_sq - n * mean * mean) / (n - 1.0)).max(0.0)
+            } else {
+                0.0
+            };
+            // The variance of a scaled sample mean, with the finite
+            // population correction (which makes an exhaustive sample exact).
+            let p = population as f64;
+            let fpc = 1.0 - n / p;
+            (p * mean, p * p * fpc * var / n)
+        };
+        Stratum {
+            name: self.name,
+            population,
+            sampled: self.n,
+            estimate,
+            variance,
+        }
+    }
+}
+
+// The triangles whose `a` is the `i`th first-level vertex.
+pub fn rooted(data: &Adjacency, i: usize) -> u64 {
+    let a_neighbors = &data[i].1;
+    let mut count = 0;
+    for &b in a_neighbors {
+        if let Ok(j) = data.binary_search_by_key(&b, |(v, _)| *v) {
+            count += intersect::count(a_neighbors, &data[j].1);
+        }
+    }
+    count
+}
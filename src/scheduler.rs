@@ -0,0 +1,154 @@
+// Admission control for queries sharing one process, as in a server. Cheap
+// queries (counts) run straight away, while at most a fixed number of heavy
+// ones (enumerations, say) run at once, so a few huge requests can't tie up
+// every thread and starve the rest. Heavy queries beyond the limit wait in a
+// bounded queue, highest priority first and in arrival order within a
+// priority, and give up if they wait too long.
+
+use std::{
+    collections::BinaryHeap,
+    fmt,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weight {
+    Light,
+    Heavy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleError {
+    // Too many heavy queries were already waiting.
+    QueueFull,
+    // The query waited longer than its timeout without being admitted.
+    TimedOut,
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::QueueFull => write!(f, "too many queries are waiting"),
+            ScheduleError::TimedOut => write!(f, "timed out waiting to run"),
+        }
+    }
+}
+
+pub struct Scheduler {
+    max_heavy: usize,
+    max_waiting: usize,
+    state: Mutex<State>,
+    // Signalled whenever a heavy query finishes or leaves the queue.
+    changed: Condvar,
+}
+
+struct State {
+    running: usize,
+    waiting: BinaryHeap<Ticket>,
+    next: u64,
+}
+
+// A place in the queue. The heap pops the highest priority first, and the
+// earliest arrival among equal priorities.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Ticket {
+    priority: i32,
+    arrival: std::cmp::Reverse<u64>,
+}
+
+impl Scheduler {
+    // A scheduler running at most `max_heavy` heavy queries at once, with at
+    // most `max_waiting` more queued up behind them.
+    pub fn new(max_heavy: usize, max_waiting: usize) -> Self {
+        Self {
+            max_heavy: max_heavy.max(1),
+            max_waiting,
+            state: Mutex::new(State {
+                running: 0,
+                waiting: BinaryHeap::new(),
+                next: 0,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    // Run `f` once it's admitted, on the calling thread. Light queries are
+    // admitted immediately; heavy ones wait (for at most `timeout`) for a
+    // slot to be free and every query ahead of them in the queue to have
+    // gone first.
+    pub fn run<T>(
+        &self,
+        weight: Weight,
+        priority: i32,
+        timeout: Duration,
+        f: impl FnOnce() -> T,
+    ) -> Result<T, ScheduleError> {
+        if weight == Weight::Light {
+            return Ok(f());
+        }
+        let _slot = self.admit(priority, timeout)?;
+        Ok(f())
+    }
+
+    // How many heavy queries are running, and how many are waiting.
+    pub fn load(&self) -> (usize, usize) {
+        let state = self.lock();
+        (state.running, state.waiting.len())
+    }
+
+    fn admit(&self, priority: i32, timeout: Duration) -> Result<Slot<'_>, ScheduleError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        if state.running < self.max_heavy && state.waiting.is_empty() {
+            state.running += 1;
+            return Ok(Slot(self));
+        }
+        if state.waiting.len() >= self.max_waiting {
+            return Err(ScheduleError::QueueFull);
+        }
+        let ticket = Ticket {
+            priority,
+            arrival: std::cmp::Reverse(state.next),
+        };
+        state.next += 1;
+        state.waiting.push(ticket);
+        loop {
+            if state.running < self.max_heavy && state.waiting.peek() == Some(&ticket) {
+                state.waiting.pop();
+                state.running += 1;
+                // Whoever is next in line may be able to go too.
+                self.changed.notify_all();
+                return Ok(Slot(self));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                state.waiting.retain(|t| *t != ticket);
+                self.changed.notify_all();
+                return Err(ScheduleError::TimedOut);
+            }
+            state = self
+                .changed
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    // The state only holds counters and a queue which are never left half
+    // updated, so a panic elsewhere is no reason to stop scheduling.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// A running heavy query's slot, given back when it's dropped (even if the
+// query panics).
+struct Slot<'a>(&'a Scheduler);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.lock().running -= 1;
+        self.0.changed.notify_all();
+    }
+}
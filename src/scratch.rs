@@ -0,0 +1,74 @@
+// Scratch space for the short-lived vectors of the inner loops, so that
+// finding one more binding doesn't mean one more trip to the allocator.
+//
+// The lists an executor gathers to intersect borrow from its iterators, so
+// they can't be kept in a buffer that outlives the call that gathered them.
+// Instead they go in `Lists`, which holds the first few on the stack: a
+// variable rarely appears in more than a handful of atoms, and past that it
+// spills to the heap. Buffers of owned values (like the keys of a grouped
+// count) are simply kept by whoever owns the loop, one per executor and so
+// one per thread, and cleared rather than dropped between uses.
+
+// How many lists `Lists` holds before it spills to the heap.
+pub const INLINE: usize = 8;
+
+pub struct Lists<'a> {
+    inline: [&'a [u64]; INLINE],
+    len: usize,
+    // Only allocated past `INLINE` lists, and then holds all of them.
+    spilled: Vec<&'a [u64]>,
+}
+
+impl<'a> Default for Lists<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Lists<'a> {
+    pub fn new() -> Self {
+        Self {
+            inline: [&[]; INLINE],
+            len: 0,
+            spilled: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, list: &'a [u64]) {
+        if self.len < INLINE {
+            self.inline[self.len] = list;
+        } else {
+            if self.len == INLINE {
+                self.spilled.extend_from_slice(&self.inline);
+            }
+            self.spilled.push(list);
+        }
+        self.len += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [&'a [u64]] {
+        if self.len <= INLINE {
+            &mut self.inline[..self.len]
+        } else {
+            &mut self.spilled
+        }
+    }
+}
+
+impl<'a> FromIterator<&'a [u64]> for Lists<'a> {
+    fn from_iter<I: IntoIterator<Item = &'a [u64]>>(lists: I) -> Self {
+        let mut buffer = Self::new();
+        for list in lists {
+            buffer.push(list);
+        }
+        buffer
+    }
+}
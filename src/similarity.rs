@@ -0,0 +1,121 @@
+// How alike two vertices' neighborhoods are, for a batch of vertex pairs, and
+// the features link prediction uses to guess whether they'll be joined. The
+// neighborhoods are the lists the index stores under each vertex, so for an
+// undirected graph the index should hold both directions of each edge.
+
+use std::collections::HashMap;
+
+use crate::{
+    index::{self, Adjacency},
+    intersect,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    // Common neighbors over all neighbors of either vertex.
+    Jaccard,
+    // Common neighbors over the geometric mean of the two degrees.
+    Cosine,
+}
+
+impl Measure {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "jaccard" => Some(Measure::Jaccard),
+            "cosine" => Some(Measure::Cosine),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Measure::Jaccard => "jaccard",
+            Measure::Cosine => "cosine",
+        }
+    }
+
+    // The measure for neighborhoods of sizes `a` and `b` with `common`
+    // neighbors in common. A vertex with no neighbors is like nothing.
+    fn score(&self, a: usize, b: usize, common: u64) -> f64 {
+        let common = common as f64;
+        match self {
+            Measure::Jaccard if a + b > 0 => common / ((a + b) as f64 - common),
+            Measure::Cosine if a > 0 && b > 0 => common / ((a as f64) * (b as f64)).sqrt(),
+            _ => 0.0,
+        }
+    }
+}
+
+// The similarity of each of the pairs, in the same order.
+pub fn similarity(data: &Adjacency, pairs: &[(u64, u64)], measure: Measure) -> Vec<f64> {
+    pairs
+        .iter()
+        .map(|&(u, v)| {
+            let (a, b) = (index::neighbors(data, u), index::neighbors(data, v));
+            measure.score(a.len(), b.len(), intersect::count(a, b))
+        })
+        .collect()
+}
+
+// What the neighborhoods of a candidate pair say about whether it's an edge
+// waiting to happen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkFeatures {
+    // How many neighbors the two vertices have in common.
+    pub common: u64,
+    // The Adamic–Adar index: the common neighbors weighted by 1 / ln(degree),
+    // so that sharing an obscure neighbor counts for more than sharing a hub.
+    pub adamic_adar: f64,
+}
+
+impl LinkFeatures {
+    // Whether adding the edge would create any triangles: it closes one for
+    // each common neighbor.
+    pub fn closes_triangles(&self) -> bool {
+        self.common > 0
+    }
+}
+
+// The link-prediction features of each of the pairs, in the same order.
+pub fn link_features(data: &Adjacency, pairs: &[(u64, u64)]) -> Vec<LinkFeatures> {
+    pairs
+        .iter()
+        .map(|&(u, v)| {
+            let mut features = LinkFeatures {
+                common: 0,
+                adamic_adar: 0.0,
+            };
+            for w in index::intersect(data, u, v) {
+                features.common += 1;
+                // A common neighbor has degree at least two, unless the index
+                // only holds one direction of each edge.
+                let degree = index::neighbors(data, w).len();
+                if degree > 1 {
+                    features.adamic_adar += 1.0 / (degree as f64).ln();
+                }
+            }
+            features
+        })
+        .collect()
+}
+
+// The `k` vertices that connecting `u` to would close the most triangles,
+// along with how many each would close, most first (and lowest id first
+// among ties). Only vertices two hops from `u` can close any, so rather than
+// scoring every vertex we walk out through each neighbor, and seek into `u`'s
+// own neighbors to skip those it's already connected to.
+pub fn recommend(data: &Adjacency, u: u64, k: usize) -> Vec<(u64, u64)> {
+    let own = index::neighbors(data, u);
+    let mut closed: HashMap<u64, u64> = HashMap::new();
+    for &w in own {
+        for &x in index::neighbors(data, w) {
+            if x != u && own.binary_search(&x).is_err() {
+                *closed.entry(x).or_default() += 1;
+            }
+        }
+    }
+    let mut ranked: Vec<_> = closed.into_iter().collect();
+    ranked.sort_unstable_by_key(|&(x, n)| (std::cmp::Reverse(n), x));
+    ranked.truncate(k);
+    ranked
+}
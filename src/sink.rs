@@ -0,0 +1,182 @@
+// Hooks for handing enumerated triangles straight to the application, which
+// can write them wherever it likes as they're found, rather than having them
+// collected up (or boxed into an iterator) first.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::index::Adjacency;
+
+// How many first-level vertices to get through between progress reports.
+pub const PROGRESS_INTERVAL: usize = 1 << 12;
+
+// Where an enumeration sends its results.
+pub trait ResultSink {
+    fn on_triangle(&mut self, a: u64, b: u64, c: u64);
+
+    // Called every so often during the enumeration, and once at the end.
+    fn on_progress(&mut self, _progress: &Progress) {}
+
+    // Called once, after the last triangle.
+    fn on_done(&mut self, _stats: &EnumerationStats) {}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    // How many of the first-level vertices have been finished with, out of
+    // how many there are.
+    pub done: usize,
+    pub total: usize,
+    pub triangles: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnumerationStats {
+    pub triangles: u64,
+    pub elapsed: Duration,
+}
+
+// Collects every triangle.
+impl ResultSink for Vec<[u64; 3]> {
+    fn on_triangle(&mut self, a: u64, b: u64, c: u64) {
+        self.push([a, b, c]);
+    }
+}
+
+// How `WriteSink` writes each triangle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListFormat {
+    // A line `a b c`.
+    Lines,
+    // A line `a,b,c`, or `a\tb\tc`.
+    Csv,
+    Tsv,
+    // Three little-endian u64s, with nothing between triangles.
+    Binary,
+}
+
+impl ListFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "lines" => Some(ListFormat::Lines),
+            "csv" => Some(ListFormat::Csv),
+            "tsv" => Some(ListFormat::Tsv),
+            "binary" => Some(ListFormat::Binary),
+            _ => None,
+        }
+    }
+
+    // The format a file's extension suggests, if it suggests one.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(ListFormat::Csv),
+            "tsv" => Some(ListFormat::Tsv),
+            "bin" => Some(ListFormat::Binary),
+            _ => None,
+        }
+    }
+}
+
+// Writes each triangle as it's found, as a line `a b c` unless told
+// otherwise. Nothing is kept, so wrap `out` in a `BufWriter` if it's a file.
+// Since `on_triangle` can't fail, the first write error is kept, and
+// everything after it is dropped.
+pub struct WriteSink<W: Write> {
+    out: W,
+    format: ListFormat,
+    pub error: Option<io::Error>,
+}
+
+impl<W: Write> WriteSink<W> {
+    pub fn new(out: W) -> Self {
+        Self::with_format(out, ListFormat::Lines)
+    }
+
+    pub fn with_format(out: W, format: ListFormat) -> Self {
+        Self {
+            out,
+            format,
+            error: None,
+        }
+    }
+
+    // Flush the output, and return the first error hit, if any.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+}
+
+impl<W: Write> ResultSink for WriteSink<W> {
+    fn on_triangle(&mut self, a: u64, b: u64, c: u64) {
+        if self.error.is_none() {
+            let written = match self.format {
+                ListFormat::Lines => writeln!(self.out, "{} {} {}", a, b, c),
+                ListFormat::Csv => writeln!(self.out, "{},{},{}", a, b, c),
+                ListFormat::Tsv => writeln!(self.out, "{}\t{}\t{}", a, b, c),
+                ListFormat::Binary => {
+                    let mut bytes = [0; 24];
+                    for (chunk, v) in bytes.chunks_exact_mut(8).zip([a, b, c]) {
+                        chunk.copy_from_slice(&v.to_le_bytes());
+                    }
+                    self.out.write_all(&bytes)
+                }
+            };
+            if let Err(e) = written {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+// Send each triangle (a, b, c) of R(a, b), S(b, c), T(a, c) over `data` to
+// `sink`.
+pub fn enumerate(data: &Adjacency, sink: &mut dyn ResultSink) {
+    enumerate_mapped(data, sink, |u| u)
+}
+
+// Like `enumerate`, but with each id passed through `id` on its way out, for
+// callers enumerating over renumbered data.
+pub fn enumerate_mapped(data: &Adjacency, sink: &mut dyn ResultSink, id: impl Fn(u64) -> u64) {
+    let start = Instant::now();
+    let mut triangles = 0;
+    for (i, (a, a_neighbors)) in data.iter().enumerate() {
+        for &b in a_neighbors {
+            let Ok(pos) = data.binary_search_by_key(&b, |(v, _)| *v) else {
+                continue;
+            };
+            let b_neighbors = &data[pos].1;
+            let (mut x, mut y) = (0, 0);
+            while x < a_neighbors.len() && y < b_neighbors.len() {
+                let (u, v) = (a_neighbors[x], b_neighbors[y]);
+                if u == v {
+                    sink.on_triangle(id(*a), id(b), id(u));
+                    triangles += 1;
+                }
+                x += (u <= v) as usize;
+                y += (v <= u) as usize;
+            }
+        }
+        if (i + 1) % PROGRESS_INTERVAL == 0 {
+            sink.on_progress(&Progress {
+                done: i + 1,
+                total: data.len(),
+                triangles,
+            });
+        }
+    }
+    sink.on_progress(&Progress {
+        done: data.len(),
+        total: data.len(),
+        triangles,
+    });
+    sink.on_done(&EnumerationStats {
+        triangles,
+        elapsed: start.elapsed(),
+    });
+}
@@ -0,0 +1,108 @@
+// Skew-resilient triangle counting. A handful of very high degree vertices
+// make merge-based intersections expensive, since every intersection with one
+// of their neighbor lists walks (or at least seeks through) a huge list.
+// Instead we split vertices into heavy and light by degree: heavy vertices get
+// their neighbors in a bitmap, which the other side of an intersection probes
+// in time proportional to its own (short) list, while intersections between
+// two light vertices use the usual merge.
+
+use std::collections::HashMap;
+
+use crate::{
+    bitmap::Bitmap,
+    index::{self, Adjacency},
+    intersect, parallel,
+    stats::GraphStats,
+};
+
+pub struct HeavyLight {
+    pub threshold: usize,
+    // Bitmaps of the neighbors of each heavy vertex, keyed by its position in
+    // the adjacency data.
+    heavy: HashMap<usize, Bitmap>,
+    // The adjacency data with its ids renumbered onto 0..n, if they were so
+    // sparse that bitmaps over them would be larger than the graph. Counting
+    // walks these lists instead, so that their values probe the bitmaps.
+    compacted: Option<Adjacency>,
+}
+
+impl HeavyLight {
+    // Split the vertices of `data`, treating those with more than `threshold`
+    // neighbors as heavy.
+    pub fn new(data: &Adjacency, threshold: usize) -> Self {
+        let heavy = (0..data.len()).filter(|&i| data[i].1.len() > threshold);
+        Self::with_heavy(data, threshold, heavy)
+    }
+
+    // Split the vertices using the heavy hitters already found while building
+    // the index, with the usual threshold of sqrt(m), which bounds the number
+    // of heavy vertices by 2 sqrt(m). Only the tracked top-k can end up heavy,
+    // which is where nearly all of the benefit is anyway.
+    pub fn from_stats(data: &Adjacency, stats: &GraphStats) -> Self {
+        let threshold = (stats.edges as f64).sqrt() as usize;
+        let heavy = stats
+            .heavy_above(threshold)
+            .filter_map(|u| data.binary_search_by_key(&u, |(v, _)| *v).ok());
+        Self::with_heavy(data, threshold, heavy)
+    }
+
+    // Renumbering keeps every vertex at the same position, so `heavy` picks
+    // out the same vertices either way.
+    fn with_heavy(data: &Adjacency, threshold: usize, heavy: impl Iterator<Item = usize>) -> Self {
+        let compacted = index::compact(data).map(|(compacted, _)| compacted);
+        let lists = compacted.as_ref().unwrap_or(data);
+        let universe = parallel::universe(lists) as u64;
+        let heavy = heavy
+            .map(|i| (i, Bitmap::from_sorted(&lists[i].1, universe)))
+            .collect();
+        Self {
+            threshold,
+            heavy,
+            compacted,
+        }
+    }
+
+    pub fn heavy_count(&self) -> usize {
+        self.heavy.len()
+    }
+
+    // Q(a, b, c) <- R(a, b), S(b, c), T(a, c), with all three atoms over
+    // `data`. Each (a, b) binding picks its plan for the c-level: probe a
+    // heavy side's bitmap with the other side's list, or merge two light
+    // lists. `data` must be what the split was made over.
+    pub fn count_triangles(&self, data: &Adjacency) -> u64 {
+        let data = self.compacted.as_ref().unwrap_or(data);
+        let mut count = 0;
+        for (i, (_, a_neighbors)) in data.iter().enumerate() {
+            let mut pos = 0;
+            for &b in a_neighbors {
+                pos += data[pos..].partition_point(|(v, _)| *v < b);
+                if pos == data.len() {
+                    break;
+                }
+                if data[pos].0 != b {
+                    continue;
+                }
+                let b_neighbors = &data[pos].1;
+                count += match (self.heavy.get(&i), self.heavy.get(&pos)) {
+                    // With both sides in bitmaps, AND them a word at a time
+                    // where that's less work than probing with a list.
+                    (Some(a_bits), Some(b_bits))
+                        if a_bits.overlap_words(b_bits)
+                            < a_neighbors.len().min(b_neighbors.len()) =>
+                    {
+                        a_bits.count_common(b_bits)
+                    }
+                    // Probe whichever bitmap lets us walk the shorter list.
+                    (Some(_), Some(b_bits)) if a_neighbors.len() < b_neighbors.len() => {
+                        b_bits.count_in(a_neighbors)
+                    }
+                    (Some(a_bits), _) => a_bits.count_in(b_neighbors),
+                    (None, Some(b_bits)) => b_bits.count_in(a_neighbors),
+                    (None, None) => intersect::count(a_neighbors, b_neighbors),
+                };
+            }
+        }
+        count
+    }
+}
@@ -0,0 +1,143 @@
+// Golden-output checks for the CLI's JSON. A small corpus of graphs is built
+// in, and each case runs the binary over one of them with some arguments,
+// capturing what it prints. `check` compares that against the snapshot saved
+// for the case, so that a change to a report's format shows up as a diff
+// rather than slipping through; `update` rewrites the snapshots once the
+// change is deliberate.
+//
+// Snapshots live in a directory of their own, one `<case>.json` file per
+// case, next to a `corpus` directory the graphs are written into. The binary
+// runs from that directory with relative paths, so nothing machine-specific
+// ends up in the output. Timings are the one thing that changes from run to
+// run, and are zeroed before comparing.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+// The graphs, by name.
+pub const CORPUS: &[(&str, &str)] = &[
+    ("triangle", "1 2\n2 3\n1 3\n"),
+    ("k4", "1 2\n1 3\n1 4\n2 3\n2 4\n3 4\n"),
+    ("self-loop", "1 1\n1 2\n2 3\n1 3\n"),
+    ("duplicates", "1 2\n1 2\n2 3\n1 3\n3 4\n"),
+    ("malformed", "1 2\n2 three\n"),
+];
+
+// Each case: its name, the graph it reads, and the rest of its arguments.
+pub const CASES: &[(&str, &str, &[&str])] = &[
+    ("count-triangle", "triangle", &[]),
+    ("count-k4", "k4", &[]),
+    ("count-self-loop", "self-loop", &[]),
+    ("count-duplicates", "duplicates", &[]),
+    ("count-generic", "k4", &["--engine", "generic"]),
+    ("degrees-k4", "k4", &["--degree-histogram", "json"]),
+    (
+        "degrees-self-loop",
+        "self-loop",
+        &["--degree-histogram", "json"],
+    ),
+    ("failure-malformed", "malformed", &[]),
+    ("failure-missing", "missing", &[]),
+];
+
+// A case whose output no longer matches its snapshot. `expected` is None if
+// there was no snapshot at all.
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub case: &'static str,
+    pub expected: Option<String>,
+    pub actual: String,
+}
+
+// The case, then its snapshot and its output line by line, marked - and +.
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:", self.case)?;
+        match &self.expected {
+            Some(expected) => {
+                for line in expected.lines() {
+                    writeln!(f, "  - {}", line)?;
+                }
+            }
+            None => writeln!(f, "  (no snapshot)")?,
+        }
+        for line in self.actual.lines() {
+            writeln!(f, "  + {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+// Run every case with the binary `exe`, and return those whose output differs
+// from the snapshots in `dir`.
+pub fn check(exe: &Path, dir: &Path) -> io::Result<Vec<Mismatch>> {
+    let mut mismatches = Vec::new();
+    for &(case, graph, args) in CASES {
+        let actual = run(exe, dir, graph, args)?;
+        let expected = match fs::read_to_string(snapshot_path(dir, case)) {
+            Ok(s) => Some(s),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        if expected.as_deref() != Some(actual.as_str()) {
+            mismatches.push(Mismatch {
+                case,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+// Run every case with the binary `exe`, and save its output as the snapshot
+// in `dir`, returning how many there were.
+pub fn update(exe: &Path, dir: &Path) -> io::Result<usize> {
+    for &(case, graph, args) in CASES {
+        let actual = run(exe, dir, graph, args)?;
+        fs::write(snapshot_path(dir, case), actual)?;
+    }
+    Ok(CASES.len())
+}
+
+fn snapshot_path(dir: &Path, case: &str) -> PathBuf {
+    dir.join(format!("{}.json", case))
+}
+
+// Run one case, returning its stdout and then its stderr, with the timings
+// scrubbed.
+fn run(exe: &Path, dir: &Path, graph: &str, args: &[&str]) -> io::Result<String> {
+    let corpus = dir.join("corpus");
+    fs::create_dir_all(&corpus)?;
+    for (name, edges) in CORPUS {
+        fs::write(corpus.join(format!("{}.txt", name)), edges)?;
+    }
+    let output = Command::new(exe)
+        .current_dir(dir)
+        .args(["--output", "json", "--input"])
+        .arg(format!("corpus/{}.txt", graph))
+        .args(args)
+        .output()?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok(scrub(&text))
+}
+
+// Zero every `"seconds":<number>`.
+fn scrub(text: &str) -> String {
+    const KEY: &str = "\"seconds\":";
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find(KEY) {
+        out.push_str(&rest[..i + KEY.len()]);
+        out.push('0');
+        rest = rest[i + KEY.len()..].trim_start_matches(|c: char| {
+            c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '-' | '+')
+        });
+    }
+    out.push_str(rest);
+    out
+}
@@ -0,0 +1,156 @@
+// Summary statistics gathered over an index once it's built, used for
+// reporting and for choosing between plans.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    fmt,
+};
+
+use crate::index::{self, Adjacency};
+
+// How many heavy hitters to keep track of by default.
+pub const DEFAULT_TOP_K: usize = 10;
+
+pub struct GraphStats {
+    // Vertices with at least one out-neighbor.
+    pub sources: usize,
+    pub edges: usize,
+    pub max_degree: usize,
+    // The vertices with the largest out-degrees, largest first, as (vertex,
+    // degree) pairs.
+    pub heavy_hitters: Vec<(u64, usize)>,
+    // See `index::fingerprint`.
+    pub fingerprint: u64,
+    // How many vertices have each out-degree, as (degree, count) pairs in
+    // ascending order of degree. Only vertices with at least one out-neighbor
+    // are counted.
+    pub degree_histogram: Vec<(usize, usize)>,
+}
+
+impl GraphStats {
+    // Collect statistics in a single pass over `data`, keeping the exact top
+    // `k` vertices by degree in a bounded heap.
+    pub fn collect(data: &Adjacency, k: usize) -> Self {
+        let mut edges = 0;
+        let mut max_degree = 0;
+        let mut fingerprint = 0_u64;
+        let mut top: BinaryHeap<Reverse<(usize, u64)>> = BinaryHeap::with_capacity(k + 1);
+        let mut histogram: BTreeMap<usize, usize> = BTreeMap::new();
+        for (u, vs) in data {
+            *histogram.entry(vs.len()).or_default() += 1;
+            edges += vs.len();
+            max_degree = max_degree.max(vs.len());
+            fingerprint = fingerprint.wrapping_add(index::fingerprint_list(*u, vs));
+            top.push(Reverse((vs.len(), *u)));
+            if top.len() > k {
+                top.pop();
+            }
+        }
+        let mut heavy_hitters: Vec<(u64, usize)> =
+            top.into_iter().map(|Reverse((d, u))| (u, d)).collect();
+        heavy_hitters.sort_by_key(|&(u, d)| (Reverse(d), u));
+        Self {
+            sources: data.len(),
+            edges,
+            max_degree,
+            heavy_hitters,
+            fingerprint,
+            degree_histogram: histogram.into_iter().collect(),
+        }
+    }
+
+    pub fn avg_degree(&self) -> f64 {
+        if self.sources == 0 {
+            0.0
+        } else {
+            self.edges as f64 / self.sources as f64
+        }
+    }
+
+    // The complementary cumulative distribution of the degrees: for each
+    // degree d in the histogram, the fraction of vertices with degree at
+    // least d.
+    pub fn degree_ccdf(&self) -> Vec<(usize, f64)> {
+        let total: usize = self.degree_histogram.iter().map(|(_, n)| n).sum();
+        let mut at_least = total;
+        self.degree_histogram
+            .iter()
+            .map(|&(d, n)| {
+                let p = at_least as f64 / total as f64;
+                at_least -= n;
+                (d, p)
+            })
+            .collect()
+    }
+
+    // The maximum likelihood estimate of the exponent alpha of a power law
+    // P(d) ~ d^-alpha fitted to the degrees of at least `d_min`, using the
+    // discrete approximation from Clauset, Shalizi, and Newman (2009). None if
+    // there are no such degrees to fit.
+    pub fn power_law_exponent(&self, d_min: usize) -> Option<f64> {
+        let d_min = d_min.max(1);
+        let shift = d_min as f64 - 0.5;
+        let (mut n, mut sum) = (0, 0.0);
+        for &(d, count) in &self.degree_histogram {
+            if d >= d_min {
+                n += count;
+                sum += count as f64 * (d as f64 / shift).ln();
+            }
+        }
+        (n > 0 && sum > 0.0).then(|| 1.0 + n as f64 / sum)
+    }
+
+    // The degree histogram and CCDF as CSV, one degree per row.
+    pub fn degree_csv(&self) -> String {
+        let mut out = String::from("degree,count,ccdf\n");
+        for (&(d, n), (_, p)) in self.degree_histogram.iter().zip(self.degree_ccdf()) {
+            out.push_str(&format!("{},{},{}\n", d, n, p));
+        }
+        out
+    }
+
+    // The degree histogram, CCDF, and power-law fit as a JSON object.
+    pub fn degree_json(&self) -> String {
+        let rows: Vec<String> = self
+            .degree_histogram
+            .iter()
+            .zip(self.degree_ccdf())
+            .map(|(&(d, n), (_, p))| format!("{{\"degree\":{},\"count\":{},\"ccdf\":{}}}", d, n, p))
+            .collect();
+        let alpha = self
+            .power_law_exponent(1)
+            .map_or("null".to_string(), |a| a.to_string());
+        format!(
+            "{{\"power_law_exponent\":{},\"degrees\":[{}]}}\n",
+            alpha,
+            rows.join(",")
+        )
+    }
+
+    // The heavy hitters whose degree is above `threshold`.
+    pub fn heavy_above(&self, threshold: usize) -> impl Iterator<Item = u64> + '_ {
+        self.heavy_hitters
+            .iter()
+            .filter(move |(_, d)| *d > threshold)
+            .map(|(u, _)| *u)
+    }
+}
+
+impl fmt::Display for GraphStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "sources:      {}", self.sources)?;
+        writeln!(f, "edges:        {}", self.edges)?;
+        writeln!(f, "max degree:   {}", self.max_degree)?;
+        writeln!(f, "avg degree:   {:.2}", self.avg_degree())?;
+        writeln!(f, "fingerprint:  {:016x}", self.fingerprint)?;
+        if let Some(alpha) = self.power_law_exponent(1) {
+            writeln!(f, "power law:    alpha ~ {:.2}", alpha)?;
+        }
+        writeln!(f, "heavy hitters:")?;
+        for (u, d) in &self.heavy_hitters {
+            writeln!(f, "  {:>12} degree {}", u, d)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+// Maintaining the number of triangles in a graph under a stream of edge
+// updates, in the `+ u v` / `- u v` format that `generate` writes. Edges are
+// undirected here: (u, v) and (v, u) are the same edge.
+//
+// Parsing and maintenance run on separate threads joined by a bounded queue,
+// so a stream that arrives faster than it can be applied can't grow memory
+// without limit. When the queue fills up, the reader either stops reading
+// (pushing back on whoever is writing, down to the socket), or starts
+// shedding load by sampling edges, in which case the count becomes an
+// estimate.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{self, BufRead},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, TrySendError},
+    },
+    thread,
+};
+
+use crate::index;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Update {
+    Insert(u64, u64),
+    Delete(u64, u64),
+}
+
+impl Update {
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let op = fields.next()?;
+        let u = fields.next()?.parse().ok()?;
+        let v = fields.next()?.parse().ok()?;
+        if fields.next().is_some() {
+            return None;
+        }
+        match op {
+            "+" => Some(Update::Insert(u, v)),
+            "-" => Some(Update::Delete(u, v)),
+            _ => None,
+        }
+    }
+
+    fn edge(&self) -> (u64, u64) {
+        match *self {
+            Update::Insert(u, v) | Update::Delete(u, v) => (u.min(v), u.max(v)),
+        }
+    }
+}
+
+// An undirected graph along with the number of triangles in it.
+#[derive(Default)]
+pub struct IncrementalTriangles {
+    neighbors: HashMap<u64, HashSet<u64>>,
+    pub triangles: u64,
+}
+
+impl IncrementalTriangles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Apply an update, returning whether it changed the graph (inserting an
+    // edge that's already there or deleting one that isn't does nothing).
+    // Self-loops are ignored.
+    pub fn apply(&mut self, update: Update) -> bool {
+        let (u, v) = update.edge();
+        if u == v {
+            return false;
+        }
+        let present = self.neighbors.get(&u).is_some_and(|ns| ns.contains(&v));
+        match update {
+            Update::Insert(..) if !present => {
+                self.triangles += self.common_neighbors(u, v);
+                self.neighbors.entry(u).or_default().insert(v);
+                self.neighbors.entry(v).or_default().insert(u);
+                true
+            }
+            Update::Delete(..) if present => {
+                self.remove(u, v);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Drop every edge for which `keep` is false.
+    pub fn retain(&mut self, mut keep: impl FnMut(u64, u64) -> bool) {
+        let doomed: Vec<(u64, u64)> = self
+            .neighbors
+            .iter()
+            .flat_map(|(&u, ns)| ns.iter().map(move |&v| (u, v)))
+            .filter(|&(u, v)| u < v && !keep(u, v))
+            .collect();
+        for (u, v) in doomed {
+            self.remove(u, v);
+        }
+    }
+
+    fn remove(&mut self, u: u64, v: u64) {
+        for (a, b) in [(u, v), (v, u)] {
+            let ns = self.neighbors.get_mut(&a).unwrap();
+            ns.remove(&b);
+            if ns.is_empty() {
+                self.neighbors.remove(&a);
+            }
+        }
+        self.triangles -= self.common_neighbors(u, v);
+    }
+
+    pub fn edges(&self) -> usize {
+        self.neighbors.values().map(HashSet::len).sum::<usize>() / 2
+    }
+
+    fn common_neighbors(&self, u: u64, v: u64) -> u64 {
+        match (self.neighbors.get(&u), self.neighbors.get(&v)) {
+            (Some(a), Some(b)) => {
+                let (small, large) = if a.len() < b.len() { (a, b) } else { (b, a) };
+                small.iter().filter(|w| large.contains(w)).count() as u64
+            }
+            _ => 0,
+        }
+    }
+}
+
+// What to do when updates arrive faster than they can be applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    // Wait for room in the queue. Nothing is lost, and the writer is slowed
+    // down to our pace.
+    Block,
+    // Halve the fraction of edges kept every time the queue fills up. Which
+    // edges are kept depends only on the edge, so an edge's deletion is kept
+    // exactly when its insertion was, and the sampled graph stays
+    // consistent. A triangle survives with probability p^3, so the count is
+    // scaled up by 1/p^3 to estimate the real one.
+    Shed,
+}
+
+impl Overflow {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "block" => Some(Overflow::Block),
+            "shed" => Some(Overflow::Shed),
+            _ => None,
+        }
+    }
+}
+
+// How many updates may be waiting to be applied by default.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1 << 16;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IngestReport {
+    // Updates read, including ones that were shed or malformed.
+    pub read: u64,
+    pub applied: u64,
+    // Updates dropped by sampling.
+    pub shed: u64,
+    pub malformed: u64,
+    // The fraction of edges being kept at the end (1 unless shedding).
+    pub sample_rate: f64,
+    // Triangles in the (possibly sampled) graph, and the estimate for the
+    // full graph scaled up from that.
+    pub triangles: u64,
+    pub estimate: f64,
+    pub edges: usize,
+}
+
+impl fmt::Display for IngestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "updates read:  {}", self.read)?;
+        writeln!(f, "applied:       {}", self.applied)?;
+        writeln!(f, "malformed:     {}", self.malformed)?;
+        writeln!(f, "edges:         {}", self.edges)?;
+        if self.sample_rate < 1.0 {
+            writeln!(f, "shed:          {}", self.shed)?;
+            writeln!(f, "sample rate:   {}", self.sample_rate)?;
+            writeln!(f, "sampled count: {}", self.triangles)?;
+            writeln!(f, "triangles:     ~{:.0}", self.estimate)
+        } else {
+            writeln!(f, "triangles:     {}", self.triangles)
+        }
+    }
+}
+
+// Read updates from `reader` until it ends, maintaining the triangle count
+// with at most `capacity` updates waiting in between. `on_apply` is called
+// with each update that changes the (possibly sampled) graph, in the order
+// they're applied.
+pub fn ingest<R: BufRead + Send>(
+    reader: R,
+    capacity: usize,
+    overflow: Overflow,
+    mut on_apply: impl FnMut(Update),
+) -> io::Result<IngestReport> {
+    // Edges are kept if their hash is at most the threshold, which starts
+    // out keeping everything.
+    let threshold = AtomicU64::new(u64::MAX);
+    let capacity = capacity.max(1);
+    let (tx, rx) = mpsc::sync_channel::<Update>(capacity);
+    let hash = |(u, v): (u64, u64)| index::fingerprint_list(u, &[v]);
+
+    thread::scope(|scope| {
+        let reader = scope.spawn(|| -> io::Result<IngestReport> {
+            let mut report = IngestReport::default();
+            let mut halved_at = 0;
+            for line in reader.lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                report.read += 1;
+                let Some(update) = Update::parse(&line) else {
+                    report.malformed += 1;
+                    continue;
+                };
+                let keep =
+                    |update: &Update| hash(update.edge()) <= threshold.load(Ordering::Relaxed);
+                if !keep(&update) {
+                    report.shed += 1;
+                    continue;
+                }
+                let update = match (overflow, tx.try_send(update)) {
+                    (_, Ok(())) => continue,
+                    (_, Err(TrySendError::Disconnected(_))) => break,
+                    (Overflow::Block, Err(TrySendError::Full(update))) => update,
+                    // Give each halving a queue's worth of updates to take
+                    // effect before halving again, and wait in the meantime.
+                    (Overflow::Shed, Err(TrySendError::Full(update)))
+                        if report.read - halved_at >= capacity as u64 =>
+                    {
+                        halved_at = report.read;
+                        threshold.store(threshold.load(Ordering::Relaxed) / 2, Ordering::Relaxed);
+                        if !keep(&update) {
+                            report.shed += 1;
+                            continue;
+                        }
+                        update
+                    }
+                    (Overflow::Shed, Err(TrySendError::Full(update))) => update,
+                };
+                if tx.send(update).is_err() {
+                    break;
+                }
+            }
+            drop(tx);
+            Ok(report)
+        });
+
+        let mut graph = IncrementalTriangles::new();
+        let (mut applied, mut shed) = (0, 0);
+        let mut current = u64::MAX;
+        for update in rx {
+            let t = threshold.load(Ordering::Relaxed);
+            if t != current {
+                // The sample shrank: drop the edges that are no longer in it.
+                graph.retain(|u, v| hash((u, v)) <= t);
+                current = t;
+            }
+            if hash(update.edge()) > current {
+                // Queued before the sample shrank.
+                shed += 1;
+            } else if graph.apply(update) {
+                on_apply(update);
+                applied += 1;
+            }
+        }
+
+        let mut report = reader.join().unwrap()?;
+        let p = current as f64 / u64::MAX as f64;
+        report.applied = applied;
+        report.shed += shed;
+        report.sample_rate = p;
+        report.triangles = graph.triangles;
+        report.estimate = graph.triangles as f64 / (p * p * p);
+        report.edges = graph.edges();
+        Ok(report)
+    })
+}
@@ -0,0 +1,53 @@
+// A slot holding the active version of some shared data (typically an
+// index), which can be replaced with a freshly built one without stopping
+// readers. Readers take a snapshot and keep using it for as long as they
+// like; a swap only changes what later snapshots see, and the old version is
+// freed once the last reader holding it lets go.
+
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+
+pub struct HotSwap<T> {
+    // The lock is only ever held long enough to clone or replace the `Arc`,
+    // never while a query runs, so a swap never waits on a query and vice
+    // versa.
+    current: RwLock<(Arc<T>, u64)>,
+}
+
+impl<T> HotSwap<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: RwLock::new((Arc::new(value), 0)),
+        }
+    }
+
+    // The active version.
+    pub fn load(&self) -> Arc<T> {
+        self.read().0.clone()
+    }
+
+    // The active version along with its generation, which starts at 0 and
+    // goes up by one with every swap.
+    pub fn load_with_generation(&self) -> (Arc<T>, u64) {
+        let current = self.read();
+        (current.0.clone(), current.1)
+    }
+
+    // Make `value` the active version, returning the one it replaces.
+    // Snapshots already taken are unaffected.
+    pub fn store(&self, value: T) -> Arc<T> {
+        let value = Arc::new(value);
+        // A panic while holding the lock can't leave the pair half-written,
+        // so there's no reason to refuse to carry on past one.
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        current.1 += 1;
+        std::mem::replace(&mut current.0, value)
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.read().1
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, (Arc<T>, u64)> {
+        self.current.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
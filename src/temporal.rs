@@ -0,0 +1,244 @@
+// Temporal triangles: graphs whose edges are events with a time, where the
+// same pair of vertices can interact any number of times, and a triangle is
+// three events, one on each of (a, b), (b, c), and (a, c), close together in
+// time. Following the usual temporal-motif definitions (Paranjape et al.),
+// the three events must all fall within `delta` of the first, and can also be
+// required to happen in a given order, say (a, b) before (b, c) before
+// (a, c).
+//
+// Each static triangle is found once, and its events are counted from the
+// sorted times of its three edges, so a pair with many events costs a few
+// binary searches per event rather than a pass over the others.
+//
+// `series` instead follows the graph as it grows: the undirected triangle
+// count after each interval of time, with the events replayed in order
+// through `stream`'s incremental counter rather than recounted per snapshot.
+
+use std::fmt;
+
+use crate::{
+    index::Adjacency,
+    stream::{IncrementalTriangles, Update},
+};
+
+// The edges of a triangle, in the order of the atoms of R(a, b), S(b, c),
+// T(a, c).
+pub const EDGES: [&str; 3] = ["ab", "bc", "ac"];
+
+pub struct TemporalGraph {
+    pub adjacency: Adjacency,
+    // For each neighbor in `adjacency`, the times of the events on that edge,
+    // in ascending order.
+    pub times: Vec<Vec<Vec<u64>>>,
+}
+
+impl TemporalGraph {
+    // Build the graph from (src, dst, time) events, in any order.
+    pub fn new(mut events: Vec<(u64, u64, u64)>) -> Self {
+        events.sort_unstable();
+        let mut adjacency: Adjacency = Vec::new();
+        let mut times: Vec<Vec<Vec<u64>>> = Vec::new();
+        for (u, v, t) in events {
+            match adjacency.last_mut() {
+                Some((last, vs)) if *last == u => {
+                    let ts = times.last_mut().unwrap();
+                    if vs.last() == Some(&v) {
+                        ts.last_mut().unwrap().push(t);
+                    } else {
+                        vs.push(v);
+                        ts.push(vec![t]);
+                    }
+                }
+                _ => {
+                    adjacency.push((u, vec![v]));
+                    times.push(vec![vec![t]]);
+                }
+            }
+        }
+        Self { adjacency, times }
+    }
+}
+
+// Which event triples count as a temporal triangle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Constraint {
+    // The most time there can be between the first event and the last.
+    pub delta: u64,
+    // If set, the edges (as indexes into `EDGES`) whose events must happen
+    // in this order, each strictly after the last. If not, any order will do.
+    pub order: Option<[usize; 3]>,
+}
+
+impl Constraint {
+    // Parse an order like "ab,bc,ac": each of the three edges once, earliest
+    // first.
+    pub fn parse_order(s: &str) -> Option<[usize; 3]> {
+        let edges: Vec<usize> = s
+            .split(',')
+            .map(|e| EDGES.iter().position(|name| *name == e.trim()))
+            .collect::<Option<_>>()?;
+        let order: [usize; 3] = edges.try_into().ok()?;
+        let mut seen = [false; 3];
+        for &e in &order {
+            if std::mem::replace(&mut seen[e], true) {
+                return None;
+            }
+        }
+        Some(order)
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.order {
+            Some([x, y, z]) => write!(f, "t({}) < t({}) < t({})", EDGES[x], EDGES[y], EDGES[z])?,
+            None => write!(f, "any order")?,
+        }
+        if self.delta == u64::MAX {
+            write!(f, ", at any distance")
+        } else {
+            write!(f, ", within {}", self.delta)
+        }
+    }
+}
+
+// The number of temporal triangles in the graph: triples of events on the
+// three edges of some triangle (a, b, c) of R(a, b), S(b, c), T(a, c) that
+// satisfy `constraint`.
+pub fn count(graph: &TemporalGraph, constraint: &Constraint) -> u64 {
+    let data = &graph.adjacency;
+    let mut count = 0;
+    for (i, (_, a_neighbors)) in data.iter().enumerate() {
+        let a_times = &graph.times[i];
+        for (x, &b) in a_neighbors.iter().enumerate() {
+            let Ok(j) = data.binary_search_by_key(&b, |(v, _)| *v) else {
+                continue;
+            };
+            let (b_neighbors, b_times) = (&data[j].1, &graph.times[j]);
+            let (mut y, mut z) = (0, 0);
+            while y < a_neighbors.len() && z < b_neighbors.len() {
+                let (u, v) = (a_neighbors[y], b_neighbors[z]);
+                if u == v {
+                    let times = [&a_times[x][..], &b_times[z][..], &a_times[y][..]];
+                    count += count_events(times, constraint);
+                }
+                y += (u <= v) as usize;
+                z += (v <= u) as usize;
+            }
+        }
+    }
+    count
+}
+
+// The number of triples of events, one from each of the sorted lists of
+// `times`, that satisfy `constraint`.
+fn count_events(times: [&[u64]; 3], constraint: &Constraint) -> u64 {
+    let delta = constraint.delta;
+    // How many of `ts` are in (lo, hi], or [lo, hi] if `inclusive`.
+    let within = |ts: &[u64], lo: u64, hi: u64, inclusive: bool| {
+        let start = if inclusive {
+            ts.partition_point(|&t| t < lo)
+        } else {
+            ts.partition_point(|&t| t <= lo)
+        };
+        ts.partition_point(|&t| t <= hi).saturating_sub(start) as u64
+    };
+    match constraint.order {
+        Some([x, y, z]) => {
+            let mut count = 0;
+            for &t1 in times[x] {
+                let end = t1.saturating_add(delta);
+                let start = times[y].partition_point(|&t| t <= t1);
+                for &t2 in times[y][start..].iter().take_while(|&&t| t <= end) {
+                    count += within(times[z], t2, end, false);
+                }
+            }
+            count
+        }
+        // Count each triple from its earliest event. Events at the same time
+        // on different edges are ordered by edge, so every triple has exactly
+        // one earliest.
+        None => {
+            let mut count = 0;
+            for e in 0..3 {
+                for &t in times[e] {
+                    let end = t.saturating_add(delta);
+                    let mut product = 1;
+                    for other in (0..3).filter(|&o| o != e) {
+                        product *= within(times[other], t, end, other > e);
+                    }
+                    count += product;
+                }
+            }
+            count
+        }
+    }
+}
+
+// The length of an interval, as "hour", "day", "week", or a number of time
+// units. The names assume the times are in seconds.
+pub fn parse_interval(s: &str) -> Option<u64> {
+    match s {
+        "hour" | "hourly" => Some(60 * 60),
+        "day" | "daily" => Some(24 * 60 * 60),
+        "week" | "weekly" => Some(7 * 24 * 60 * 60),
+        _ => s.parse().ok().filter(|&n| n > 0),
+    }
+}
+
+// The graph as of the end of one interval.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Point {
+    // When the interval starts. Intervals are aligned to multiples of their
+    // length, so with times in seconds since the Unix epoch, days are UTC
+    // days.
+    pub start: u64,
+    // Distinct undirected edges, and the triangles they make, counting every
+    // event up to the end of the interval.
+    pub edges: usize,
+    pub triangles: u64,
+}
+
+// An interval of no time at all, which a series would never get past.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmptyInterval;
+
+impl fmt::Display for EmptyInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "intervals must be at least one unit long")
+    }
+}
+
+// The cumulative triangle count at the end of every interval of length
+// `interval` from the first event's to the last's, found in one pass over
+// the (src, dst, time) events in time order. Intervals without events repeat
+// the last count, so the series has no gaps.
+pub fn series(
+    mut events: Vec<(u64, u64, u64)>,
+    interval: u64,
+) -> Result<Vec<Point>, EmptyInterval> {
+    if interval == 0 {
+        return Err(EmptyInterval);
+    }
+    events.sort_unstable_by_key(|&(_, _, t)| t);
+    let mut graph = IncrementalTriangles::new();
+    let mut points = Vec::new();
+    let mut events = events.into_iter().peekable();
+    let Some(&(_, _, first)) = events.peek() else {
+        return Ok(points);
+    };
+    let mut start = first - first % interval;
+    while events.peek().is_some() {
+        let end = start.saturating_add(interval);
+        while let Some((u, v, _)) = events.next_if(|&(_, _, t)| t < end) {
+            graph.apply(Update::Insert(u, v));
+        }
+        points.push(Point {
+            start,
+            edges: graph.edges(),
+            triangles: graph.triangles,
+        });
+        start = end;
+    }
+    Ok(points)
+}
@@ -0,0 +1,310 @@
+// Recording what a session did (the graph it loaded and how, the queries it
+// ran and what they returned, the updates it applied) to a plain text
+// transcript, so that a run can be replayed later and checked against what
+// it saw the first time. One event per line:
+//
+//     dialect whitespace 0,1 0 noheader #
+//     load edges.txt 39ce73d99346026d
+//     reorder rcm
+//     orient degree
+//     count handwritten 4
+//     + 1 2
+//     - 1 2
+//     triangles 0
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    engine::{self, Engine},
+    failure::{Failure, FailureKind},
+    index::{self, Adjacency},
+    loader::{self, Delimiter, Dialect, IndexBase, LoadError},
+    orient::{Orientation, Oriented},
+    persist,
+    reorder::{Order, Relabeling},
+    stats::{self, GraphStats},
+    stream::{IncrementalTriangles, Update},
+};
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    // How the next graph is to be read.
+    Dialect(Dialect),
+    // A graph was loaded from `path`, and had the given fingerprint (see
+    // `index::fingerprint`) once built.
+    Load { path: PathBuf, fingerprint: u64 },
+    Reorder(Order),
+    // The counts after this one are over the graph loaded last, oriented.
+    Orient(Orientation),
+    // A count with the named engine, and its result.
+    Count { engine: String, triangles: u64 },
+    // An update applied to the streamed graph.
+    Update(Update),
+    // The number of triangles in the streamed graph at the end of the stream.
+    Triangles(u64),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Dialect(d) => {
+                let delimiter = match d.delimiter {
+                    Delimiter::Whitespace => "whitespace",
+                    Delimiter::Tab => "tab",
+                    Delimiter::Comma => "comma",
+                };
+                let base = match d.base {
+                    IndexBase::Zero => "0",
+                    IndexBase::One => "1",
+                    IndexBase::Auto => "auto",
+                };
+                let header = if d.header { "header" } else { "noheader" };
+                write!(
+                    f,
+                    "dialect {} {},{} {} {}",
+                    delimiter, d.columns.0, d.columns.1, base, header
+                )?;
+                for prefix in &d.comment_prefixes {
+                    write!(f, " {}", prefix)?;
+                }
+                Ok(())
+            }
+            Event::Load { path, fingerprint } => {
+                write!(f, "load {} {:016x}", path.display(), fingerprint)
+            }
+            Event::Reorder(order) => write!(f, "reorder {}", order.name()),
+            Event::Orient(orientation) => write!(f, "orient {}", orientation.name()),
+            Event::Count { engine, triangles } => write!(f, "count {} {}", engine, triangles),
+            Event::Update(Update::Insert(u, v)) => write!(f, "+ {} {}", u, v),
+            Event::Update(Update::Delete(u, v)) => write!(f, "- {} {}", u, v),
+            Event::Triangles(n) => write!(f, "triangles {}", n),
+        }
+    }
+}
+
+impl Event {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        let number = |s: &str| s.parse().map_err(|_| format!("invalid number {:?}", s));
+        match (kind, fields.as_slice()) {
+            ("dialect", [delimiter, columns, base, header, comments @ ..]) => {
+                let delimiter = Delimiter::parse(delimiter)
+                    .ok_or_else(|| format!("unknown delimiter {:?}", delimiter))?;
+                let columns = match columns.split_once(',') {
+                    Some((s, d)) => (number(s)? as usize, number(d)? as usize),
+                    None => return Err(format!("invalid columns {:?}", columns)),
+                };
+                let base = IndexBase::parse(base)
+                    .ok_or_else(|| format!("unknown index base {:?}", base))?;
+                Ok(Event::Dialect(Dialect {
+                    delimiter,
+                    comment_prefixes: comments.iter().map(|c| c.to_string()).collect(),
+                    header: *header == "header",
+                    columns,
+                    base,
+                }))
+            }
+            // The path may have spaces in it, so the fingerprint is taken from
+            // the end.
+            ("load", [.., _]) => {
+                let (path, fingerprint) = rest
+                    .rsplit_once(' ')
+                    .ok_or_else(|| format!("invalid load line {:?}", line))?;
+                let fingerprint = u64::from_str_radix(fingerprint, 16)
+                    .map_err(|_| format!("invalid fingerprint {:?}", fingerprint))?;
+                Ok(Event::Load {
+                    path: PathBuf::from(path),
+                    fingerprint,
+                })
+            }
+            ("reorder", [order]) => Order::parse(order)
+                .map(Event::Reorder)
+                .ok_or_else(|| format!("unknown ordering {:?}", order)),
+            ("orient", [orientation]) => Orientation::parse(orientation)
+                .map(Event::Orient)
+                .ok_or_else(|| format!("unknown orientation {:?}", orientation)),
+            ("count", [engine, triangles]) => Ok(Event::Count {
+                engine: engine.to_string(),
+                triangles: number(triangles)?,
+            }),
+            ("+" | "-", _) => Update::parse(line)
+                .map(Event::Update)
+                .ok_or_else(|| format!("invalid update {:?}", line)),
+            ("triangles", [n]) => Ok(Event::Triangles(number(n)?)),
+            _ => Err(format!("unrecognized event {:?}", line)),
+        }
+    }
+}
+
+// Read a whole transcript. Blank lines and lines starting with `#` are
+// skipped.
+pub fn read<R: BufRead>(reader: R) -> Result<Vec<Event>, LoadError> {
+    let mut events = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        events.push(Event::parse(line).map_err(|message| LoadError::Parse {
+            line: i + 1,
+            message,
+        })?);
+    }
+    Ok(events)
+}
+
+// Writes events to a transcript file as they happen.
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "# find-triangles transcript")?;
+        Ok(Self { out })
+    }
+
+    pub fn record(&mut self, event: &Event) -> io::Result<()> {
+        writeln!(self.out, "{}", event)
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// One step of a replay: a graph being oriented, or a graph or result checked
+// against what the transcript recorded for it.
+pub enum Step {
+    Orient(Orientation),
+    Check {
+        what: String,
+        recorded: String,
+        actual: String,
+    },
+}
+
+impl Step {
+    pub fn matches(&self) -> bool {
+        match self {
+            Step::Orient(_) => true,
+            Step::Check {
+                recorded, actual, ..
+            } => recorded == actual,
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Orient(orientation) => write!(f, "orient {}", orientation.name()),
+            Step::Check {
+                what,
+                recorded,
+                actual,
+            } if recorded == actual => write!(f, "{}: {} (matches)", what, actual),
+            Step::Check {
+                what,
+                recorded,
+                actual,
+            } => write!(f, "{}: {} (recorded {})", what, actual, recorded),
+        }
+    }
+}
+
+// Run a recorded session again, checking that each graph and each result
+// comes out the same as it did the first time. Edge lists are read with
+// `read_edges`, which is given each one's path and dialect; index files are
+// loaded directly.
+pub fn replay(
+    events: Vec<Event>,
+    mut read_edges: impl FnMut(&Path, &Dialect) -> Result<Vec<(u64, u64)>, LoadError>,
+) -> Result<Vec<Step>, Failure> {
+    let mut dialect = Dialect::default();
+    let mut order = None;
+    let mut data: Option<Arc<Adjacency>> = None;
+    let mut streamed = IncrementalTriangles::new();
+    let mut steps = Vec::new();
+    let check =
+        |what: String, recorded: &dyn fmt::Display, actual: &dyn fmt::Display| Step::Check {
+            what,
+            recorded: recorded.to_string(),
+            actual: actual.to_string(),
+        };
+    for event in events {
+        match event {
+            Event::Dialect(d) => dialect = d,
+            Event::Reorder(o) => order = Some(o),
+            Event::Orient(orientation) => {
+                let Some(loaded) = &data else {
+                    return Err(Failure::new(
+                        FailureKind::Parse,
+                        "transcript orients before loading a graph",
+                    ));
+                };
+                steps.push(Step::Orient(orientation));
+                data = Some(Arc::new(Oriented::from_adjacency(loaded).into_adjacency()));
+            }
+            Event::Load { path, fingerprint } => {
+                let built = if persist::is_index_file(&path) {
+                    persist::load(&path).map_err(|e| Failure::index(path.display(), e))?
+                } else {
+                    let mut edges = read_edges(&path, &dialect)
+                        .map_err(|e| Failure::load(path.display(), e))?;
+                    if let Some(order) = order.take() {
+                        Relabeling::new(&edges, order).apply(&mut edges);
+                    }
+                    loader::to_adjacency(edges)
+                };
+                steps.push(check(
+                    format!("load {}", path.display()),
+                    &format_args!("{:016x}", fingerprint),
+                    &format_args!("{:016x}", index::fingerprint(&built)),
+                ));
+                data = Some(Arc::new(built));
+            }
+            Event::Count {
+                engine: name,
+                triangles,
+            } => {
+                let Some(engine) = Engine::parse(&name) else {
+                    return Err(Failure::new(
+                        FailureKind::Parse,
+                        format!("unknown engine {:?} in transcript", name),
+                    ));
+                };
+                let Some(data) = &data else {
+                    return Err(Failure::new(
+                        FailureKind::Parse,
+                        "transcript counts before loading a graph",
+                    ));
+                };
+                let stats = GraphStats::collect(data, stats::DEFAULT_TOP_K);
+                steps.push(check(
+                    format!("count {}", name),
+                    &triangles,
+                    &engine::run(&engine, data, &stats)?,
+                ));
+            }
+            Event::Update(update) => {
+                streamed.apply(update);
+            }
+            Event::Triangles(n) => steps.push(check(
+                "streamed triangles".to_string(),
+                &n,
+                &streamed.triangles,
+            )),
+        }
+    }
+    Ok(steps)
+}
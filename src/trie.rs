@@ -0,0 +1,206 @@
+// A trie over tuples of any arity, for relations that don't fit `Index`'s
+// two levels: timestamped edges, RDF triples and the like. Each level is
+// stored as a column: the distinct values under every entry of the level
+// above, one run after another, with the offsets of each entry's run in the
+// level below. For arity two this is exactly a `Csr`.
+//
+//    level 0:  1       2
+//    level 1:  2   3   4
+//    level 2:  5 6 7   5 7
+//
+// holds (1, 2, 5), (1, 2, 6), (1, 3, 7), (2, 4, 5) and (2, 4, 7).
+
+use std::sync::Arc;
+
+use crate::index::{gallop, TrieIterator};
+
+pub struct Trie {
+    levels: Vec<Level>,
+}
+
+struct Level {
+    values: Vec<u64>,
+    // The run under values[i] in the next level is offsets[i]..offsets[i + 1].
+    // Empty for the last level.
+    offsets: Vec<usize>,
+}
+
+impl Trie {
+    // A trie over `tuples`, each with `arity` values, in any order and with
+    // duplicates dropped.
+    pub fn new(arity: usize, mut tuples: Vec<Vec<u64>>) -> Self {
+        assert!(arity > 0, "a trie needs at least one level");
+        assert!(
+            tuples.iter().all(|t| t.len() == arity),
+            "every tuple of a trie must have {} values",
+            arity
+        );
+        tuples.sort_unstable();
+        tuples.dedup();
+        let mut levels: Vec<Level> = (0..arity)
+            .map(|_| Level {
+                values: Vec::new(),
+                offsets: Vec::new(),
+            })
+            .collect();
+        for (i, tuple) in tuples.iter().enumerate() {
+            // The first level at which this tuple parts from the one before;
+            // from there down it starts a new entry at every level.
+            let split = match i {
+                0 => 0,
+                _ => tuple
+                    .iter()
+                    .zip(&tuples[i - 1])
+                    .position(|(a, b)| a != b)
+                    .unwrap_or(arity),
+            };
+            for d in split..arity {
+                if d + 1 < arity {
+                    let start = levels[d + 1].values.len();
+                    levels[d].offsets.push(start);
+                }
+                levels[d].values.push(tuple[d]);
+            }
+        }
+        for d in 0..arity - 1 {
+            let end = levels[d + 1].values.len();
+            levels[d].offsets.push(end);
+        }
+        Self { levels }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.levels.len()
+    }
+
+    // The number of tuples, which is the number of entries at the last level.
+    pub fn len(&self) -> usize {
+        self.levels[self.arity() - 1].values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // A cursor at the start of the first level.
+    pub fn iter(self: &Arc<Self>) -> TrieCursor {
+        TrieCursor {
+            trie: self.clone(),
+            path: vec![Span {
+                start: 0,
+                at: 0,
+                end: self.levels[0].values.len(),
+            }],
+        }
+    }
+
+    // Every tuple, in ascending order.
+    pub fn tuples(&self) -> Vec<Vec<u64>> {
+        let mut tuples = Vec::with_capacity(self.len());
+        let mut tuple = Vec::with_capacity(self.arity());
+        self.collect(0, 0..self.levels[0].values.len(), &mut tuple, &mut tuples);
+        tuples
+    }
+
+    fn collect(
+        &self,
+        d: usize,
+        run: std::ops::Range<usize>,
+        tuple: &mut Vec<u64>,
+        tuples: &mut Vec<Vec<u64>>,
+    ) {
+        let level = &self.levels[d];
+        for i in run {
+            tuple.push(level.values[i]);
+            if d + 1 == self.arity() {
+                tuples.push(tuple.clone());
+            } else {
+                self.collect(d + 1, level.offsets[i]..level.offsets[i + 1], tuple, tuples);
+            }
+            tuple.pop();
+        }
+    }
+}
+
+// Where a cursor is in one level: at an offset within the run it's in.
+#[derive(Clone, Copy)]
+struct Span {
+    start: usize,
+    at: usize,
+    end: usize,
+}
+
+// A trie iterator over a `Trie`, keeping its place at every level from the
+// first down to the one it's at.
+pub struct TrieCursor {
+    trie: Arc<Trie>,
+    path: Vec<Span>,
+}
+
+impl TrieCursor {
+    fn level(&self) -> &Level {
+        &self.trie.levels[self.path.len() - 1]
+    }
+
+    fn span(&mut self) -> &mut Span {
+        self.path
+            .last_mut()
+            .expect("a cursor is always at some level")
+    }
+}
+
+impl TrieIterator for TrieCursor {
+    fn seek(&mut self, v: u64) {
+        let Span { start, at, end } = *self.path.last().unwrap();
+        let run = &self.level().values[start..end];
+        let to = start + gallop(run, at - start, |&x| x < v);
+        self.span().at = to;
+    }
+
+    fn up(&mut self) {
+        // At the top level, there's nothing bound to undo.
+        if self.path.len() > 1 {
+            self.path.pop();
+        }
+    }
+
+    fn down(&mut self) {
+        // At the bottom level, there's no level below.
+        if self.path.len() == self.trie.arity() {
+            return;
+        }
+        let Span { at, end, .. } = *self.path.last().unwrap();
+        // Past the end of the run there's nothing to bind, so the level below
+        // is empty.
+        let (start, end) = if at < end {
+            (self.level().offsets[at], self.level().offsets[at + 1])
+        } else {
+            (0, 0)
+        };
+        self.path.push(Span {
+            start,
+            at: start,
+            end,
+        });
+    }
+
+    fn reset(&mut self) {
+        let span = self.span();
+        span.at = span.start;
+    }
+
+    fn value(&self) -> Option<u64> {
+        let Span { at, end, .. } = *self.path.last().unwrap();
+        (at < end).then(|| self.level().values[at])
+    }
+
+    fn next(&mut self) {
+        self.span().at += 1;
+    }
+
+    // Every level is a plain sorted array of u64s.
+    fn rest(&self) -> Option<&[u64]> {
+        let Span { at, end, .. } = *self.path.last().unwrap();
+        Some(&self.level().values[at.min(end)..end])
+    }
+}
@@ -0,0 +1,220 @@
+// Graphs with a weight on every edge, and queries over them.
+
+use std::{cmp::Ordering, collections::BinaryHeap, thread};
+
+use crate::{
+    index::Adjacency,
+    intersect,
+    parallel::{self, Ranges},
+};
+
+// The usual two-level adjacency, with each neighbor list paired with a list
+// of the weights of those edges.
+pub struct WeightedGraph {
+    pub adjacency: Adjacency,
+    pub weights: Vec<Vec<f64>>,
+}
+
+impl WeightedGraph {
+    // Build the graph from (src, dst, weight) triples. If an edge is listed
+    // more than once, the last weight given for it wins.
+    pub fn new(mut edges: Vec<(u64, u64, f64)>) -> Self {
+        // A stable sort keeps duplicates in input order, so the last of each
+        // run is the one that was listed last.
+        edges.sort_by_key(|&(u, v, _)| (u, v));
+        let mut adjacency: Adjacency = Vec::new();
+        let mut weights: Vec<Vec<f64>> = Vec::new();
+        for (u, v, w) in edges {
+            match adjacency.last_mut() {
+                Some((last, vs)) if *last == u => {
+                    let ws = weights.last_mut().unwrap();
+                    if vs.last() == Some(&v) {
+                        *ws.last_mut().unwrap() = w;
+                    } else {
+                        vs.push(v);
+                        ws.push(w);
+                    }
+                }
+                _ => {
+                    adjacency.push((u, vec![v]));
+                    weights.push(vec![w]);
+                }
+            }
+        }
+        Self { adjacency, weights }
+    }
+}
+
+// How to combine the weights of a triangle's three edges into its weight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Min,
+    // For weights that are probabilities, the chance all three edges exist.
+    Product,
+}
+
+impl Aggregate {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "sum" => Some(Aggregate::Sum),
+            "min" => Some(Aggregate::Min),
+            "product" => Some(Aggregate::Product),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, a: f64, b: f64, c: f64) -> f64 {
+        match self {
+            Aggregate::Sum => a + b + c,
+            Aggregate::Min => a.min(b).min(c),
+            Aggregate::Product => a * b * c,
+        }
+    }
+}
+
+// A triangle and its weight, ordered so that a `BinaryHeap` of them pops the
+// *lightest* first.
+struct Lightest(f64, [u64; 3]);
+
+impl PartialEq for Lightest {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Lightest {}
+
+impl PartialOrd for Lightest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Lightest {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0).then(other.1.cmp(&self.1))
+    }
+}
+
+// The `k` triangles (a, b, c) of R(a, b), S(b, c), T(a, c) with the largest
+// weight, heaviest first. Only the best `k` seen so far are kept while
+// enumerating, so memory stays O(k) however many triangles there are.
+pub fn top_k_triangles(graph: &WeightedGraph, k: usize, agg: Aggregate) -> Vec<(f64, [u64; 3])> {
+    if k == 0 {
+        return Vec::new();
+    }
+    let mut heap: BinaryHeap<Lightest> = BinaryHeap::with_capacity(k + 1);
+    for i in 0..graph.adjacency.len() {
+        for_each_triangle(graph, i, agg, |w, triangle| {
+            let t = Lightest(w, triangle);
+            if heap.len() < k {
+                heap.push(t);
+            } else if heap.peek().is_some_and(|lightest| t < *lightest) {
+                heap.pop();
+                heap.push(t);
+            }
+        });
+    }
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Lightest(w, t)| (w, t))
+        .collect()
+}
+
+// The expected number of triangles in an uncertain graph, whose weights are
+// the probabilities that each edge exists, independently of the others: by
+// linearity of expectation, the sum over the triangles of the chance that all
+// three of their edges exist.
+pub fn expected_triangles(graph: &WeightedGraph, threads: usize, reduction: Reduction) -> f64 {
+    total_weight(graph, Aggregate::Product, threads, reduction)
+}
+
+// How `total_weight` adds up the threads' sums.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reduction {
+    // Each thread adds up the triangles it happens to get, and the threads'
+    // sums are added up after. Floating-point addition isn't associative, so
+    // the last few bits of the total depend on how the work was split.
+    Fast,
+    // Each first-level vertex's triangles are added up on their own, in
+    // order, and the per-vertex sums are combined by a fixed tree over the
+    // vertices. The total is bit-for-bit the same for any number of threads,
+    // at the cost of holding one sum per vertex.
+    Deterministic,
+}
+
+// The total weight of the triangles (a, b, c) of R(a, b), S(b, c), T(a, c),
+// counted on `threads` threads.
+pub fn total_weight(
+    graph: &WeightedGraph,
+    agg: Aggregate,
+    threads: usize,
+    reduction: Reduction,
+) -> f64 {
+    let threads = threads.max(1);
+    let work = Ranges::new(graph.adjacency.len(), threads);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|me| {
+                let work = &work;
+                scope.spawn(move || {
+                    let mut total = 0.0;
+                    let mut partials = Vec::new();
+                    work.run(me, |i| {
+                        let mut sum = 0.0;
+                        for_each_triangle(graph, i, agg, |w, _| sum += w);
+                        match reduction {
+                            Reduction::Fast => total += sum,
+                            Reduction::Deterministic => partials.push((i, sum)),
+                        }
+                    });
+                    (total, partials)
+                })
+            })
+            .collect();
+        let results = workers.into_iter().map(|w| w.join().unwrap());
+        match reduction {
+            Reduction::Fast => results.map(|(total, _)| total).sum(),
+            Reduction::Deterministic => {
+                let mut sums = vec![0.0; graph.adjacency.len()];
+                for (i, sum) in results.flat_map(|(_, partials)| partials) {
+                    sums[i] = sum;
+                }
+                pairwise_sum(&sums)
+            }
+        }
+    })
+}
+
+// Sum by halves, which fixes the order of the additions by position alone
+// (and loses less precision than adding left to right).
+fn pairwise_sum(xs: &[f64]) -> f64 {
+    if xs.len() <= 8 {
+        return xs.iter().sum();
+    }
+    let (left, right) = xs.split_at(xs.len() / 2);
+    pairwise_sum(left) + pairwise_sum(right)
+}
+
+// Call `f` with the weight of each triangle whose `a` is at position `i`.
+fn for_each_triangle(
+    graph: &WeightedGraph,
+    i: usize,
+    agg: Aggregate,
+    mut f: impl FnMut(f64, [u64; 3]),
+) {
+    let data = &graph.adjacency;
+    let (a, a_neighbors) = &data[i];
+    let a_weights = &graph.weights[i];
+    parallel::for_each_b_at(data, i, |bi, pos| {
+        let (b, b_neighbors) = &data[pos];
+        let b_weights = &graph.weights[pos];
+        // Each c comes with where it is in both lists, to pick up the weights
+        // of (a, c) and (b, c).
+        for (x, y) in intersect::common_positions(a_neighbors, b_neighbors) {
+            let w = agg.apply(a_weights[bi], b_weights[y], a_weights[x]);
+            f(w, [*a, *b, a_neighbors[x]]);
+        }
+    });
+}
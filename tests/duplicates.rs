@@ -0,0 +1,75 @@
+// A repeated line in an edge list is one edge, however the graph was loaded
+// and whichever engine counts it. Engines that count over whole lists and
+// ones that seek through them used to disagree when repeats were kept.
+
+use std::{io::Cursor, sync::Arc};
+
+use find_triangles::{
+    batch, hashtrie::HashTrie, index::Adjacency, loader, parallel, roaring::RoaringGraph,
+    skew::HeavyLight, Graph,
+};
+
+const REPEATED: &str = "1 2\n1 2\n2 3\n1 3\n1 3\n2 3\n3 4\n";
+
+fn counts(data: &Adjacency) -> Vec<(&'static str, u64)> {
+    let trie = Arc::new(HashTrie::from_edges(
+        data.iter()
+            .flat_map(|(u, vs)| vs.iter().map(move |v| (*u, *v))),
+    ));
+    let mut hash_trie = 0;
+    find_triangles::for_each_triangle_in(
+        &mut trie.iter(),
+        &mut trie.iter(),
+        &mut trie.iter(),
+        |_, _, _| hash_trie += 1,
+    );
+    vec![
+        (
+            "handwritten",
+            find_triangles::count_triangles(&Graph::from(data)),
+        ),
+        (
+            "batched",
+            batch::count_triangles(data, batch::DEFAULT_BATCH_SIZE),
+        ),
+        (
+            "heavy-light",
+            HeavyLight::new(data, 1).count_triangles(data),
+        ),
+        ("parallel", parallel::count_triangles(data, 2)),
+        ("roaring", RoaringGraph::from(data).count_triangles()),
+        ("hash-trie", hash_trie),
+    ]
+}
+
+#[test]
+fn repeated_edges_are_loaded_once() {
+    let edges = loader::read_edges(Cursor::new(REPEATED), &Default::default()).unwrap();
+    let data = loader::to_adjacency(edges);
+    assert_eq!(data, vec![(1, vec![2, 3]), (2, vec![3]), (3, vec![4])]);
+    for (engine, count) in counts(&data) {
+        assert_eq!(count, 1, "{} counted the triangle {} times", engine, count);
+    }
+}
+
+#[cfg(feature = "loaders")]
+#[test]
+fn the_pipelined_loader_drops_repeats_too() {
+    let data = find_triangles::pipeline::build(Cursor::new(REPEATED), &Default::default()).unwrap();
+    assert_eq!(data, vec![(1, vec![2, 3]), (2, vec![3]), (3, vec![4])]);
+}
+
+#[cfg(feature = "loaders")]
+#[test]
+fn repeated_pairs_in_a_dump_are_loaded_once() {
+    let edges = [(1, 2), (1, 2), (1, 3), (2, 3), (2, 3)];
+    let bytes: Vec<u8> = edges
+        .iter()
+        .flat_map(|&(u, v): &(u32, u32)| u.to_le_bytes().into_iter().chain(v.to_le_bytes()))
+        .collect();
+    let data = find_triangles::dump::build(&bytes).unwrap();
+    assert_eq!(data, vec![(1, vec![2, 3]), (2, vec![3])]);
+    for (engine, count) in counts(&data) {
+        assert_eq!(count, 1, "{} counted the triangle {} times", engine, count);
+    }
+}
@@ -0,0 +1,76 @@
+// The engines and backends the binary picks between, run through the library
+// the way it runs them: every pairing that's allowed comes up with the same
+// count, and the ones that aren't are refused rather than miscounted.
+
+use std::{io::Cursor, sync::Arc};
+
+use find_triangles::{
+    engine::{self, Backend, Engine},
+    index::Adjacency,
+    loader, plan,
+    stats::{self, GraphStats},
+};
+
+// K4 on 1..=4, which has four triangles, plus a pendant edge.
+const K4: &str = "1 2\n1 3\n1 4\n2 3\n2 4\n3 4\n4 5\n";
+
+fn k4() -> Arc<Adjacency> {
+    let edges = loader::read_edges(Cursor::new(K4), &Default::default()).unwrap();
+    Arc::new(loader::to_adjacency(edges))
+}
+
+#[test]
+fn every_engine_agrees() {
+    let data = k4();
+    let stats = GraphStats::collect(&data, stats::DEFAULT_TOP_K);
+    let timings = engine::compare(&data, &stats, None, 2).unwrap();
+    assert_eq!(timings.len(), engine::all(None, 2).len());
+    for t in &timings {
+        assert_eq!(t.triangles, 4, "{} counted {}", t.engine, t.triangles);
+    }
+    assert!(engine::check_agreement(&timings).is_ok());
+    assert_eq!(
+        engine::reduce_disagreement(vec![(1, 2), (2, 3), (1, 3)], None, 2),
+        None
+    );
+}
+
+#[test]
+fn every_backend_agrees() {
+    let data = k4();
+    let stats = GraphStats::collect(&data, stats::DEFAULT_TOP_K);
+    for backend in [
+        Backend::Csr,
+        Backend::Csr32,
+        Backend::Ef,
+        Backend::Roaring,
+        Backend::Auto,
+    ] {
+        for engine in [Engine::Handwritten, Engine::Compiled] {
+            let backend = backend.resolve(&engine, &data, &stats);
+            let counted = engine::run_on(backend, &engine, &data, &stats).unwrap();
+            assert_eq!(
+                counted.triangles,
+                4,
+                "{} on {}",
+                engine.name(),
+                backend.name()
+            );
+            assert_eq!(counted.ef.is_some(), backend == Backend::Ef);
+        }
+    }
+    for backend in [Backend::Csr32, Backend::Ef, Backend::Roaring] {
+        assert!(engine::run_on(backend, &Engine::Generic, &data, &stats).is_err());
+    }
+    let wide = Arc::new(vec![(1, vec![u32::MAX as u64 + 1])]);
+    assert!(!engine::fits_u32(&wide));
+    assert!(engine::run_on(Backend::Csr32, &Engine::Handwritten, &wide, &stats).is_err());
+}
+
+#[test]
+fn participating() {
+    let found = plan::participating(&k4());
+    assert_eq!(found.vertices, vec![1, 2, 3, 4]);
+    // As a, 1 and 2; as b, 2 and 3; as c, 3 and 4.
+    assert_eq!(found.roles, [2, 2, 2]);
+}
@@ -0,0 +1,45 @@
+// Library entry points given arguments they can't work with return an error
+// saying so, rather than panicking, whatever the binary checks first.
+
+use find_triangles::{
+    columnar::{self, UnevenColumns},
+    temporal::{self, EmptyInterval},
+};
+
+#[test]
+fn uneven_columns() {
+    assert_eq!(
+        columnar::from_columns(&[1, 2], &[2]),
+        Err(UnevenColumns(vec![2, 1]))
+    );
+    assert!(columnar::from_weighted_columns(&[1], &[2], &[]).is_err());
+    assert_eq!(
+        columnar::from_columns(&[1, 2], &[2, 3]),
+        Ok(vec![(1, vec![2]), (2, vec![3])])
+    );
+}
+
+#[test]
+fn an_empty_interval() {
+    assert_eq!(temporal::series(vec![(1, 2, 0)], 0), Err(EmptyInterval));
+    assert_eq!(
+        temporal::series(vec![(1, 2, 0)], 10).map(|s| s.len()),
+        Ok(1)
+    );
+}
+
+#[cfg(feature = "random")]
+#[test]
+fn probabilities_out_of_range() {
+    use find_triangles::sparsify::{self, InvalidProbability};
+
+    let data = vec![(1, vec![2, 3]), (2, vec![3])];
+    for p in [-0.5, 1.5, f64::NAN] {
+        assert!(sparsify::sparsify(&data, p, 0).is_err());
+    }
+    assert!(matches!(
+        sparsify::estimate(&data, 0.0, 0),
+        Err(InvalidProbability(p)) if p == 0.0
+    ));
+    assert_eq!(sparsify::estimate(&data, 1.0, 0).unwrap().estimate, 1.0);
+}
@@ -0,0 +1,27 @@
+// The linter looks over any edge list the loader accepts, including ones at
+// the very ends of the id range.
+
+use find_triangles::{
+    lint::{self, Finding},
+    loader::IndexBase,
+};
+
+#[test]
+fn the_largest_id() {
+    let findings = lint::lint(&[(u64::MAX, 0)], IndexBase::Zero);
+    assert!(!findings
+        .iter()
+        .any(|f| matches!(f, Finding::Isolated { .. })));
+}
+
+#[test]
+fn gaps_below_the_largest_id() {
+    let findings = lint::lint(&[(0, 1000)], IndexBase::Zero);
+    assert!(findings.iter().any(|f| matches!(
+        f,
+        Finding::Isolated {
+            unused: 999,
+            range: 1001
+        }
+    )));
+}
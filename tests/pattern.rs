@@ -0,0 +1,92 @@
+// Patterns parsed from text count what they say over a small graph, and
+// malformed ones are reported with the error that names the problem.
+
+use std::sync::Arc;
+
+use find_triangles::{
+    loader,
+    pattern::{Pattern, PatternError, Semantics, Union},
+    plan::Statistics,
+    relation::{EdgeRelation, Relation},
+};
+
+const TRIANGLE: &str = "E(a, b), E(b, c), E(a, c)";
+
+// Every edge of the 4-clique on 1..=4, from the smaller id to the larger, so
+// it has each of its 4 triangles once.
+fn clique() -> EdgeRelation {
+    relation(&[(1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)])
+}
+
+fn relation(edges: &[(u64, u64)]) -> EdgeRelation {
+    EdgeRelation::new(Arc::new(loader::to_adjacency(edges.to_vec())))
+}
+
+fn count(text: &str, relations: &[(&str, &dyn Relation)]) -> Result<u64, PatternError> {
+    let (count, _) = Pattern::parse(text)?.count(relations, &mut Statistics::default())?;
+    Ok(count)
+}
+
+#[test]
+fn projection() {
+    let edges = clique();
+    let relations: [(&str, &dyn Relation); 1] = [("E", &edges)];
+    let every = format!("Q(a, b, c) :- {}", TRIANGLE);
+    assert_eq!(count(&every, &relations), Ok(4));
+    // Only 1 and 2 start a triangle.
+    let starts = format!("Q(a) :- {}", TRIANGLE);
+    assert_eq!(count(&starts, &relations), Ok(2));
+    // (1, 2), (1, 3) and (2, 3) are the first two vertices of one.
+    let pairs = format!("Q(a, b) :- {}", TRIANGLE);
+    assert_eq!(count(&pairs, &relations), Ok(3));
+}
+
+#[test]
+fn repeated_variable() {
+    assert_eq!(
+        Pattern::parse("Q(a) :- E(a, b), E(a, a)"),
+        Err(PatternError::RepeatedVariable {
+            atom: 1,
+            var: "a".to_string()
+        })
+    );
+}
+
+#[test]
+fn only_negated() {
+    let edges = clique();
+    assert_eq!(
+        count("Q(a) :- E(a, b), !E(b, c)", &[("E", &edges)]),
+        Err(PatternError::OnlyNegated("c".to_string()))
+    );
+}
+
+#[test]
+fn syntax_error_in_a_later_rule() {
+    let text = "Q(a) :- E(a, b); Q(a) :- E(a b)";
+    let at = text.rfind('b').unwrap();
+    assert!(matches!(
+        Union::parse(text),
+        Err(PatternError::Syntax { at: offset, .. }) if offset == at
+    ));
+}
+
+#[test]
+fn set_and_bag_unions() {
+    let edges = clique();
+    let r1 = relation(&[(1, 2)]);
+    let r2 = relation(&[(1, 2), (1, 3)]);
+    let relations: [(&str, &dyn Relation); 3] = [("E", &edges), ("R1", &r1), ("R2", &r2)];
+    let union = Union::parse(
+        "Q(a, b, c) :- R1(a, b), E(b, c), E(a, c); Q(a, b, c) :- R2(a, b), E(b, c), E(a, c)",
+    )
+    .unwrap();
+    // R1 closes (1, 2, 3) and (1, 2, 4), and R2 those and (1, 3, 4).
+    let count = |semantics| {
+        union
+            .count(&relations, semantics, &mut Statistics::default())
+            .map(|(count, _)| count)
+    };
+    assert_eq!(count(Semantics::Set), Ok(3));
+    assert_eq!(count(Semantics::Bag), Ok(5));
+}
@@ -0,0 +1,85 @@
+// A saved index loads back as the same graph, in either byte order, and a
+// reader refuses files it can't read correctly rather than guessing.
+
+use find_triangles::{
+    index::Adjacency,
+    persist::{self, ByteOrder, Csr, FormatError},
+};
+
+fn graph() -> Adjacency {
+    vec![
+        (1, vec![2, 3]),
+        (2, vec![3, 4]),
+        (3, vec![]),
+        (7, vec![1, 9]),
+    ]
+}
+
+fn written(data: &Adjacency, order: ByteOrder) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    persist::write_index_in(data, &mut bytes, order).unwrap();
+    bytes
+}
+
+#[test]
+fn save_then_load() {
+    let path = std::env::temp_dir().join(format!("persist-{}.idx", std::process::id()));
+    persist::save(&graph(), &path).unwrap();
+    let loaded = persist::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap(), graph());
+}
+
+#[test]
+fn big_endian() {
+    let bytes = written(&graph(), ByteOrder::Big);
+    let csr = Csr::parse(&bytes).unwrap();
+    assert_eq!(csr.byte_order(), ByteOrder::Big);
+    assert_eq!(csr.to_adjacency(), graph());
+    assert_ne!(bytes, written(&graph(), ByteOrder::Little));
+}
+
+#[test]
+fn newer_version() {
+    let mut bytes = written(&graph(), ByteOrder::Little);
+    bytes[8..12].copy_from_slice(&(persist::VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        Csr::parse(&bytes),
+        Err(FormatError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn unknown_required_flag() {
+    let mut bytes = written(&graph(), ByteOrder::Little);
+    bytes[12..16].copy_from_slice(&(1_u32 << 31).to_le_bytes());
+    assert!(matches!(
+        Csr::parse(&bytes),
+        Err(FormatError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn unknown_optional_flag() {
+    let mut bytes = written(&graph(), ByteOrder::Little);
+    bytes[12..16].copy_from_slice(&1_u32.to_le_bytes());
+    assert_eq!(Csr::parse(&bytes).unwrap().to_adjacency(), graph());
+}
+
+#[test]
+fn unsorted_targets() {
+    let bytes = written(&vec![(1, vec![3, 2])], ByteOrder::Little);
+    assert!(matches!(Csr::parse(&bytes), Err(FormatError::Corrupt(_))));
+}
+
+#[test]
+fn duplicate_targets() {
+    let bytes = written(&vec![(1, vec![2, 2, 3])], ByteOrder::Little);
+    assert!(matches!(Csr::parse(&bytes), Err(FormatError::Corrupt(_))));
+}
+
+#[test]
+fn unsorted_sources() {
+    let bytes = written(&vec![(2, vec![3]), (1, vec![3])], ByteOrder::Little);
+    assert!(matches!(Csr::parse(&bytes), Err(FormatError::Corrupt(_))));
+}
@@ -0,0 +1,27 @@
+// A roaring cursor that steps down from past the end of the first level
+// binds nothing, and the level it lands on stays empty however it's moved
+// after, including on a reset.
+
+use std::{io::Cursor, sync::Arc};
+
+use find_triangles::{index::TrieIterator, loader, roaring::RoaringGraph};
+
+#[test]
+fn down_past_the_last_set() {
+    let edges = loader::read_edges(Cursor::new("1 2\n1 3\n2 3\n"), &Default::default()).unwrap();
+    let graph = Arc::new(RoaringGraph::from(&loader::to_adjacency(edges)));
+    let mut it = graph.iter();
+    it.seek(u64::MAX);
+    assert_eq!(it.value(), None);
+    it.down();
+    assert_eq!(it.value(), None);
+    it.reset();
+    assert_eq!(it.value(), None);
+    it.seek(1);
+    assert_eq!(it.value(), None);
+    it.next();
+    assert_eq!(it.value(), None);
+    it.up();
+    it.reset();
+    assert_eq!(it.value(), Some(1));
+}
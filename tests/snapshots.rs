@@ -0,0 +1,24 @@
+// The CLI's JSON outputs over the built-in corpus, checked against the
+// snapshots committed in `tests/snapshots`. After a deliberate change to a
+// report, rewrite them with
+// `cargo run --features cli -- --update-snapshots tests/snapshots`.
+
+use std::path::Path;
+
+use find_triangles::snapshot;
+
+#[test]
+fn outputs_match_snapshots() {
+    let exe = Path::new(env!("CARGO_BIN_EXE_find-triangles"));
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let mismatches = snapshot::check(exe, &dir).unwrap();
+    for m in &mismatches {
+        eprintln!("{}: expected {:?}, got {:?}", m.case, m.expected, m.actual);
+    }
+    assert!(
+        mismatches.is_empty(),
+        "{} of {} snapshots differ",
+        mismatches.len(),
+        snapshot::CASES.len()
+    );
+}
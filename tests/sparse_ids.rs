@@ -0,0 +1,75 @@
+// Graphs whose ids are spread far apart: a handful of vertices numbered in
+// the trillions. Anything that sizes an array by the largest id would try to
+// allocate terabytes for them, so these check that each count is the same as
+// over the same graph numbered from zero.
+
+use std::sync::Arc;
+
+use find_triangles::{columnar, loader, parallel, pool::Pool, skew::HeavyLight, Graph};
+
+const SPREAD: u64 = 25_000_000_000;
+
+// Every edge between 40 vertices whose ids differ in a few bits, so the
+// graph has plenty of triangles and 4-cycles.
+fn dense() -> Vec<(u64, u64)> {
+    (0..40_u64)
+        .flat_map(|u| (0..40_u64).map(move |v| (u, v)))
+        .filter(|&(u, v)| u != v && (u ^ v).count_ones() <= 2)
+        .collect()
+}
+
+fn sparse() -> Vec<(u64, u64)> {
+    dense()
+        .into_iter()
+        .map(|(u, v)| (u * SPREAD + 7, v * SPREAD + 7))
+        .collect()
+}
+
+#[test]
+fn four_cycles() {
+    let count = find_triangles::count_four_cycles(&Graph::from_edges(dense()));
+    assert!(count > 0);
+    assert_eq!(
+        find_triangles::count_four_cycles(&Graph::from_edges(sparse())),
+        count
+    );
+}
+
+#[test]
+fn heavy_light_with_every_vertex_heavy() {
+    let (dense, sparse) = (
+        loader::to_adjacency(dense()),
+        loader::to_adjacency(sparse()),
+    );
+    let count = find_triangles::count_triangles(&Graph::from(&dense));
+    assert!(count > 0);
+    assert_eq!(HeavyLight::new(&sparse, 1).count_triangles(&sparse), count);
+}
+
+#[test]
+fn triangles_per_vertex() {
+    let (dense, sparse) = (
+        loader::to_adjacency(dense()),
+        loader::to_adjacency(sparse()),
+    );
+    let expected: Vec<(u64, u64)> = parallel::triangles_per_vertex(&dense, 2)
+        .into_iter()
+        .map(|(u, c)| (u * SPREAD + 7, c))
+        .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(parallel::triangles_per_vertex(&sparse, 2), expected);
+    let columns = columnar::triangles_per_vertex(&sparse, 2);
+    assert_eq!(
+        columns
+            .vertex
+            .into_iter()
+            .zip(columns.triangles)
+            .collect::<Vec<_>>(),
+        expected
+    );
+    let pool = Pool::new();
+    assert_eq!(
+        pool.triangles_per_vertex(&Arc::new(sparse), 2).unwrap(),
+        expected
+    );
+}